@@ -1,4 +1,4 @@
-use image::{GenericImageView, ImageFormat};
+use image::{DynamicImage, GenericImageView, ImageFormat, RgbaImage};
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
 use std::collections::HashMap;
@@ -7,7 +7,17 @@ use std::io::BufReader;
 use exif::Reader;
 use crate::utils::file_metadata::get_file_metadata;
 use crate::utils::compression::CompressionLevel;
-use crate::utils::path_utils::generate_output_path;
+use crate::utils::path_utils::{collect_image_files, generate_output_path};
+use crate::utils::drawing::{
+    anchor_position, apply_opacity, draw_thick_line, draw_thick_polyline, load_font,
+    line_height, parse_hex_color, render_text, rotate_expanded, trim_transparent,
+};
+use crate::utils::image_quality;
+use crate::utils::image_hash::{hamming_distance, HashAlgorithm};
+use ab_glyph::FontVec;
+use imageproc::drawing::{draw_filled_circle_mut, draw_filled_ellipse_mut, draw_filled_rect_mut, draw_polygon_mut};
+use imageproc::point::Point;
+use imageproc::rect::Rect;
 // use rmbg::Rmbg;  // Temporarily disabled - incompatible with current ort versions
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -182,6 +192,20 @@ pub async fn flip_image(input_path: String, output_path: String, direction: Stri
     .map_err(|e| format!("Task failed: {}", e))?
 }
 
+/// Maps a user-facing format name to an `ImageFormat`
+fn parse_image_format(format: &str) -> Result<ImageFormat, String> {
+    match format.to_lowercase().as_str() {
+        "png" => Ok(ImageFormat::Png),
+        "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
+        "webp" => Ok(ImageFormat::WebP),
+        "gif" => Ok(ImageFormat::Gif),
+        "bmp" => Ok(ImageFormat::Bmp),
+        "ico" => Ok(ImageFormat::Ico),
+        "tiff" => Ok(ImageFormat::Tiff),
+        _ => Err(format!("Unsupported format: {}", format)),
+    }
+}
+
 #[tauri::command]
 pub async fn convert_image(
     input_path: String,
@@ -192,16 +216,7 @@ pub async fn convert_image(
         let img = image::open(&input_path)
             .map_err(|e| format!("Failed to open image: {}", e))?;
 
-        let output_format = match format.to_lowercase().as_str() {
-            "png" => ImageFormat::Png,
-            "jpg" | "jpeg" => ImageFormat::Jpeg,
-            "webp" => ImageFormat::WebP,
-            "gif" => ImageFormat::Gif,
-            "bmp" => ImageFormat::Bmp,
-            "ico" => ImageFormat::Ico,
-            "tiff" => ImageFormat::Tiff,
-            _ => return Err(format!("Unsupported format: {}", format)),
-        };
+        let output_format = parse_image_format(&format)?;
 
         img.save_with_format(&output_path, output_format)
             .map_err(|e| format!("Failed to save image: {}", e))?;
@@ -477,3 +492,977 @@ pub async fn estimate_compressed_size(
     .await
     .map_err(|e| format!("Task failed: {}", e))?
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WatermarkParams {
+    kind: String,               // "text" or "image"
+    text: Option<String>,
+    font_path: Option<String>,
+    font_size: Option<f32>,     // Pixels, defaults to 1/20 of the image width
+    color: Option<String>,      // Hex, e.g. "#FFFFFF"
+    image_path: Option<String>, // PNG logo for "image" watermarks
+    scale: Option<f32>,         // Logo width as a fraction of the image width
+    opacity: f32,               // 0.0 - 1.0
+    rotation: f32,              // Degrees clockwise
+    position: String,           // Anchor ("top-left" ... "bottom-right", "center") or "tiled"
+    margin: Option<u32>,
+    spacing: Option<u32>,       // Gap between tiles when tiled
+}
+
+/// Encodes an image as PNG into memory for previews
+fn encode_png(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    {
+        let mut cursor = std::io::Cursor::new(&mut buffer);
+        img.write_to(&mut cursor, ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode image: {}", e))?;
+    }
+    Ok(buffer)
+}
+
+/// Drops the alpha channel again if the source image had none, so JPEG output keeps working
+fn restore_color_type(canvas: RgbaImage, original: &DynamicImage) -> DynamicImage {
+    let result = DynamicImage::ImageRgba8(canvas);
+    if original.color().has_alpha() {
+        result
+    } else {
+        DynamicImage::ImageRgb8(result.to_rgb8())
+    }
+}
+
+/// Builds the watermark stamp (text or logo) with opacity and rotation applied
+fn build_watermark_stamp(img: &DynamicImage, params: &WatermarkParams) -> Result<RgbaImage, String> {
+    let mut stamp = match params.kind.as_str() {
+        "text" => {
+            let text = params.text.as_deref().filter(|t| !t.is_empty())
+                .ok_or("Watermark text cannot be empty")?;
+            let font = load_font(params.font_path.as_deref())?;
+            let font_size = params.font_size.unwrap_or((img.width() as f32 / 20.0).max(12.0));
+            let color = parse_hex_color(params.color.as_deref().unwrap_or("#FFFFFF"))?;
+            render_text(text, &font, font_size, color)
+        }
+        "image" => {
+            let logo_path = params.image_path.as_deref().ok_or("No watermark image selected")?;
+            let logo = image::open(logo_path)
+                .map_err(|e| format!("Failed to open watermark image: {}", e))?;
+            let scale = params.scale.unwrap_or(0.2).clamp(0.01, 1.0);
+            let target_width = ((img.width() as f32 * scale).round() as u32).max(1);
+            let target_height = ((logo.height() as f32 * target_width as f32 / logo.width() as f32).round() as u32).max(1);
+            logo.resize_exact(target_width, target_height, image::imageops::FilterType::Lanczos3)
+                .to_rgba8()
+        }
+        _ => return Err("Watermark kind must be 'text' or 'image'".to_string()),
+    };
+
+    apply_opacity(&mut stamp, params.opacity);
+    Ok(rotate_expanded(&stamp, params.rotation))
+}
+
+/// Overlays the watermark onto an image, either anchored or tiled
+fn apply_watermark(img: &DynamicImage, params: &WatermarkParams) -> Result<DynamicImage, String> {
+    let stamp = build_watermark_stamp(img, params)?;
+    let mut canvas = img.to_rgba8();
+    let (width, height) = canvas.dimensions();
+    let margin = params.margin.unwrap_or(20);
+
+    if params.position == "tiled" {
+        let spacing = params.spacing.unwrap_or(stamp.width() / 2) as i64;
+        let step_x = stamp.width() as i64 + spacing;
+        let step_y = stamp.height() as i64 + spacing;
+
+        // Offset every other row by half a tile for a brick pattern
+        let mut row = 0;
+        let mut y = -(stamp.height() as i64) / 2;
+        while y < height as i64 {
+            let mut x = if row % 2 == 0 { 0 } else { -step_x / 2 };
+            while x < width as i64 {
+                image::imageops::overlay(&mut canvas, &stamp, x, y);
+                x += step_x;
+            }
+            y += step_y;
+            row += 1;
+        }
+    } else {
+        let (x, y) = anchor_position(&params.position, (width, height), stamp.dimensions(), margin)?;
+        image::imageops::overlay(&mut canvas, &stamp, x, y);
+    }
+
+    Ok(restore_color_type(canvas, img))
+}
+
+#[tauri::command]
+pub async fn watermark_image_preview(
+    input_path: String,
+    watermark: WatermarkParams,
+) -> Result<Vec<u8>, String> {
+    tokio::task::spawn_blocking(move || {
+        let img = image::open(&input_path)
+            .map_err(|e| format!("Failed to open image: {}", e))?;
+
+        let watermarked = apply_watermark(&img, &watermark)?;
+
+        encode_png(&watermarked)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn watermark_image(
+    input_path: String,
+    output_path: String,
+    watermark: WatermarkParams,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let img = image::open(&input_path)
+            .map_err(|e| format!("Failed to open image: {}", e))?;
+
+        let watermarked = apply_watermark(&img, &watermark)?;
+
+        watermarked.save(&output_path)
+            .map_err(|e| format!("Failed to save watermarked image: {}", e))?;
+
+        Ok::<String, String>("Watermark applied successfully".to_string())
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Watermarks a batch of images, writing each next to its source with a "_watermarked" suffix
+#[tauri::command]
+pub async fn watermark_images(
+    input_paths: Vec<String>,
+    watermark: WatermarkParams,
+) -> Result<Vec<String>, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut output_paths = Vec::new();
+
+        for input_path in input_paths {
+            let img = image::open(&input_path)
+                .map_err(|e| format!("Failed to open image {}: {}", input_path, e))?;
+
+            let watermarked = apply_watermark(&img, &watermark)?;
+
+            let extension = std::path::Path::new(&input_path)
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("png")
+                .to_lowercase();
+            let output_path = generate_output_path(&input_path, "watermarked", &extension);
+
+            watermarked.save(&output_path)
+                .map_err(|e| format!("Failed to save watermarked image {}: {}", output_path, e))?;
+            output_paths.push(output_path);
+        }
+
+        Ok::<Vec<String>, String>(output_paths)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// A vector shape drawn onto an image, in image pixel coordinates
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Annotation {
+    Arrow {
+        from: (f32, f32),
+        to: (f32, f32),
+        color: String,
+        thickness: f32,
+    },
+    Rectangle {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: String,
+        thickness: f32,
+        fill: Option<String>,
+    },
+    Ellipse {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: String,
+        thickness: f32,
+        fill: Option<String>,
+    },
+    Freehand {
+        points: Vec<(f32, f32)>,
+        color: String,
+        thickness: f32,
+    },
+    Text {
+        x: f32,
+        y: f32,
+        text: String,
+        color: String,
+        font_size: f32,
+        background: Option<String>,
+    },
+    Marker {
+        x: f32,
+        y: f32,
+        number: u32,
+        color: String,
+        radius: f32,
+    },
+}
+
+/// A box given by a corner and a possibly negative size (dragged up or left) as its
+/// top-left corner and positive size
+fn normalize_box(x: f32, y: f32, width: f32, height: f32) -> (f32, f32, f32, f32) {
+    (x.min(x + width), y.min(y + height), width.abs(), height.abs())
+}
+
+/// Area an annotation can draw into, as (left, top, right, bottom) in image pixels
+fn annotation_bounds(annotation: &Annotation, font: Option<&FontVec>) -> (f32, f32, f32, f32) {
+    let around = |points: &[(f32, f32)], margin: f32| {
+        points.iter().fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |(left, top, right, bottom), &(x, y)| {
+            (left.min(x - margin), top.min(y - margin), right.max(x + margin), bottom.max(y + margin))
+        })
+    };
+
+    match annotation {
+        // The arrowhead spreads at most 0.6 x max(thickness * 4, 10) either side of the shaft
+        Annotation::Arrow { from, to, thickness, .. } => around(&[*from, *to], thickness * 3.0 + 8.0),
+        Annotation::Rectangle { x, y, width, height, thickness, .. }
+        | Annotation::Ellipse { x, y, width, height, thickness, .. } => {
+            let (x, y, width, height) = normalize_box(*x, *y, *width, *height);
+            around(&[(x, y), (x + width, y + height)], thickness / 2.0 + 2.0)
+        }
+        Annotation::Freehand { points, thickness, .. } => around(points, thickness / 2.0 + 2.0),
+        Annotation::Text { x, y, text, font_size, .. } => {
+            let (width, height) = match font {
+                Some(font) => (
+                    imageproc::drawing::text_size(*font_size, font, text).0 as f32 + font_size / 4.0,
+                    line_height(font, *font_size) as f32,
+                ),
+                None => (0.0, 0.0),
+            };
+            let padding = font_size / 4.0 + 1.0;
+            around(&[(*x, *y), (x + width, y + height)], padding)
+        }
+        // The number is drawn at 1.2 x the radius, so a long one can stick out of the circle
+        Annotation::Marker { x, y, number, radius, .. } => {
+            let digits = number.to_string().len() as f32;
+            around(&[(*x, *y)], radius * (1.0 + 0.6 * digits) + 2.0)
+        }
+    }
+}
+
+/// Draws an annotation onto a layer whose top-left corner sits at `origin` in the image
+fn draw_annotation(
+    layer: &mut RgbaImage,
+    annotation: &Annotation,
+    font: Option<&FontVec>,
+    origin: (f32, f32),
+) -> Result<(), String> {
+    let at = |(x, y): (f32, f32)| (x - origin.0, y - origin.1);
+
+    match annotation {
+        Annotation::Arrow { from, to, color, thickness } => {
+            let color = parse_hex_color(color)?;
+            let (from, to) = (&at(*from), &at(*to));
+            let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+            let length = dx.hypot(dy);
+            if length < 1.0 {
+                return Ok(());
+            }
+
+            // Arrowhead scales with line thickness but never exceeds half the shaft
+            let head_length = (thickness * 4.0).max(10.0).min(length / 2.0);
+            let head_width = head_length * 0.6;
+            let (ux, uy) = (dx / length, dy / length);
+            let base = (to.0 - ux * head_length, to.1 - uy * head_length);
+
+            draw_thick_line(layer, *from, base, *thickness, color);
+            let head = [
+                Point::new(to.0.round() as i32, to.1.round() as i32),
+                Point::new((base.0 - uy * head_width).round() as i32, (base.1 + ux * head_width).round() as i32),
+                Point::new((base.0 + uy * head_width).round() as i32, (base.1 - ux * head_width).round() as i32),
+            ];
+            // Very short arrows round to a degenerate head, which draw_polygon_mut rejects
+            if head[0] != head[2] {
+                draw_polygon_mut(layer, &head, color);
+            }
+        }
+        Annotation::Rectangle { x, y, width, height, color, thickness, fill } => {
+            let (x, y, width, height) = normalize_box(*x, *y, *width, *height);
+            let (x, y) = at((x, y));
+            if let Some(fill) = fill {
+                let rect = Rect::at(x.round() as i32, y.round() as i32)
+                    .of_size(width.round().max(1.0) as u32, height.round().max(1.0) as u32);
+                draw_filled_rect_mut(layer, rect, parse_hex_color(fill)?);
+            }
+            let corners = [(x, y), (x + width, y), (x + width, y + height), (x, y + height)];
+            draw_thick_polyline(layer, &corners, *thickness, parse_hex_color(color)?, true);
+        }
+        Annotation::Ellipse { x, y, width, height, color, thickness, fill } => {
+            let (x, y, width, height) = normalize_box(*x, *y, *width, *height);
+            let (x, y) = at((x, y));
+            let (rx, ry) = (width / 2.0, height / 2.0);
+            let center = (x + rx, y + ry);
+            if let Some(fill) = fill {
+                draw_filled_ellipse_mut(
+                    layer,
+                    (center.0.round() as i32, center.1.round() as i32),
+                    rx.round() as i32,
+                    ry.round() as i32,
+                    parse_hex_color(fill)?,
+                );
+            }
+
+            // Approximate the outline with enough segments to look smooth at this size
+            let segments = ((rx + ry) * 0.5).clamp(24.0, 360.0) as usize;
+            let outline: Vec<(f32, f32)> = (0..segments)
+                .map(|i| {
+                    let t = i as f32 / segments as f32 * std::f32::consts::TAU;
+                    (center.0 + rx * t.cos(), center.1 + ry * t.sin())
+                })
+                .collect();
+            draw_thick_polyline(layer, &outline, *thickness, parse_hex_color(color)?, true);
+        }
+        Annotation::Freehand { points, color, thickness } => {
+            let color = parse_hex_color(color)?;
+            let points: Vec<(f32, f32)> = points.iter().map(|&point| at(point)).collect();
+            if let [point] = points.as_slice() {
+                draw_thick_line(layer, *point, *point, *thickness, color);
+            } else {
+                draw_thick_polyline(layer, &points, *thickness, color, false);
+            }
+        }
+        Annotation::Text { x, y, text, color, font_size, background } => {
+            let font = font.ok_or("No font available for text annotations")?;
+            let label = render_text(text, font, *font_size, parse_hex_color(color)?);
+            let padding = (font_size / 4.0).round() as i64;
+            let (x, y) = at((*x, *y));
+            let (left, top) = (x.round() as i64, y.round() as i64);
+
+            if let Some(background) = background {
+                let rect = Rect::at((left - padding) as i32, (top - padding) as i32).of_size(
+                    label.width() + 2 * padding as u32,
+                    label.height() + 2 * padding as u32,
+                );
+                draw_filled_rect_mut(layer, rect, parse_hex_color(background)?);
+            }
+            image::imageops::overlay(layer, &label, left, top);
+        }
+        Annotation::Marker { x, y, number, color, radius } => {
+            let font = font.ok_or("No font available for numbered markers")?;
+            let (x, y) = at((*x, *y));
+            let center = (x.round() as i32, y.round() as i32);
+            draw_filled_circle_mut(layer, center, radius.round() as i32, parse_hex_color(color)?);
+
+            let font_size = radius * 1.2;
+            let label = render_text(&number.to_string(), font, font_size, image::Rgba([255, 255, 255, 255]));
+            let label = trim_transparent(&label);
+            let label_x = center.0 as i64 - label.width() as i64 / 2;
+            let label_y = center.1 as i64 - label.height() as i64 / 2;
+            image::imageops::overlay(layer, &label, label_x, label_y);
+        }
+    }
+
+    Ok(())
+}
+
+/// Draws all annotations in order, compositing each shape so translucent colours blend once
+fn apply_annotations(
+    img: &DynamicImage,
+    annotations: &[Annotation],
+    font_path: Option<&str>,
+) -> Result<DynamicImage, String> {
+    let needs_font = annotations
+        .iter()
+        .any(|a| matches!(a, Annotation::Text { .. } | Annotation::Marker { .. }));
+    let font = if needs_font { Some(load_font(font_path)?) } else { None };
+
+    let mut canvas = img.to_rgba8();
+    let (width, height) = canvas.dimensions();
+
+    for annotation in annotations {
+        // Each shape gets a layer just covering it (clipped to the image)
+        let (left, top, right, bottom) = annotation_bounds(annotation, font.as_ref());
+        let (left, top) = (left.floor().max(0.0), top.floor().max(0.0));
+        let (right, bottom) = (right.ceil().min(width as f32), bottom.ceil().min(height as f32));
+        let (layer_width, layer_height) = ((right - left).max(0.0) as u32, (bottom - top).max(0.0) as u32);
+
+        let mut layer = RgbaImage::new(layer_width, layer_height);
+        draw_annotation(&mut layer, annotation, font.as_ref(), (left, top))?;
+        image::imageops::overlay(&mut canvas, &layer, left as i64, top as i64);
+    }
+
+    Ok(restore_color_type(canvas, img))
+}
+
+#[tauri::command]
+pub async fn annotate_image_preview(
+    input_path: String,
+    annotations: Vec<Annotation>,
+    font_path: Option<String>,
+) -> Result<Vec<u8>, String> {
+    tokio::task::spawn_blocking(move || {
+        let img = image::open(&input_path)
+            .map_err(|e| format!("Failed to open image: {}", e))?;
+
+        let annotated = apply_annotations(&img, &annotations, font_path.as_deref())?;
+
+        encode_png(&annotated)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn annotate_image(
+    input_path: String,
+    output_path: String,
+    annotations: Vec<Annotation>,
+    font_path: Option<String>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let img = image::open(&input_path)
+            .map_err(|e| format!("Failed to open image: {}", e))?;
+
+        let annotated = apply_annotations(&img, &annotations, font_path.as_deref())?;
+
+        annotated.save(&output_path)
+            .map_err(|e| format!("Failed to save annotated image: {}", e))?;
+
+        Ok::<String, String>("Image annotated successfully".to_string())
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImageComparison {
+    width: u32,
+    height: u32,
+    psnr: f64,
+    ssim: f64,
+    perceptual_distance: f64, // Butteraugli-like: below 1.0 is visually identical
+    max_delta_e: f64,
+    heatmap_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QualityTargetResult {
+    output_path: String,
+    file_size: u64,
+    quality: u8,
+    psnr: f64,
+    ssim: f64,
+}
+
+/// Opens both images for comparison, requiring matching dimensions
+fn open_comparison_pair(reference_path: &str, distorted_path: &str) -> Result<(image::RgbImage, image::RgbImage), String> {
+    let reference = image::open(reference_path)
+        .map_err(|e| format!("Failed to open reference image: {}", e))?
+        .to_rgb8();
+    let distorted = image::open(distorted_path)
+        .map_err(|e| format!("Failed to open comparison image: {}", e))?
+        .to_rgb8();
+
+    if reference.dimensions() != distorted.dimensions() {
+        return Err(format!(
+            "Images must have the same dimensions ({}x{} vs {}x{})",
+            reference.width(), reference.height(), distorted.width(), distorted.height()
+        ));
+    }
+
+    Ok((reference, distorted))
+}
+
+/// Encodes RGB pixels as JPEG into memory
+fn encode_jpeg(rgb_img: &image::RgbImage, quality: u8) -> Result<Vec<u8>, String> {
+    use image::codecs::jpeg::JpegEncoder;
+
+    let mut buffer = Vec::new();
+    let mut encoder = JpegEncoder::new_with_quality(&mut buffer, quality);
+    encoder.encode(
+        rgb_img.as_raw(),
+        rgb_img.width(),
+        rgb_img.height(),
+        image::ColorType::Rgb8.into(),
+    )
+    .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+
+    Ok(buffer)
+}
+
+/// Compares two images with PSNR, SSIM and a perceptual distance, optionally writing a diff heatmap PNG
+#[tauri::command]
+pub async fn compare_images(
+    reference_path: String,
+    distorted_path: String,
+    heatmap_path: Option<String>,
+) -> Result<ImageComparison, String> {
+    tokio::task::spawn_blocking(move || {
+        let (reference, distorted) = open_comparison_pair(&reference_path, &distorted_path)?;
+
+        let (scores, delta_e) = image_quality::compare(&reference, &distorted);
+
+        if let Some(ref path) = heatmap_path {
+            image_quality::render_heatmap(&reference, &delta_e)
+                .save_with_format(path, ImageFormat::Png)
+                .map_err(|e| format!("Failed to save heatmap: {}", e))?;
+        }
+
+        Ok::<ImageComparison, String>(ImageComparison {
+            width: reference.width(),
+            height: reference.height(),
+            psnr: scores.psnr,
+            ssim: scores.ssim,
+            perceptual_distance: scores.perceptual_distance,
+            max_delta_e: scores.max_delta_e,
+            heatmap_path,
+        })
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn compare_images_heatmap_preview(
+    reference_path: String,
+    distorted_path: String,
+) -> Result<Vec<u8>, String> {
+    tokio::task::spawn_blocking(move || {
+        let (reference, distorted) = open_comparison_pair(&reference_path, &distorted_path)?;
+
+        let delta_e = image_quality::delta_e_map(&reference, &distorted);
+        let heatmap = image_quality::render_heatmap(&reference, &delta_e);
+
+        encode_png(&DynamicImage::ImageRgb8(heatmap))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Finds the smallest JPEG encode whose SSIM against the original stays at or above `min_ssim`
+#[tauri::command]
+pub async fn compress_image_to_quality(
+    input_path: String,
+    output_path: String,
+    min_ssim: f64,
+) -> Result<QualityTargetResult, String> {
+    tokio::task::spawn_blocking(move || {
+        let img = image::open(&input_path)
+            .map_err(|e| format!("Failed to open image: {}", e))?;
+        let original = img.to_rgb8();
+
+        // Returns the encoded bytes with their SSIM and PSNR against the original
+        let evaluate = |quality: u8| -> Result<(Vec<u8>, f64, f64), String> {
+            let encoded = encode_jpeg(&original, quality)?;
+            let decoded = image::load_from_memory_with_format(&encoded, ImageFormat::Jpeg)
+                .map_err(|e| format!("Failed to decode JPEG: {}", e))?
+                .to_rgb8();
+            let ssim = image_quality::ssim(&original, &decoded);
+            let psnr = image_quality::psnr(&original, &decoded);
+            Ok((encoded, ssim, psnr))
+        };
+
+        let mut best = evaluate(100)?;
+        let mut best_quality = 100;
+        if best.1 < min_ssim {
+            return Err(format!(
+                "Even maximum JPEG quality only reaches SSIM {:.4}, below the target {:.4}",
+                best.1, min_ssim
+            ));
+        }
+
+        // SSIM grows (almost) monotonically with quality, so binary search for the lowest passing setting
+        let (mut low, mut high) = (1u8, 99u8);
+        while low <= high {
+            let quality = low + (high - low) / 2;
+            let candidate = evaluate(quality)?;
+            if candidate.1 >= min_ssim {
+                if candidate.0.len() <= best.0.len() {
+                    best = candidate;
+                    best_quality = quality;
+                }
+                if quality == 1 {
+                    break;
+                }
+                high = quality - 1;
+            } else {
+                low = quality + 1;
+            }
+        }
+
+        std::fs::write(&output_path, &best.0)
+            .map_err(|e| format!("Failed to write output file: {}", e))?;
+
+        Ok::<QualityTargetResult, String>(QualityTargetResult {
+            output_path,
+            file_size: best.0.len() as u64,
+            quality: best_quality,
+            psnr: best.2,
+            ssim: best.1,
+        })
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateImage {
+    path: String,
+    width: u32,
+    height: u32,
+    file_size: u64,
+    distance: u32, // Hamming distance to the best candidate
+    is_best: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuplicateScanResult {
+    scanned: usize,
+    skipped: Vec<String>,
+    groups: Vec<Vec<DuplicateImage>>,
+}
+
+/// Fingerprint of a single scanned image
+struct HashedImage {
+    path: String,
+    hash: u64,
+    width: u32,
+    height: u32,
+    file_size: u64,
+}
+
+/// Decodes and hashes images across all available cores; unreadable files are returned separately
+fn hash_images(paths: &[String], algorithm: HashAlgorithm) -> (Vec<HashedImage>, Vec<String>) {
+    let workers = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    let chunk_size = paths.len().div_ceil(workers).max(1);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|path| {
+                            let img = image::open(path).map_err(|_| path.clone())?;
+                            let file_size = get_file_metadata(path).map(|m| m.size).unwrap_or(0);
+                            Ok(HashedImage {
+                                path: path.clone(),
+                                hash: algorithm.hash(&img),
+                                width: img.width(),
+                                height: img.height(),
+                                file_size,
+                            })
+                        })
+                        .collect::<Vec<Result<HashedImage, String>>>()
+                })
+            })
+            .collect();
+
+        let mut hashed = Vec::new();
+        let mut skipped = Vec::new();
+        for handle in handles {
+            // Release builds abort on panic, so a worker only fails to join in debug builds
+            let results = handle.join().expect("Image hashing thread panicked");
+            for result in results {
+                match result {
+                    Ok(image) => hashed.push(image),
+                    Err(path) => skipped.push(path),
+                }
+            }
+        }
+        (hashed, skipped)
+    })
+}
+
+/// Scans a directory for duplicate and near-duplicate images using perceptual hashes.
+/// Nothing is deleted; each group marks its highest-resolution (then largest) file as best,
+/// and every other member is within `max_distance` of that file.
+#[tauri::command]
+pub async fn find_duplicate_images(
+    directory: String,
+    recursive: bool,
+    algorithm: String,
+    max_distance: u32,
+) -> Result<DuplicateScanResult, String> {
+    tokio::task::spawn_blocking(move || {
+        let algorithm = HashAlgorithm::from_name(&algorithm)?;
+        let paths = collect_image_files(&directory, recursive)?;
+        let (hashed, skipped) = hash_images(&paths, algorithm);
+
+        // Best candidates first: each ungrouped image collects every ungrouped image within
+        // the distance threshold of it, so no group chains through intermediate near-matches
+        let mut order: Vec<usize> = (0..hashed.len()).collect();
+        order.sort_by_key(|&i| {
+            let image = &hashed[i];
+            std::cmp::Reverse((image.width as u64 * image.height as u64, image.file_size))
+        });
+
+        let mut grouped = vec![false; hashed.len()];
+        let mut groups: Vec<Vec<DuplicateImage>> = Vec::new();
+        for (position, &best) in order.iter().enumerate() {
+            if grouped[best] {
+                continue;
+            }
+
+            let matches: Vec<(usize, u32)> = order[position + 1..]
+                .iter()
+                .filter(|&&i| !grouped[i])
+                .map(|&i| (i, hamming_distance(hashed[i].hash, hashed[best].hash)))
+                .filter(|&(_, distance)| distance <= max_distance)
+                .collect();
+            if matches.is_empty() {
+                continue;
+            }
+
+            let mut group: Vec<DuplicateImage> = std::iter::once((best, 0))
+                .chain(matches)
+                .map(|(i, distance)| {
+                    grouped[i] = true;
+                    DuplicateImage {
+                        path: hashed[i].path.clone(),
+                        width: hashed[i].width,
+                        height: hashed[i].height,
+                        file_size: hashed[i].file_size,
+                        distance,
+                        is_best: i == best,
+                    }
+                })
+                .collect();
+            group.sort_by(|a, b| b.is_best.cmp(&a.is_best).then(a.distance.cmp(&b.distance)));
+            groups.push(group);
+        }
+        groups.sort_by(|a, b| a[0].path.cmp(&b[0].path));
+
+        Ok::<DuplicateScanResult, String>(DuplicateScanResult {
+            scanned: paths.len(),
+            skipped,
+            groups,
+        })
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StitchParams {
+    layout: String,              // "horizontal", "vertical" or "grid"
+    columns: Option<u32>,        // Grid columns, defaults to a roughly square grid
+    spacing: u32,                // Gap between images and around the edge, in pixels
+    background: String,          // Hex colour
+    size: Option<u32>,           // Common height (horizontal), width (vertical) or cell size (grid)
+    captions: bool,              // Draw each file name under its image
+    caption_size: Option<f32>,
+    caption_color: Option<String>,
+    font_path: Option<String>,
+}
+
+/// Largest stitched image, in pixels (16384 x 16384, 1 GiB as RGBA)
+const MAX_CANVAS_PIXELS: u64 = 1 << 28;
+
+/// Contact sheet cell size when none is given, so a folder of photos doesn't become a giant canvas
+const CONTACT_SHEET_CELL: u32 = 256;
+
+/// Size of an image in its slot in the layout
+fn layout_size(width: u32, height: u32, params: &StitchParams) -> (u32, u32) {
+    let (width, height) = (width.max(1) as f64, height.max(1) as f64);
+    let scaled = |scale: f64| ((width * scale).round().max(1.0) as u32, (height * scale).round().max(1.0) as u32);
+
+    match (params.layout.as_str(), params.size.filter(|&size| size > 0)) {
+        ("horizontal", Some(size)) => (scaled(size as f64 / height).0, size),
+        ("vertical", Some(size)) => (size, scaled(size as f64 / width).1),
+        ("grid", Some(cell)) => scaled((cell as f64 / width).min(cell as f64 / height)),
+        _ => scaled(1.0),
+    }
+}
+
+/// Shortens a caption with an ellipsis until it fits within `max_width`
+fn fit_caption(text: &str, font: &FontVec, font_size: f32, max_width: u32) -> String {
+    let fits = |t: &str| imageproc::drawing::text_size(font_size, font, t).0 <= max_width;
+    if fits(text) {
+        return text.to_string();
+    }
+
+    let mut chars: Vec<char> = text.chars().collect();
+    while !chars.is_empty() {
+        chars.pop();
+        let candidate: String = chars.iter().collect::<String>() + "…";
+        if fits(&candidate) {
+            return candidate;
+        }
+    }
+    String::new()
+}
+
+/// Composes images into a row, column or grid, with optional file name captions
+fn stitch(paths: &[String], params: &StitchParams) -> Result<RgbaImage, String> {
+    if paths.is_empty() {
+        return Err("No images to stitch".to_string());
+    }
+    if !matches!(params.layout.as_str(), "horizontal" | "vertical" | "grid") {
+        return Err("Layout must be 'horizontal', 'vertical' or 'grid'".to_string());
+    }
+
+    // The layout comes from the image headers; images are decoded one at a time while drawing
+    let mut sizes = Vec::with_capacity(paths.len());
+    for path in paths {
+        let (width, height) = image::image_dimensions(path)
+            .map_err(|e| format!("Failed to open image {}: {}", path, e))?;
+        sizes.push(layout_size(width, height, params));
+    }
+
+    let font = if params.captions { Some(load_font(params.font_path.as_deref())?) } else { None };
+    let caption_size = params.caption_size.unwrap_or(14.0);
+    let caption_height = match &font {
+        Some(font) => line_height(font, caption_size) + params.spacing.max(4) / 2,
+        None => 0,
+    };
+
+    let count = sizes.len() as u32;
+    let (columns, rows) = match params.layout.as_str() {
+        "horizontal" => (count, 1),
+        "vertical" => (1, count),
+        _ => {
+            let columns = params.columns
+                .unwrap_or_else(|| (count as f64).sqrt().ceil() as u32)
+                .clamp(1, count);
+            (columns, count.div_ceil(columns))
+        }
+    };
+
+    // Column widths and row heights fit the largest image in each, so every image gets a cell
+    let mut column_widths = vec![0u32; columns as usize];
+    let mut row_heights = vec![0u32; rows as usize];
+    for (i, &(width, height)) in sizes.iter().enumerate() {
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        column_widths[column as usize] = column_widths[column as usize].max(width);
+        row_heights[row as usize] = row_heights[row as usize].max(height);
+    }
+    if params.layout == "grid" {
+        // Grid cells are uniform
+        let cell_width = *column_widths.iter().max().unwrap();
+        let cell_height = *row_heights.iter().max().unwrap();
+        column_widths.iter_mut().for_each(|w| *w = cell_width);
+        row_heights.iter_mut().for_each(|h| *h = cell_height);
+    }
+
+    let spacing = params.spacing;
+    let total = |lengths: &[u32], extra: u32| -> Option<u32> {
+        let gaps = spacing.checked_mul(lengths.len() as u32 + 1)?;
+        lengths.iter().try_fold(gaps, |sum, &length| sum.checked_add(length)?.checked_add(extra))
+    };
+    let (width, height) = match (total(&column_widths, 0), total(&row_heights, caption_height)) {
+        (Some(width), Some(height)) if width as u64 * height as u64 <= MAX_CANVAS_PIXELS => (width, height),
+        _ => {
+            return Err(format!(
+                "The stitched image would be larger than {} megapixels; choose a smaller size or spacing",
+                MAX_CANVAS_PIXELS / 1_000_000
+            ))
+        }
+    };
+    let mut canvas = RgbaImage::from_pixel(width, height, parse_hex_color(&params.background)?);
+    let caption_color = parse_hex_color(params.caption_color.as_deref().unwrap_or("#000000"))?;
+
+    for (i, (path, &(slot_width, slot_height))) in paths.iter().zip(&sizes).enumerate() {
+        let img = image::open(path)
+            .map_err(|e| format!("Failed to open image {}: {}", path, e))?;
+        let img = if (img.width(), img.height()) == (slot_width, slot_height) {
+            img
+        } else {
+            img.resize_exact(slot_width, slot_height, image::imageops::FilterType::Lanczos3)
+        };
+        let (column, row) = ((i as u32 % columns) as usize, (i as u32 / columns) as usize);
+        let cell_x = spacing * (column as u32 + 1) + column_widths[..column].iter().sum::<u32>();
+        let cell_y = spacing * (row as u32 + 1)
+            + row_heights[..row].iter().map(|h| h + caption_height).sum::<u32>();
+        let (cell_width, cell_height) = (column_widths[column], row_heights[row]);
+
+        let x = cell_x + (cell_width - img.width()) / 2;
+        let y = cell_y + (cell_height - img.height()) / 2;
+        image::imageops::overlay(&mut canvas, &img.to_rgba8(), x as i64, y as i64);
+
+        if let Some(font) = &font {
+            let name = std::path::Path::new(path)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or(path);
+            let caption = fit_caption(name, font, caption_size, cell_width);
+            if !caption.is_empty() {
+                let label = render_text(&caption, font, caption_size, caption_color);
+                let label_x = cell_x as i64 + (cell_width as i64 - label.width() as i64) / 2;
+                let label_y = (cell_y + cell_height + params.spacing.max(4) / 4) as i64;
+                image::imageops::overlay(&mut canvas, &label, label_x, label_y);
+            }
+        }
+    }
+
+    Ok(canvas)
+}
+
+#[tauri::command]
+pub async fn stitch_images_preview(
+    input_paths: Vec<String>,
+    params: StitchParams,
+) -> Result<Vec<u8>, String> {
+    tokio::task::spawn_blocking(move || {
+        let stitched = stitch(&input_paths, &params)?;
+
+        encode_png(&DynamicImage::ImageRgba8(stitched))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn stitch_images(
+    input_paths: Vec<String>,
+    output_path: String,
+    format: String,
+    params: StitchParams,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let output_format = parse_image_format(&format)?;
+        let stitched = DynamicImage::ImageRgba8(stitch(&input_paths, &params)?);
+
+        // JPEG has no alpha channel
+        let stitched = if output_format == ImageFormat::Jpeg {
+            DynamicImage::ImageRgb8(stitched.to_rgb8())
+        } else {
+            stitched
+        };
+
+        stitched.save_with_format(&output_path, output_format)
+            .map_err(|e| format!("Failed to save stitched image: {}", e))?;
+
+        Ok::<String, String>(format!("{} images stitched successfully", input_paths.len()))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Builds a grid of every readable image in a folder
+#[tauri::command]
+pub async fn create_contact_sheet(
+    directory: String,
+    recursive: bool,
+    output_path: String,
+    format: String,
+    params: StitchParams,
+) -> Result<String, String> {
+    let input_paths = tokio::task::spawn_blocking(move || {
+        let paths = collect_image_files(&directory, recursive)?;
+
+        // Skip files whose headers can't be read rather than failing the whole sheet
+        Ok::<Vec<String>, String>(
+            paths.into_iter().filter(|p| image::image_dimensions(p).is_ok()).collect(),
+        )
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))??;
+
+    let params = StitchParams { size: params.size.or(Some(CONTACT_SHEET_CELL)), ..params };
+    stitch_images(input_paths, output_path, format, params).await
+}
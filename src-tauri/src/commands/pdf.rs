@@ -1,11 +1,36 @@
-use lopdf::{Document, Object};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream, StringFormat};
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use crate::utils::file_metadata::get_file_metadata;
 use crate::utils::compression::CompressionLevel;
-use crate::utils::path_utils::generate_output_path;
+use crate::utils::path_utils::{generate_output_path, sanitize_file_name};
+use crate::utils::pdf_pages::{
+    clip_box, displayed_to_user, media_box_size, page_box, page_rotation, paper_size, paper_size_name, parse_page_ranges,
+    top_level_bookmarks, upright_text_matrix, visible_box,
+};
 use crate::utils::command_executor::{GhostscriptExecutor, CommandExecutor, validate_output};
+use crate::utils::pdf_merge::{find_inherited_attribute, import_object, ImportedPages, PdfMerger};
+use crate::utils::pdf_strings::encode_text_string;
+use crate::utils::pdf_images::{decode_image_xobject, embed_image, embed_image_file, placement_matrix, EmbeddedImage};
+use crate::utils::pdf_text::{extract_page_text, render_markdown, search_pages, BoundingBox};
+use crate::utils::pdf_render::{content_bounds, render_page_image, render_pages, RenderFormat};
+use crate::utils::path_utils::get_temp_path;
+use crate::utils::pdf_content::{add_page_content, add_page_resource, encode_win_ansi, helvetica_text_width, standard_font};
+use crate::utils::ocr::{invisible_text_layer, recognize_text, recognize_words, words_to_text};
+use crate::utils::pdf_metadata::{build_xmp, format_pdf_date, info_dictionary_mut, read_info, read_xmp, scrub_metadata, write_xmp};
+use crate::utils::pdf_encryption::{
+    decrypt_loaded, decrypted_bytes, encrypt_document, load_decrypted, load_protected, reencrypt_document, EncryptionAlgorithm,
+    PdfSecurity, Permissions,
+};
+use crate::utils::pdf_imposition::{fit_matrix, page_form, replace_pages_with_sheets, scale_page, PageForm, Sheet};
+use crate::utils::pdf_annotations::{
+    add_free_text, add_highlight, add_note, flatten_annotations, kind_matches, list_annotations, remove_annotations, AnnotationInfo,
+};
+use crate::utils::pdf_forms::{fill_form_fields, flatten_form, list_form_fields, parse_csv, FormField};
+use crate::utils::pdf_redaction::{covers_glyph, drawn_xobjects, flatten_page_to_image, redact_page, RedactionCounts};
+use crate::utils::drawing::{anchor_position, parse_hex_color};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PdfInfo {
@@ -30,34 +55,105 @@ pub struct PageSelection {
     page_numbers: Vec<u32>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MergeOptions {
+    keep_outlines: bool,      // Carry over bookmarks
+    bookmark_per_file: bool,  // Nest each file's bookmarks under one named after the file
+    keep_forms: bool,         // Carry over form fields
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            keep_outlines: true,
+            bookmark_per_file: false,
+            keep_forms: true,
+        }
+    }
+}
+
+/// Loads a PDF, decrypting it first when it is password protected
+fn load_pdf(path: &str, password: Option<&str>) -> Result<Document, String> {
+    load_protected_pdf(path, password).map(|(doc, _)| doc)
+}
+
+/// Loads a PDF to edit, keeping the security settings of an encrypted file for `save_pdf`
+fn load_protected_pdf(path: &str, password: Option<&str>) -> Result<(Document, Option<PdfSecurity>), String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read PDF file: {}", e))?;
+    load_protected(&bytes, password)
+}
+
+/// Saves an edited PDF, encrypted again with its source's passwords and permissions if it had any
+fn save_pdf(doc: &mut Document, path: impl AsRef<std::path::Path>, security: Option<&PdfSecurity>) -> Result<(), String> {
+    if let Some(security) = security {
+        reencrypt_document(doc, security)?;
+    }
+    let path = path.as_ref();
+    doc.save(path)
+        .map_err(|e| format!("Failed to save {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// Ghostscript can't be handed a password safely, so an encrypted PDF is rendered from a decrypted
+/// temporary copy of `doc`. Returns that copy (to remove afterwards), or None when the file renders as is.
+fn decrypted_copy(doc: &mut Document, security: Option<&PdfSecurity>) -> Result<Option<String>, String> {
+    if security.is_none() {
+        return Ok(None);
+    }
+    let path = get_temp_path("pdf_decrypted", "pdf");
+    doc.save(&path)
+        .map_err(|e| format!("Failed to write decrypted PDF: {}", e))?;
+    Ok(Some(path))
+}
+
+/// Carries bookmarks and form fields of a merged source over, as selected in `options`
+fn merge_document_extras(
+    merger: &mut PdfMerger,
+    doc: &Document,
+    path: &str,
+    mut imported: ImportedPages,
+    options: &MergeOptions,
+) {
+    if options.keep_outlines {
+        let title = std::path::Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string());
+        let group_title = if options.bookmark_per_file { title.as_deref() } else { None };
+        merger.add_outlines(doc, &mut imported, group_title);
+    }
+    if options.keep_forms {
+        merger.add_form_fields(doc, &mut imported);
+    }
+}
+
 #[tauri::command]
-pub async fn merge_pdfs(input_paths: Vec<String>, output_path: String) -> Result<String, String> {
+pub async fn merge_pdfs(
+    input_paths: Vec<String>,
+    output_path: String,
+    options: Option<MergeOptions>,
+    password: Option<String>,  // Tried on every encrypted input
+) -> Result<String, String> {
     tokio::task::spawn_blocking(move || {
-        let mut merged_doc = Document::with_version("1.5");
-        let mut max_id = 1;
+        let options = options.unwrap_or_default();
+        let mut merger = PdfMerger::new();
+        let mut security = None;
 
         // Merge all PDF documents
         for path in input_paths {
-            let doc = Document::load(&path)
-                .map_err(|e| format!("Failed to load PDF {}: {}", path, e))?;
-
-            // Get the page count
-            let pages = doc.get_pages();
-
-            // Add pages from this document
-            for (_, page_id) in pages.iter() {
-                let page_object = doc.get_object(*page_id)
-                    .map_err(|e| format!("Failed to get page object: {}", e))?;
+            let (doc, doc_security) = load_protected_pdf(&path, password.as_deref())
+                .map_err(|e| format!("{} ({})", e, path))?;
+            // The merged file is protected like the first encrypted input
+            security = security.or(doc_security);
 
-                // Clone and add the page to merged document
-                let new_page_id = merged_doc.add_object(page_object.clone());
-                max_id = max_id.max(new_page_id.0);
-            }
+            let imported = merger.append_document(&doc)
+                .map_err(|e| format!("Failed to merge {}: {}", path, e))?;
+            merge_document_extras(&mut merger, &doc, &path, imported, &options);
         }
 
         // Save the merged document
-        merged_doc.save(&output_path)
-            .map_err(|e| format!("Failed to save merged PDF: {}", e))?;
+        let mut merged_doc = merger.finish()?;
+        save_pdf(&mut merged_doc, &output_path, security.as_ref())?;
 
         Ok::<String, String>("PDFs merged successfully".to_string())
     })
@@ -69,299 +165,2275 @@ pub async fn merge_pdfs(input_paths: Vec<String>, output_path: String) -> Result
 pub async fn merge_pdfs_with_pages(
     page_selections: Vec<PageSelection>,
     output_path: String,
+    options: Option<MergeOptions>,
+    password: Option<String>,  // Tried on every encrypted input
 ) -> Result<String, String> {
     tokio::task::spawn_blocking(move || {
-        let mut merged_doc = Document::with_version("1.5");
+        let options = options.unwrap_or_default();
+        let mut merger = PdfMerger::new();
+        let mut security = None;
 
         for selection in page_selections {
-            let doc = Document::load(&selection.pdf_path)
-                .map_err(|e| format!("Failed to load PDF {}: {}", selection.pdf_path, e))?;
+            let (doc, doc_security) = load_protected_pdf(&selection.pdf_path, password.as_deref())
+                .map_err(|e| format!("{} ({})", e, selection.pdf_path))?;
+            security = security.or(doc_security);
+
+            let imported = merger.append_pages(&doc, &selection.page_numbers)
+                .map_err(|e| format!("Failed to merge {}: {}", selection.pdf_path, e))?;
+            merge_document_extras(&mut merger, &doc, &selection.pdf_path, imported, &options);
+        }
+
+        let mut merged_doc = merger.finish()?;
+        save_pdf(&mut merged_doc, &output_path, security.as_ref())?;
+
+        Ok::<String, String>("PDFs merged successfully with page selection".to_string())
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SplitParams {
+    mode: String,                   // "ranges", "every", "bookmarks" or "single"
+    ranges: Option<String>,         // e.g. "1-3,5,7-", one output file per range
+    pages_per_file: Option<u32>,    // For "every"
+    name_template: Option<String>,  // Placeholders: {name}, {index}, {start}, {end}, {title}
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SplitOutput {
+    path: String,
+    start_page: u32,
+    end_page: u32,
+    title: Option<String>,
+}
+
+#[tauri::command]
+pub async fn split_pdf(
+    input_path: String,
+    output_dir: Option<String>,
+    params: SplitParams,
+    password: Option<String>,
+) -> Result<Vec<SplitOutput>, String> {
+    tokio::task::spawn_blocking(move || {
+        let (doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let page_count = doc.get_pages().len() as u32;
+        if page_count == 0 {
+            return Err("PDF has no pages".to_string());
+        }
 
-            let all_pages = doc.get_pages();
+        // Each part is an inclusive page range with an optional bookmark title
+        let parts: Vec<(u32, u32, Option<String>)> = match params.mode.as_str() {
+            "ranges" => {
+                let spec = params.ranges.as_deref().ok_or("Page ranges are required")?;
+                parse_page_ranges(spec, page_count)?
+                    .into_iter()
+                    .map(|(start, end)| (start, end, None))
+                    .collect()
+            }
+            "every" => {
+                let size = params.pages_per_file.filter(|&n| n > 0).ok_or("Pages per file must be at least 1")?;
+                (1..=page_count)
+                    .step_by(size as usize)
+                    .map(|start| (start, start.saturating_add(size - 1).min(page_count), None))
+                    .collect()
+            }
+            "single" => (1..=page_count).map(|page| (page, page, None)).collect(),
+            "bookmarks" => {
+                let mut bookmarks = top_level_bookmarks(&doc);
+                if bookmarks.is_empty() {
+                    return Err("PDF has no top-level bookmarks to split at".to_string());
+                }
+                bookmarks.sort_by_key(|(_, page)| *page);
+                // Several bookmarks on one page start a single part, named after the first
+                bookmarks.dedup_by_key(|(_, page)| *page);
 
-            for page_num in selection.page_numbers {
-                if let Some((_, page_id)) = all_pages.iter().find(|(num, _)| **num == page_num) {
-                    let page_object = doc.get_object(*page_id)
-                        .map_err(|e| format!("Failed to get page object: {}", e))?;
-                    merged_doc.add_object(page_object.clone());
+                let mut parts = Vec::new();
+                if bookmarks[0].1 > 1 {
+                    parts.push((1, bookmarks[0].1 - 1, None));
                 }
+                for (i, (title, start)) in bookmarks.iter().enumerate() {
+                    let end = bookmarks.get(i + 1).map(|(_, next)| next - 1).unwrap_or(page_count);
+                    parts.push((*start, end, Some(title.clone())));
+                }
+                parts
             }
-        }
+            other => return Err(format!("Unsupported split mode: {}", other)),
+        };
+
+        let input = std::path::Path::new(&input_path);
+        let name = input.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+        let output_dir = match output_dir {
+            Some(dir) => std::path::PathBuf::from(dir),
+            None => input.parent().map(|p| p.to_path_buf()).unwrap_or_default(),
+        };
+        std::fs::create_dir_all(&output_dir)
+            .map_err(|e| format!("Failed to create output directory: {}", e))?;
 
-        merged_doc.save(&output_path)
-            .map_err(|e| format!("Failed to save merged PDF: {}", e))?;
+        let template = params.name_template.as_deref().unwrap_or("{name}_{index}");
+        let index_width = parts.len().to_string().len();
 
-        Ok::<String, String>("PDFs merged successfully with page selection".to_string())
+        let mut outputs = Vec::new();
+        let mut used_names = HashSet::new();
+        for (i, (start, end, title)) in parts.into_iter().enumerate() {
+            let index = format!("{:0width$}", i + 1, width = index_width);
+            let file_name = template
+                .replace("{name}", name)
+                .replace("{index}", &index)
+                .replace("{start}", &start.to_string())
+                .replace("{end}", &end.to_string())
+                .replace("{title}", title.as_deref().unwrap_or(name));
+            let mut file_name = sanitize_file_name(&file_name);
+            // Parts sharing a name (repeated titles, no {index}) don't overwrite each other
+            if !used_names.insert(file_name.clone()) {
+                file_name = format!("{}_{}", file_name, index);
+                used_names.insert(file_name.clone());
+            }
+            let path = output_dir.join(format!("{}.pdf", file_name));
+
+            let pages: Vec<u32> = (start..=end).collect();
+            let mut merger = PdfMerger::new();
+            let imported = merger.append_pages(&doc, &pages)
+                .map_err(|e| format!("Failed to extract pages {}-{}: {}", start, end, e))?;
+            merge_document_extras(&mut merger, &doc, &input_path, imported, &MergeOptions::default());
+
+            let mut part_doc = merger.finish()?;
+            save_pdf(&mut part_doc, &path, security.as_ref())?;
+
+            outputs.push(SplitOutput {
+                path: path.to_string_lossy().to_string(),
+                start_page: start,
+                end_page: end,
+                title,
+            });
+        }
+
+        Ok::<Vec<SplitOutput>, String>(outputs)
     })
     .await
     .map_err(|e| format!("Task failed: {}", e))?
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PageSequenceItem {
+    Page {
+        number: u32,          // 1-based page of the input; may appear more than once
+    },
+    Blank {
+        width: Option<f32>,   // Points; defaults to the size of the preceding page
+        height: Option<f32>,
+    },
+}
+
 #[tauri::command]
-pub async fn rotate_pdf(
+pub async fn reorganize_pdf(
     input_path: String,
     output_path: String,
-    degrees: i32,
-    page_numbers: Option<Vec<u32>>,
+    sequence: Vec<PageSequenceItem>,
+    password: Option<String>,
 ) -> Result<String, String> {
     tokio::task::spawn_blocking(move || {
-        let mut doc = Document::load(&input_path)
-            .map_err(|e| format!("Failed to load PDF: {}", e))?;
+        if sequence.is_empty() {
+            return Err("The new page sequence is empty".to_string());
+        }
 
+        let (doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
         let pages = doc.get_pages();
-        let rotation = degrees % 360;
 
-        // Determine which pages to rotate
-        let pages_to_rotate: Vec<u32> = if let Some(page_nums) = page_numbers {
-            page_nums
-        } else {
-            // Rotate all pages
-            (1..=pages.len() as u32).collect()
-        };
+        // Import all kept pages in one pass so links between them survive, then slot blanks in
+        let page_numbers: Vec<u32> = sequence
+            .iter()
+            .filter_map(|item| match item {
+                PageSequenceItem::Page { number } => Some(*number),
+                PageSequenceItem::Blank { .. } => None,
+            })
+            .collect();
 
-        for (page_num, page_id) in pages.iter() {
-            if pages_to_rotate.contains(page_num) {
-                if let Ok(page_obj) = doc.get_object_mut(*page_id) {
-                    if let Object::Dictionary(ref mut dict) = page_obj {
-                        // Set or update the Rotate entry
-                        dict.set("Rotate", Object::Integer(rotation as i64));
+        let mut merger = PdfMerger::new();
+        let imported = merger.append_pages(&doc, &page_numbers)?;
+        merge_document_extras(&mut merger, &doc, &input_path, imported, &MergeOptions::default());
+
+        let first_page = pages.values().next().copied();
+        let mut previous_size = first_page.map(|id| media_box_size(&doc, id)).unwrap_or((612.0, 792.0));
+        for (index, item) in sequence.iter().enumerate() {
+            match item {
+                PageSequenceItem::Page { number } => {
+                    if let Some(&page_id) = pages.get(number) {
+                        previous_size = media_box_size(&doc, page_id);
                     }
                 }
+                PageSequenceItem::Blank { width, height } => {
+                    let width = width.unwrap_or(previous_size.0);
+                    let height = height.unwrap_or(previous_size.1);
+                    if width <= 0.0 || height <= 0.0 {
+                        return Err(format!("Invalid blank page size: {} x {}", width, height));
+                    }
+                    merger.insert_blank_page(index, width, height);
+                    previous_size = (width, height);
+                }
             }
         }
 
-        doc.save(&output_path)
-            .map_err(|e| format!("Failed to save rotated PDF: {}", e))?;
+        let mut reorganized = merger.finish()?;
+        save_pdf(&mut reorganized, &output_path, security.as_ref())?;
 
-        Ok::<String, String>("PDF rotated successfully".to_string())
+        Ok::<String, String>(format!("PDF reorganized into {} pages", sequence.len()))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdfThumbnail {
+    page_number: u32,
+    data: Vec<u8>,  // PNG bytes
+}
+
+/// Page numbers to work on: the given ones (validated, duplicates dropped) or every page
+fn resolve_page_numbers(doc: &Document, page_numbers: Option<Vec<u32>>) -> Result<Vec<u32>, String> {
+    let page_count = doc.get_pages().len() as u32;
+    match page_numbers {
+        Some(mut pages) => {
+            if let Some(page) = pages.iter().find(|&&p| p == 0 || p > page_count) {
+                return Err(format!("Page {} does not exist (document has {} pages)", page, page_count));
+            }
+            let mut seen = HashSet::new();
+            pages.retain(|&page| seen.insert(page));
+            Ok(pages)
+        }
+        None => Ok((1..=page_count).collect()),
+    }
+}
+
+#[tauri::command]
+pub async fn render_pdf_page_preview(
+    input_path: String,
+    page_number: u32,
+    dpi: Option<u32>,
+    password: Option<String>,
+) -> Result<Vec<u8>, String> {
+    tokio::task::spawn_blocking(move || {
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let decrypted = decrypted_copy(&mut doc, security.as_ref())?;
+        let rendered = render_page_image(decrypted.as_deref().unwrap_or(&input_path), page_number, dpi.unwrap_or(96));
+        if let Some(path) = &decrypted {
+            let _ = std::fs::remove_file(path);
+        }
+        let img = rendered?;
+
+        let mut buffer = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut buffer), image::ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode page image: {}", e))?;
+
+        Ok::<Vec<u8>, String>(buffer)
     })
     .await
     .map_err(|e| format!("Task failed: {}", e))?
 }
 
 #[tauri::command]
-pub async fn extract_text(input_path: String) -> Result<String, String> {
+pub async fn get_pdf_thumbnails(
+    input_path: String,
+    max_width: u32,
+    page_numbers: Option<Vec<u32>>,
+    password: Option<String>,
+) -> Result<Vec<PdfThumbnail>, String> {
     tokio::task::spawn_blocking(move || {
-        // Use pdf-extract crate for text extraction
-        let bytes = std::fs::read(&input_path)
-            .map_err(|e| format!("Failed to read PDF file: {}", e))?;
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let page_numbers = resolve_page_numbers(&doc, page_numbers)?;
+        let pages = doc.get_pages();
 
-        let text = pdf_extract::extract_text_from_mem(&bytes)
-            .map_err(|e| format!("Failed to extract text: {}", e))?;
+        // Render at a DPI where even the narrowest page is at least max_width, then scale down
+        let narrowest = page_numbers
+            .iter()
+            .filter_map(|p| pages.get(p))
+            .map(|&id| media_box_size(&doc, id).0)
+            .fold(f32::MAX, f32::min);
+        let dpi = ((max_width.max(1) as f32 * 72.0 / narrowest.max(1.0)).ceil() as u32).clamp(10, 300);
 
-        Ok::<String, String>(text)
+        let decrypted = decrypted_copy(&mut doc, security.as_ref())?;
+        let scratch_dir = std::path::PathBuf::from(get_temp_path("pdf_thumbnails", "d"));
+        let source = decrypted.as_deref().unwrap_or(&input_path);
+        let rendered = render_pages(source, &page_numbers, dpi, RenderFormat::Png, &scratch_dir, "thumb");
+
+        let result = rendered.and_then(|paths| {
+            page_numbers
+                .iter()
+                .zip(paths)
+                .map(|(&page_number, path)| {
+                    let img = image::open(&path)
+                        .map_err(|e| format!("Failed to read rendered page {}: {}", page_number, e))?;
+                    let thumbnail = if img.width() > max_width {
+                        img.resize(max_width, u32::MAX, image::imageops::FilterType::Triangle)
+                    } else {
+                        img
+                    };
+
+                    let mut data = Vec::new();
+                    thumbnail
+                        .write_to(&mut std::io::Cursor::new(&mut data), image::ImageFormat::Png)
+                        .map_err(|e| format!("Failed to encode thumbnail: {}", e))?;
+                    Ok(PdfThumbnail { page_number, data })
+                })
+                .collect::<Result<Vec<_>, String>>()
+        });
+
+        let _ = std::fs::remove_dir_all(&scratch_dir);
+        if let Some(path) = &decrypted {
+            let _ = std::fs::remove_file(path);
+        }
+        result
     })
     .await
     .map_err(|e| format!("Task failed: {}", e))?
 }
 
 #[tauri::command]
-pub async fn extract_images(input_path: String, output_dir: String) -> Result<Vec<String>, String> {
+pub async fn export_pdf_pages_as_images(
+    input_path: String,
+    output_dir: String,
+    page_numbers: Option<Vec<u32>>,
+    dpi: u32,
+    format: String,
+    quality: Option<u8>,
+    password: Option<String>,
+) -> Result<Vec<String>, String> {
     tokio::task::spawn_blocking(move || {
-        let doc = Document::load(&input_path)
-            .map_err(|e| format!("Failed to load PDF: {}", e))?;
+        let format = RenderFormat::from_name(&format, quality)?;
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let page_numbers = resolve_page_numbers(&doc, page_numbers)?;
 
-        let mut image_paths = Vec::new();
-        let mut image_counter = 0;
+        let name = std::path::Path::new(&input_path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("page")
+            .to_string();
 
-        // Create output directory if it doesn't exist
-        std::fs::create_dir_all(&output_dir)
-            .map_err(|e| format!("Failed to create output directory: {}", e))?;
+        let decrypted = decrypted_copy(&mut doc, security.as_ref())?;
+        let source = decrypted.as_deref().unwrap_or(&input_path);
+        let rendered = render_pages(source, &page_numbers, dpi, format, std::path::Path::new(&output_dir), &name);
+        if let Some(path) = &decrypted {
+            let _ = std::fs::remove_file(path);
+        }
+        rendered
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
 
-        // Iterate through all objects in the PDF
-        for (_object_id, object) in doc.objects.iter() {
-            if let Object::Stream(stream) = object {
-                if let Ok(dict) = stream.dict.get(b"Subtype") {
-                    if let Object::Name(name) = dict {
-                        if name == b"Image" {
-                            // Extract image data
-                            if let Ok(content) = stream.decompressed_content() {
-                                image_counter += 1;
-                                let image_path = format!("{}/image_{}.bin", output_dir, image_counter);
-                                std::fs::write(&image_path, content)
-                                    .map_err(|e| format!("Failed to write image: {}", e))?;
-                                image_paths.push(image_path);
-                            }
-                        }
-                    }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImagesToPdfParams {
+    page_size: String,       // "fit" (page sized to the image), "a4" or "letter"
+    orientation: String,     // "auto" (follows each image), "portrait" or "landscape"
+    margin: f32,             // Points on every side
+    dpi: Option<f32>,        // Image resolution for "fit" pages, default 96
+}
+
+#[tauri::command]
+pub async fn images_to_pdf(
+    input_paths: Vec<String>,
+    output_path: String,
+    params: ImagesToPdfParams,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        if input_paths.is_empty() {
+            return Err("No images selected".to_string());
+        }
+
+        let paper: Option<(f32, f32)> = match params.page_size.to_lowercase().as_str() {
+            "fit" => None,
+            "a4" => Some((595.28, 841.89)),
+            "letter" => Some((612.0, 792.0)),
+            other => return Err(format!("Unsupported page size: {}", other)),
+        };
+        if !matches!(params.orientation.as_str(), "auto" | "portrait" | "landscape") {
+            return Err(format!("Unsupported orientation: {}", params.orientation));
+        }
+        let margin = params.margin.max(0.0);
+        let points_per_pixel = 72.0 / params.dpi.filter(|&d| d > 0.0).unwrap_or(96.0);
+
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let mut page_ids = Vec::new();
+
+        // Pages follow the order of input_paths
+        for path in &input_paths {
+            let image = embed_image_file(&mut doc, path)?;
+            let (pixel_w, pixel_h) = image.display_size();
+            let (image_w, image_h) = (pixel_w as f32 * points_per_pixel, pixel_h as f32 * points_per_pixel);
+
+            let (page_w, page_h) = match paper {
+                None => (image_w + 2.0 * margin, image_h + 2.0 * margin),
+                Some((short, long)) => {
+                    let landscape = match params.orientation.as_str() {
+                        "landscape" => true,
+                        "portrait" => false,
+                        _ => pixel_w > pixel_h,
+                    };
+                    if landscape { (long, short) } else { (short, long) }
                 }
-            }
+            };
+
+            // Scale to fit inside the margins, keeping the aspect ratio, and centre
+            let (box_w, box_h) = ((page_w - 2.0 * margin).max(1.0), (page_h - 2.0 * margin).max(1.0));
+            let scale = (box_w / image_w).min(box_h / image_h);
+            let (draw_w, draw_h) = (image_w * scale, image_h * scale);
+            let (x, y) = ((page_w - draw_w) / 2.0, (page_h - draw_h) / 2.0);
+
+            let matrix = placement_matrix(image.orientation, x, y, draw_w, draw_h);
+            let content = Content {
+                operations: vec![
+                    Operation::new("q", vec![]),
+                    Operation::new("cm", matrix.iter().map(|&v| Object::Real(v)).collect()),
+                    Operation::new("Do", vec![Object::Name(b"Im0".to_vec())]),
+                    Operation::new("Q", vec![]),
+                ],
+            };
+            let content_id = doc.add_object(Stream::new(
+                dictionary! {},
+                content.encode().map_err(|e| format!("Failed to encode page content: {}", e))?,
+            ));
+
+            let page_id = doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "MediaBox" => vec![0.into(), 0.into(), Object::Real(page_w), Object::Real(page_h)],
+                "Resources" => dictionary! {
+                    "XObject" => dictionary! { "Im0" => image.id },
+                },
+                "Contents" => content_id,
+            });
+            page_ids.push(page_id);
         }
 
-        Ok::<Vec<String>, String>(image_paths)
+        let page_count = page_ids.len() as i64;
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => page_ids.into_iter().map(Object::Reference).collect::<Vec<_>>(),
+            "Count" => page_count,
+        }));
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+
+        doc.save(&output_path)
+            .map_err(|e| format!("Failed to save PDF: {}", e))?;
+
+        Ok::<String, String>(format!("Created PDF with {} pages", page_count))
     })
     .await
     .map_err(|e| format!("Task failed: {}", e))?
 }
 
 #[tauri::command]
-pub async fn get_pdf_metadata(input_path: String) -> Result<PdfMetadata, String> {
+pub async fn rotate_pdf(
+    input_path: String,
+    output_path: String,
+    degrees: i32,
+    page_numbers: Option<Vec<u32>>,
+    password: Option<String>,
+) -> Result<String, String> {
     tokio::task::spawn_blocking(move || {
-        let doc = Document::load(&input_path)
-            .map_err(|e| format!("Failed to load PDF: {}", e))?;
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
 
         let pages = doc.get_pages();
-        let page_count = pages.len() as u32;
-        let pdf_version = Some(doc.version.clone());
+        let rotation = degrees % 360;
 
-        // Get file metadata
-        let file_metadata = get_file_metadata(&input_path)?;
+        // Determine which pages to rotate
+        let pages_to_rotate: Vec<u32> = if let Some(page_nums) = page_numbers {
+            page_nums
+        } else {
+            // Rotate all pages
+            (1..=pages.len() as u32).collect()
+        };
 
-        // Extract PDF document info - dynamically get all fields
-        let mut pdf_metadata = HashMap::new();
-        let encrypted = doc.trailer.get(b"Encrypt").is_ok();
-
-        if let Ok(&Object::Reference(ref_id)) = doc.trailer.get(b"Info") {
-            if let Ok(Object::Dictionary(ref dict)) = doc.get_object(ref_id) {
-                for (key, value) in dict.iter() {
-                    if let Ok(key_str) = String::from_utf8(key.to_vec()) {
-                        let value_str = match value {
-                            Object::String(bytes, _) => String::from_utf8(bytes.clone()).unwrap_or_default(),
-                            Object::Integer(i) => i.to_string(),
-                            Object::Real(f) => f.to_string(),
-                            Object::Boolean(b) => b.to_string(),
-                            _ => format!("{:?}", value),
-                        };
-                        pdf_metadata.insert(key_str, value_str);
+        for (page_num, page_id) in pages.iter() {
+            if pages_to_rotate.contains(page_num) {
+                if let Ok(page_obj) = doc.get_object_mut(*page_id) {
+                    if let Object::Dictionary(ref mut dict) = page_obj {
+                        // Set or update the Rotate entry
+                        dict.set("Rotate", Object::Integer(rotation as i64));
                     }
                 }
             }
         }
 
-        Ok::<PdfMetadata, String>(PdfMetadata {
-            pages: page_count,
-            file_size: file_metadata.size,
-            pdf_version,
-            encrypted,
-            file_created: file_metadata.created,
-            file_modified: file_metadata.modified,
-            all_metadata: pdf_metadata,
-        })
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        Ok::<String, String>("PDF rotated successfully".to_string())
     })
     .await
     .map_err(|e| format!("Task failed: {}", e))?
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct PdfPageDimensions {
-    width: f64,
-    height: f64,
+pub struct TextExtractionOptions {
+    format: String,                 // "text", "markdown" or "json" (pages, blocks, lines and words with bounding boxes)
+    layout: bool,                   // Detect columns and read in visual order instead of content-stream order
+    page_numbers: Option<Vec<u32>>, // Defaults to all pages
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct PdfCompressionResult {
-    output_path: String,
-    file_size: u64,
+pub struct PdfPageText {
+    page_number: u32,
+    text: String,
 }
 
-
 #[tauri::command]
-pub async fn compress_pdf(
+pub async fn extract_text(
     input_path: String,
-    quality_level: u8,
-) -> Result<PdfCompressionResult, String> {
+    options: Option<TextExtractionOptions>,
+    password: Option<String>,
+) -> Result<String, String> {
     tokio::task::spawn_blocking(move || {
-        let output_path = generate_output_path(&input_path, "compressed", "pdf");
-        let compression = CompressionLevel::from_u8(quality_level);
-        let pdf_settings = compression.ghostscript_settings();
+        let bytes = std::fs::read(&input_path)
+            .map_err(|e| format!("Failed to read PDF file: {}", e))?;
+        let bytes = decrypted_bytes(bytes, password.as_deref())?;
 
-        // Use ghostscript for PDF compression
-        let executor = GhostscriptExecutor;
-        let output = executor.execute_strings(vec![
-            "-sDEVICE=pdfwrite".to_string(),
-            "-dCompatibilityLevel=1.4".to_string(),
-            format!("-dPDFSETTINGS={}", pdf_settings),
-            "-dNOPAUSE".to_string(),
-            "-dQUIET".to_string(),
-            "-dBATCH".to_string(),
-            format!("-sOutputFile={}", output_path),
-            input_path,
-        ])?;
-        validate_output(&output)?;
+        let Some(options) = options else {
+            // Use pdf-extract crate for text extraction
+            return pdf_extract::extract_text_from_mem(&bytes)
+                .map_err(|e| format!("Failed to extract text: {}", e));
+        };
 
-        // Get output file size
-        let file_size = get_file_metadata(&output_path)?.size;
+        let pages = extract_page_text(&bytes, options.page_numbers.as_deref(), options.layout)?;
+        let text = match options.format.as_str() {
+            // Pages are separated by form feeds, like pdftotext
+            "text" => pages.iter().map(|page| page.plain_text()).collect::<Vec<_>>().join("\n\u{c}"),
+            "markdown" => render_markdown(&pages),
+            "json" => serde_json::to_string_pretty(&pages)
+                .map_err(|e| format!("Failed to serialize text: {}", e))?,
+            other => return Err(format!("Unsupported text format: {}", other)),
+        };
 
-        Ok::<PdfCompressionResult, String>(PdfCompressionResult {
-            output_path,
-            file_size,
-        })
+        Ok::<String, String>(text)
     })
     .await
     .map_err(|e| format!("Task failed: {}", e))?
 }
 
 #[tauri::command]
-pub async fn estimate_pdf_compressed_size(
+pub async fn extract_text_by_page(
     input_path: String,
-    quality_level: u8,
-) -> Result<u64, String> {
+    layout: bool,
+    password: Option<String>,
+) -> Result<Vec<PdfPageText>, String> {
     tokio::task::spawn_blocking(move || {
-        let file_metadata = get_file_metadata(&input_path)?;
-        let compression = CompressionLevel::from_u8(quality_level);
-        let estimated_size = (file_metadata.size as f64 * compression.size_reduction_factor()) as u64;
+        let bytes = std::fs::read(&input_path)
+            .map_err(|e| format!("Failed to read PDF file: {}", e))?;
+        let bytes = decrypted_bytes(bytes, password.as_deref())?;
 
-        Ok::<u64, String>(estimated_size)
+        let pages = extract_page_text(&bytes, None, layout)?
+            .into_iter()
+            .map(|page| PdfPageText {
+                page_number: page.page_number,
+                text: page.plain_text(),
+            })
+            .collect();
+
+        Ok::<Vec<PdfPageText>, String>(pages)
     })
     .await
     .map_err(|e| format!("Task failed: {}", e))?
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchOptions {
+    regex: bool,                  // Treat the query as a regular expression
+    case_sensitive: bool,
+    context_chars: Option<usize>, // Characters of context on each side, default 40
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    file_path: String,
+    page_number: u32,
+    text: String,
+    context: String,
+    rects: Vec<BoundingBox>,  // PDF user space, one per line the match spans
+}
+
+#[derive(Debug, Serialize)]
+pub struct PdfSearchResult {
+    hits: Vec<SearchHit>,
+    failed_files: Vec<String>,  // "path: error" for files that couldn't be searched
+}
+
 #[tauri::command]
-pub async fn get_pdf_page_dimensions(input_path: String) -> Result<PdfPageDimensions, String> {
+pub async fn search_pdf(
+    input_paths: Vec<String>,
+    query: String,
+    options: SearchOptions,
+    password: Option<String>,  // Tried on every encrypted input
+) -> Result<PdfSearchResult, String> {
     tokio::task::spawn_blocking(move || {
-        let doc = Document::load(&input_path)
-            .map_err(|e| format!("Failed to load PDF: {}", e))?;
-
-        let pages = doc.get_pages();
-        if pages.is_empty() {
-            return Err("PDF has no pages".to_string());
+        if query.trim().is_empty() {
+            return Err("Search query is empty".to_string());
         }
 
-        // Get first page
-        let (_, first_page_id) = pages.iter().next().unwrap();
-        let page_obj = doc.get_object(*first_page_id)
-            .map_err(|e| format!("Failed to get page object: {}", e))?;
-
-        // Get MediaBox from page
-        let mut width = 612.0; // Default US Letter width in points
-        let mut height = 792.0; // Default US Letter height in points
-
-        if let Object::Dictionary(dict) = page_obj {
-            if let Ok(media_box) = dict.get(b"MediaBox") {
-                if let Object::Array(ref arr) = media_box {
-                    if arr.len() >= 4 {
-                        let x1 = match &arr[0] {
-                            Object::Real(f) => *f as f64,
-                            Object::Integer(i) => *i as f64,
-                            _ => 0.0,
-                        };
-                        let y1 = match &arr[1] {
-                            Object::Real(f) => *f as f64,
-                            Object::Integer(i) => *i as f64,
-                            _ => 0.0,
-                        };
-                        let x2 = match &arr[2] {
-                            Object::Real(f) => *f as f64,
-                            Object::Integer(i) => *i as f64,
-                            _ => 0.0,
-                        };
-                        let y2 = match &arr[3] {
-                            Object::Real(f) => *f as f64,
-                            Object::Integer(i) => *i as f64,
-                            _ => 0.0,
-                        };
-                        width = (x2 - x1).abs();
-                        height = (y2 - y1).abs();
-                    }
+        let pattern = if options.regex { query.clone() } else { regex::escape(&query) };
+        let pattern = regex::RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .build()
+            .map_err(|e| format!("Invalid search pattern: {}", e))?;
+        let context_chars = options.context_chars.unwrap_or(40);
+
+        let mut hits = Vec::new();
+        let mut failed_files = Vec::new();
+        for path in input_paths {
+            // Same extraction as extract_text, in reading order so phrases across columns aren't mixed up
+            let pages = std::fs::read(&path)
+                .map_err(|e| format!("Failed to read PDF file: {}", e))
+                .and_then(|bytes| decrypted_bytes(bytes, password.as_deref()))
+                .and_then(|bytes| extract_page_text(&bytes, None, true));
+            let pages = match pages {
+                Ok(pages) => pages,
+                Err(e) => {
+                    failed_files.push(format!("{}: {}", path, e));
+                    continue;
                 }
-            }
+            };
+
+            hits.extend(search_pages(&pages, &pattern, context_chars).into_iter().map(|found| SearchHit {
+                file_path: path.clone(),
+                page_number: found.page_number,
+                text: found.text,
+                context: found.context,
+                rects: found.rects,
+            }));
         }
 
-        Ok::<PdfPageDimensions, String>(PdfPageDimensions {
-            width,
-            height,
-        })
+        Ok::<PdfSearchResult, String>(PdfSearchResult { hits, failed_files })
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// Tesseract language codes, e.g. "eng" or "eng+deu"; defaults to English
+fn ocr_language(language: Option<String>) -> Result<String, String> {
+    let language = language.filter(|l| !l.trim().is_empty()).unwrap_or_else(|| "eng".to_string());
+    if !language.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '+') {
+        return Err(format!("Invalid OCR language: {}", language));
+    }
+    Ok(language)
+}
+
+#[tauri::command]
+pub async fn ocr_image(input_path: String, language: Option<String>) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let language = ocr_language(language)?;
+        recognize_text(&input_path, &language)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn ocr_pdf_text(
+    input_path: String,
+    language: Option<String>,
+    page_numbers: Option<Vec<u32>>,
+    dpi: Option<u32>,
+    password: Option<String>,
+) -> Result<Vec<PdfPageText>, String> {
+    tokio::task::spawn_blocking(move || {
+        let language = ocr_language(language)?;
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let page_numbers = resolve_page_numbers(&doc, page_numbers)?;
+
+        let decrypted = decrypted_copy(&mut doc, security.as_ref())?;
+        let source = decrypted.as_deref().unwrap_or(&input_path);
+        let scratch_dir = std::path::PathBuf::from(get_temp_path("pdf_ocr", "d"));
+        let result = render_pages(source, &page_numbers, dpi.unwrap_or(300), RenderFormat::Png, &scratch_dir, "ocr")
+            .and_then(|images| {
+                page_numbers
+                    .iter()
+                    .zip(&images)
+                    .map(|(&page_number, image_path)| {
+                        let words = recognize_words(image_path, &language)?;
+                        Ok(PdfPageText { page_number, text: words_to_text(&words) })
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+            });
+
+        let _ = std::fs::remove_dir_all(&scratch_dir);
+        if let Some(path) = &decrypted {
+            let _ = std::fs::remove_file(path);
+        }
+        result
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn make_pdf_searchable(
+    input_path: String,
+    output_path: String,
+    language: Option<String>,
+    dpi: Option<u32>,
+    skip_text_pages: bool,  // Leave pages that already have extractable text alone
+    password: Option<String>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let language = ocr_language(language)?;
+        let bytes = std::fs::read(&input_path)
+            .map_err(|e| format!("Failed to read PDF file: {}", e))?;
+        let (mut doc, security) = load_protected(&bytes, password.as_deref())?;
+        let pages = doc.get_pages();
+
+        let text_pages: HashSet<u32> = if skip_text_pages {
+            // If the existing text can't be read, OCR every page rather than fail
+            decrypted_bytes(bytes, password.as_deref())
+                .and_then(|bytes| extract_page_text(&bytes, None, false))
+                .map(|pages| pages.into_iter().filter(|p| !p.blocks.is_empty()).map(|p| p.page_number).collect())
+                .unwrap_or_default()
+        } else {
+            HashSet::new()
+        };
+        let targets: Vec<u32> = pages.keys().copied().filter(|p| !text_pages.contains(p)).collect();
+        if targets.is_empty() {
+            return Err("Every page already has text".to_string());
+        }
+
+        let decrypted = decrypted_copy(&mut doc, security.as_ref())?;
+        let source = decrypted.as_deref().unwrap_or(&input_path);
+        let scratch_dir = std::path::PathBuf::from(get_temp_path("pdf_ocr", "d"));
+        let result = render_pages(source, &targets, dpi.unwrap_or(300), RenderFormat::Png, &scratch_dir, "ocr")
+            .and_then(|images| {
+                let font_id = standard_font(&mut doc, "Helvetica");
+                for (page_number, image_path) in targets.iter().zip(&images) {
+                    let page_id = pages[page_number];
+                    let words = recognize_words(image_path, &language)?;
+                    if words.is_empty() {
+                        continue;
+                    }
+                    let image_size = image::image_dimensions(image_path)
+                        .map_err(|e| format!("Failed to read rendered page: {}", e))?;
+
+                    let font = add_page_resource(&mut doc, page_id, "Font", "FOcr", Object::Reference(font_id))?;
+                    let layer = invisible_text_layer(
+                        &words,
+                        image_size,
+                        visible_box(&doc, page_id),
+                        page_rotation(&doc, page_id),
+                        &font,
+                    )?;
+                    add_page_content(&mut doc, page_id, layer, false)?;
+                }
+                Ok(())
+            });
+
+        let _ = std::fs::remove_dir_all(&scratch_dir);
+        if let Some(path) = &decrypted {
+            let _ = std::fs::remove_file(path);
+        }
+        result?;
+
+        doc.compress();
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        Ok::<String, String>(format!("Added a text layer to {} pages", targets.len()))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExtractedImage {
+    path: String,
+    page_number: u32,   // First page the image appears on
+    width: u32,
+    height: u32,
+    format: String,     // "jpg", "jp2" or "png"
+}
+
+/// Image XObjects drawn by a page, including those nested in form XObjects
+fn collect_page_images(doc: &Document, resources: &Object, found: &mut Vec<ObjectId>, visited: &mut HashSet<ObjectId>) {
+    let Ok((_, Object::Dictionary(resources))) = doc.dereference(resources) else {
+        return;
+    };
+    let Ok(xobjects) = resources.get_deref(b"XObject", doc).and_then(Object::as_dict) else {
+        return;
+    };
+
+    for (_, xobject) in xobjects.iter() {
+        let Ok(id) = xobject.as_reference() else {
+            continue;
+        };
+        if !visited.insert(id) {
+            continue;
+        }
+        let Ok(Object::Stream(stream)) = doc.get_object(id) else {
+            continue;
+        };
+        match stream.dict.get(b"Subtype").and_then(Object::as_name) {
+            Ok(b"Image") => found.push(id),
+            Ok(b"Form") => {
+                if let Ok(nested) = stream.dict.get(b"Resources") {
+                    collect_page_images(doc, nested, found, visited);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn extract_images(
+    input_path: String,
+    output_dir: String,
+    password: Option<String>,
+) -> Result<Vec<ExtractedImage>, String> {
+    tokio::task::spawn_blocking(move || {
+        let doc = load_pdf(&input_path, password.as_deref())?;
+
+        // Create output directory if it doesn't exist
+        std::fs::create_dir_all(&output_dir)
+            .map_err(|e| format!("Failed to create output directory: {}", e))?;
+
+        let mut images = Vec::new();
+        let mut visited = HashSet::new();
+
+        // Walk pages in order so every image is reported with the first page that uses it
+        for (page_number, page_id) in doc.get_pages() {
+            let Some(resources) = find_inherited_attribute(&doc, page_id, b"Resources") else {
+                continue;
+            };
+            let mut image_ids = Vec::new();
+            collect_page_images(&doc, resources, &mut image_ids, &mut visited);
+
+            for (index, image_id) in image_ids.into_iter().enumerate() {
+                let Ok(Object::Stream(stream)) = doc.get_object(image_id) else {
+                    continue;
+                };
+                // Images in encodings we can't reconstruct (CCITT, JBIG2...) are skipped
+                let Ok(decoded) = decode_image_xobject(&doc, stream) else {
+                    continue;
+                };
+
+                let image_path = format!("{}/page{}_image{}.{}", output_dir, page_number, index + 1, decoded.extension);
+                std::fs::write(&image_path, &decoded.data)
+                    .map_err(|e| format!("Failed to write image: {}", e))?;
+
+                images.push(ExtractedImage {
+                    path: image_path,
+                    page_number,
+                    width: decoded.width,
+                    height: decoded.height,
+                    format: decoded.extension.to_string(),
+                });
+            }
+        }
+
+        Ok::<Vec<ExtractedImage>, String>(images)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_pdf_metadata(input_path: String, password: Option<String>) -> Result<PdfMetadata, String> {
+    tokio::task::spawn_blocking(move || {
+        let bytes = std::fs::read(&input_path)
+            .map_err(|e| format!("Failed to read PDF file: {}", e))?;
+        let doc = Document::load_mem(&bytes)
+            .map_err(|e| format!("Failed to load PDF: {}", e))?;
+        let encrypted = doc.is_encrypted();
+
+        let pages = doc.get_pages();
+        let page_count = pages.len() as u32;
+        let pdf_version = Some(doc.version.clone());
+
+        // Get file metadata
+        let file_metadata = get_file_metadata(&input_path)?;
+
+        // Extract PDF document info - dynamically get all fields. Its strings are encrypted, so
+        // without the password an encrypted file reports only what's above (and no error)
+        let pdf_metadata: HashMap<String, String> = match &password {
+            _ if !encrypted => read_info(&doc).into_iter().collect(),
+            Some(password) => read_info(&decrypt_loaded(&doc, &bytes, password)?).into_iter().collect(),
+            None => decrypt_loaded(&doc, &bytes, "")
+                .map(|doc| read_info(&doc).into_iter().collect())
+                .unwrap_or_default(),
+        };
+
+        Ok::<PdfMetadata, String>(PdfMetadata {
+            pages: page_count,
+            file_size: file_metadata.size,
+            pdf_version,
+            encrypted,
+            file_created: file_metadata.created,
+            file_modified: file_metadata.modified,
+            all_metadata: pdf_metadata,
+        })
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetadataUpdate {
+    title: Option<String>,              // None keeps the current value, "" removes it
+    author: Option<String>,
+    subject: Option<String>,
+    keywords: Option<String>,
+    creator: Option<String>,            // Application the document was created in
+    producer: Option<String>,
+    creation_date: Option<String>,      // RFC 3339, e.g. "2024-05-01T09:30:00+02:00"
+    modification_date: Option<String>,
+    scrub_all: bool,                    // Remove all existing metadata (Info, XMP, private data) first
+}
+
+#[tauri::command]
+pub async fn set_pdf_metadata(
+    input_path: String,
+    output_path: String,
+    update: MetadataUpdate,
+    password: Option<String>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let previous_xmp = read_xmp(&doc);
+
+        let date = |value: Option<String>, label: &str| -> Result<Option<String>, String> {
+            match value {
+                Some(text) if !text.trim().is_empty() => chrono::DateTime::parse_from_rfc3339(text.trim())
+                    .map(|date| Some(format_pdf_date(&date)))
+                    .map_err(|e| format!("Invalid {}: {}", label, e)),
+                other => Ok(other),
+            }
+        };
+        let fields = [
+            ("Title", update.title),
+            ("Author", update.author),
+            ("Subject", update.subject),
+            ("Keywords", update.keywords),
+            ("Creator", update.creator),
+            ("Producer", update.producer),
+            ("CreationDate", date(update.creation_date, "creation date")?),
+            ("ModDate", date(update.modification_date, "modification date")?),
+        ];
+
+        if update.scrub_all {
+            scrub_metadata(&mut doc);
+        }
+
+        if fields.iter().any(|(_, value)| value.is_some()) {
+            let info = info_dictionary_mut(&mut doc)?;
+            for (key, value) in fields {
+                match value {
+                    Some(text) if text.is_empty() => {
+                        info.remove(key.as_bytes());
+                    }
+                    Some(text) => info.set(key, Object::String(encode_text_string(&text), StringFormat::Literal)),
+                    None => {}
+                }
+            }
+        }
+
+        // Keep the XMP packet in step with Info, unless everything was scrubbed
+        let info = read_info(&doc);
+        if !(update.scrub_all && info.is_empty()) {
+            write_xmp(&mut doc, build_xmp(&info, previous_xmp.as_deref()))?;
+        }
+
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        Ok::<String, String>("PDF metadata updated successfully".to_string())
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncryptionParams {
+    user_password: String,   // Needed to open the PDF; may be empty to only restrict permissions
+    owner_password: String,  // Needed to lift restrictions; empty uses the user password
+    algorithm: String,       // "aes256", "aes128" or "rc4" (legacy readers only)
+    allow_print: bool,
+    allow_copy: bool,
+    allow_modify: bool,
+    allow_annotate: bool,    // Also covers filling in forms
+}
+
+#[tauri::command]
+pub async fn encrypt_pdf(
+    input_path: String,
+    output_path: String,
+    params: EncryptionParams,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let algorithm = EncryptionAlgorithm::from_name(&params.algorithm)?;
+        let permissions = Permissions {
+            print: params.allow_print,
+            copy: params.allow_copy,
+            modify: params.allow_modify,
+            annotate: params.allow_annotate,
+        };
+
+        let mut doc = Document::load(&input_path)
+            .map_err(|e| format!("Failed to load PDF: {}", e))?;
+        encrypt_document(&mut doc, &params.user_password, &params.owner_password, algorithm, permissions)?;
+
+        doc.save(&output_path)
+            .map_err(|e| format!("Failed to save encrypted PDF: {}", e))?;
+
+        Ok::<String, String>("PDF encrypted successfully".to_string())
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn decrypt_pdf(input_path: String, output_path: String, password: String) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let bytes = std::fs::read(&input_path)
+            .map_err(|e| format!("Failed to read PDF file: {}", e))?;
+        let mut doc = load_decrypted(&bytes, Some(&password))?;
+
+        doc.save(&output_path)
+            .map_err(|e| format!("Failed to save decrypted PDF: {}", e))?;
+
+        Ok::<String, String>("PDF decrypted successfully".to_string())
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdfPageDimensions {
+    width: f64,
+    height: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdfCompressionResult {
+    output_path: String,
+    file_size: u64,
+}
+
+
+#[tauri::command]
+pub async fn compress_pdf(
+    input_path: String,
+    quality_level: u8,
+    password: Option<String>,
+) -> Result<PdfCompressionResult, String> {
+    tokio::task::spawn_blocking(move || {
+        let output_path = generate_output_path(&input_path, "compressed", "pdf");
+        let compression = CompressionLevel::from_u8(quality_level);
+        let pdf_settings = compression.ghostscript_settings();
+
+        // Hand Ghostscript a decrypted copy rather than the password on its command line
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let decrypted = decrypted_copy(&mut doc, security.as_ref())?;
+
+        // Use ghostscript for PDF compression
+        let executor = GhostscriptExecutor;
+        let output = executor.execute_strings(vec![
+            "-sDEVICE=pdfwrite".to_string(),
+            "-dCompatibilityLevel=1.4".to_string(),
+            format!("-dPDFSETTINGS={}", pdf_settings),
+            "-dNOPAUSE".to_string(),
+            "-dQUIET".to_string(),
+            "-dBATCH".to_string(),
+            format!("-sOutputFile={}", output_path),
+            decrypted.clone().unwrap_or(input_path),
+        ]);
+        if let Some(path) = &decrypted {
+            let _ = std::fs::remove_file(path);
+        }
+        validate_output(&output?)?;
+
+        // Protect the compressed file the way the original was
+        if let Some(security) = &security {
+            let mut compressed = Document::load(&output_path)
+                .map_err(|e| format!("Failed to load compressed PDF: {}", e))?;
+            save_pdf(&mut compressed, &output_path, Some(security))?;
+        }
+
+        // Get output file size
+        let file_size = get_file_metadata(&output_path)?.size;
+
+        Ok::<PdfCompressionResult, String>(PdfCompressionResult {
+            output_path,
+            file_size,
+        })
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn estimate_pdf_compressed_size(
+    input_path: String,
+    quality_level: u8,
+) -> Result<u64, String> {
+    tokio::task::spawn_blocking(move || {
+        let file_metadata = get_file_metadata(&input_path)?;
+        let compression = CompressionLevel::from_u8(quality_level);
+        let estimated_size = (file_metadata.size as f64 * compression.size_reduction_factor()) as u64;
+
+        Ok::<u64, String>(estimated_size)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_pdf_page_dimensions(input_path: String, password: Option<String>) -> Result<PdfPageDimensions, String> {
+    tokio::task::spawn_blocking(move || {
+        let doc = load_pdf(&input_path, password.as_deref())?;
+
+        // First page's MediaBox, which may be inherited from the page tree
+        let pages = doc.get_pages();
+        let first_page_id = *pages.values().next().ok_or("PDF has no pages")?;
+        let (width, height) = media_box_size(&doc, first_page_id);
+
+        Ok::<PdfPageDimensions, String>(PdfPageDimensions {
+            width: width as f64,
+            height: height as f64,
+        })
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdfPageGeometry {
+    page_number: u32,
+    media_box: [f32; 4],         // [x0, y0, x1, y1] in points, inherited from the page tree if needed
+    crop_box: [f32; 4],          // Visible region, clipped to the MediaBox; defaults to it
+    trim_box: [f32; 4],          // Finished page size; defaults to the CropBox
+    bleed_box: [f32; 4],         // Defaults to the CropBox
+    rotation: i64,               // Clockwise degrees: 0, 90, 180 or 270
+    width: f32,                  // Displayed size in points: the CropBox, rotated and scaled by /UserUnit
+    height: f32,
+    paper_size: Option<String>,  // "A4", "Letter"... in either orientation
+    orientation: String,         // "portrait", "landscape" or "square", as displayed
+}
+
+#[tauri::command]
+pub async fn get_pdf_page_geometry(input_path: String, password: Option<String>) -> Result<Vec<PdfPageGeometry>, String> {
+    tokio::task::spawn_blocking(move || {
+        let doc = load_pdf(&input_path, password.as_deref())?;
+
+        let geometry = doc
+            .get_pages()
+            .into_iter()
+            .map(|(page_number, page_id)| {
+                let media_box = page_box(&doc, page_id, b"MediaBox").unwrap_or([0.0, 0.0, 612.0, 792.0]);
+                let crop_box = visible_box(&doc, page_id);
+                let trim_box = page_box(&doc, page_id, b"TrimBox").map_or(crop_box, |b| clip_box(b, media_box));
+                let bleed_box = page_box(&doc, page_id, b"BleedBox").map_or(crop_box, |b| clip_box(b, media_box));
+                let rotation = page_rotation(&doc, page_id);
+
+                let user_unit = doc
+                    .get_dictionary(page_id)
+                    .and_then(|page| page.get(b"UserUnit"))
+                    .and_then(Object::as_float)
+                    .unwrap_or(1.0);
+                let (mut width, mut height) = ((crop_box[2] - crop_box[0]) * user_unit, (crop_box[3] - crop_box[1]) * user_unit);
+                if rotation == 90 || rotation == 270 {
+                    std::mem::swap(&mut width, &mut height);
+                }
+
+                let orientation = if (width - height).abs() < 1.0 {
+                    "square"
+                } else if width > height {
+                    "landscape"
+                } else {
+                    "portrait"
+                };
+
+                PdfPageGeometry {
+                    page_number,
+                    media_box,
+                    crop_box,
+                    trim_box,
+                    bleed_box,
+                    rotation,
+                    width,
+                    height,
+                    paper_size: paper_size_name(width, height).map(str::to_string),
+                    orientation: orientation.to_string(),
+                }
+            })
+            .collect();
+
+        Ok::<Vec<PdfPageGeometry>, String>(geometry)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CropMargins {
+    top: f32,     // Points trimmed off each side of the page as displayed
+    right: f32,
+    bottom: f32,
+    left: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CropParams {
+    page_numbers: Option<Vec<u32>>,  // 1-based; all pages when omitted
+    margins: Option<CropMargins>,    // Manual crop
+    auto_detect: bool,               // Crop to the content instead, found by rendering (requires Ghostscript)
+    padding: Option<f32>,            // Whitespace kept around detected content, in points; default 6
+}
+
+#[tauri::command]
+pub async fn crop_pdf(
+    input_path: String,
+    output_path: String,
+    params: CropParams,
+    password: Option<String>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        if params.margins.is_none() && !params.auto_detect {
+            return Err("Choose crop margins or automatic whitespace detection".to_string());
+        }
+
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let pages = doc.get_pages();
+        let targets = resolve_page_numbers(&doc, params.page_numbers)?;
+
+        let mut crop_boxes: Vec<(u32, [f32; 4])> = Vec::new();
+        if let Some(margins) = &params.margins {
+            for &page_number in &targets {
+                let page_id = pages[&page_number];
+                let [x0, y0, x1, y1] = visible_box(&doc, page_id);
+
+                // Displayed sides clockwise from the top, moved onto the unrotated sides they show
+                let displayed = [margins.top, margins.right, margins.bottom, margins.left];
+                let turns = (page_rotation(&doc, page_id) / 90) as usize;
+                let mut trim = [0.0f32; 4];
+                for (side, amount) in displayed.iter().enumerate() {
+                    trim[(side + 4 - turns) % 4] = *amount;
+                }
+
+                let crop = [x0 + trim[3], y0 + trim[2], x1 - trim[1], y1 - trim[0]];
+                if crop[0] >= crop[2] || crop[1] >= crop[3] {
+                    return Err(format!("Crop margins leave nothing of page {}", page_number));
+                }
+                crop_boxes.push((page_number, crop));
+            }
+        } else {
+            let decrypted = decrypted_copy(&mut doc, security.as_ref())?;
+            let source = decrypted.as_deref().unwrap_or(&input_path);
+            let scratch_dir = std::path::PathBuf::from(get_temp_path("pdf_crop", "d"));
+            let rendered = render_pages(source, &targets, 72, RenderFormat::Png, &scratch_dir, "crop");
+
+            let padding = params.padding.unwrap_or(6.0).max(0.0);
+            let result = rendered.and_then(|images| {
+                for (&page_number, image_path) in targets.iter().zip(&images) {
+                    let image = image::open(image_path)
+                        .map_err(|e| format!("Failed to read rendered page: {}", e))?;
+                    // Blank pages are left alone
+                    let Some((left, top, right, bottom)) = content_bounds(&image) else {
+                        continue;
+                    };
+
+                    let page_id = pages[&page_number];
+                    let visible = visible_box(&doc, page_id);
+                    let rotation = page_rotation(&doc, page_id);
+                    let displayed_width = if rotation == 90 || rotation == 270 {
+                        visible[3] - visible[1]
+                    } else {
+                        visible[2] - visible[0]
+                    };
+                    let scale = displayed_width / image.width().max(1) as f32;
+
+                    let corner_a = displayed_to_user(visible, rotation, left as f32 * scale - padding, top as f32 * scale - padding);
+                    let corner_b = displayed_to_user(visible, rotation, (right + 1) as f32 * scale + padding, (bottom + 1) as f32 * scale + padding);
+                    let crop = [
+                        corner_a.0.min(corner_b.0),
+                        corner_a.1.min(corner_b.1),
+                        corner_a.0.max(corner_b.0),
+                        corner_a.1.max(corner_b.1),
+                    ];
+                    crop_boxes.push((page_number, clip_box(crop, visible)));
+                }
+                Ok(())
+            });
+
+            let _ = std::fs::remove_dir_all(&scratch_dir);
+            if let Some(path) = &decrypted {
+                let _ = std::fs::remove_file(path);
+            }
+            result?;
+        }
+
+        for (page_number, crop) in &crop_boxes {
+            doc.get_dictionary_mut(pages[page_number])
+                .map_err(|e| format!("Failed to get page object: {}", e))?
+                .set("CropBox", crop.iter().map(|&v| Object::Real(v)).collect::<Vec<_>>());
+        }
+
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        Ok::<String, String>(format!("Cropped {} pages", crop_boxes.len()))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+/// A sheet size from a paper size name or explicit width and height in points, as (short, long) sides
+fn sheet_size(paper_name: Option<&str>, width: Option<f32>, height: Option<f32>) -> Result<Option<(f32, f32)>, String> {
+    let size = match (paper_name, width, height) {
+        (Some(name), _, _) => paper_size(name).ok_or_else(|| format!("Unknown paper size: {}", name))?,
+        (None, Some(width), Some(height)) => (width, height),
+        _ => return Ok(None),
+    };
+    if size.0 <= 0.0 || size.1 <= 0.0 {
+        return Err(format!("Invalid paper size: {} x {}", size.0, size.1));
+    }
+    Ok(Some((size.0.min(size.1), size.0.max(size.1))))
+}
+
+/// Copies a document's pages into a fresh one with inherited attributes pinned and no
+/// bookmarks or form fields pointing at them, ready to be turned into forms
+fn imposition_document(source: &Document) -> Result<Document, String> {
+    let mut merger = PdfMerger::new();
+    merger.append_document(source)?;
+    merger.finish()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResizeParams {
+    paper_size: Option<String>,      // "A4", "Letter"...; or give width and height
+    width: Option<f32>,              // Points
+    height: Option<f32>,
+    orientation: Option<String>,     // "portrait" or "landscape"; defaults to each page's own
+    page_numbers: Option<Vec<u32>>,  // 1-based; all pages when omitted
+}
+
+#[tauri::command]
+pub async fn resize_pdf(
+    input_path: String,
+    output_path: String,
+    params: ResizeParams,
+    password: Option<String>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let (short, long) = sheet_size(params.paper_size.as_deref(), params.width, params.height)?
+            .ok_or("Choose a paper size or a width and height")?;
+        let landscape = match params.orientation.as_deref() {
+            Some("portrait") => Some(false),
+            Some("landscape") => Some(true),
+            None => None,
+            Some(other) => return Err(format!("Unknown orientation: {}", other)),
+        };
+
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let targets = resolve_page_numbers(&doc, params.page_numbers)?;
+        let pages = doc.get_pages();
+
+        // Only the chosen pages change; the sheet is picked as displayed, then unrotated
+        for page_number in &targets {
+            let page_id = pages[page_number];
+            let [x0, y0, x1, y1] = visible_box(&doc, page_id);
+            let turned = matches!(page_rotation(&doc, page_id), 90 | 270);
+            let (width, height) = if turned { (y1 - y0, x1 - x0) } else { (x1 - x0, y1 - y0) };
+            let (width, height) = if landscape.unwrap_or(width > height) { (long, short) } else { (short, long) };
+            let (width, height) = if turned { (height, width) } else { (width, height) };
+            scale_page(&mut doc, page_id, width, height)?;
+        }
+
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        Ok::<String, String>(format!("Resized {} pages", targets.len()))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NupParams {
+    pages_per_sheet: u32,          // 2, 4, 6, 8, 9 or 16
+    booklet: bool,                 // Saddle-stitch booklet: 2 per side in folding order, padded to a multiple of 4
+    paper_size: Option<String>,    // Sheet size; defaults to the size of the first page
+    margin: Option<f32>,           // Points around the edge and between pages; default 0
+    borders: bool,                 // Outline each page's cell
+}
+
+#[tauri::command]
+pub async fn nup_pdf(
+    input_path: String,
+    output_path: String,
+    params: NupParams,
+    password: Option<String>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let pages_per_sheet = if params.booklet { 2 } else { params.pages_per_sheet };
+        // Grid as (shorter, longer) count of cells
+        let grid = match pages_per_sheet {
+            2 => (1, 2),
+            4 => (2, 2),
+            6 => (2, 3),
+            8 => (2, 4),
+            9 => (3, 3),
+            16 => (4, 4),
+            n => return Err(format!("Unsupported number of pages per sheet: {}", n)),
+        };
+
+        let (source, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let mut doc = imposition_document(&source)?;
+        let forms = doc
+            .get_pages()
+            .into_values()
+            .map(|page_id| page_form(&mut doc, page_id))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let (first_width, first_height) = forms[0].displayed_size();
+        let (short, long) = sheet_size(params.paper_size.as_deref(), None, None)?
+            .unwrap_or((first_width.min(first_height), first_width.max(first_height)));
+
+        // Non-square grids lay pages out along the sheet's long side, which means turning the sheet
+        let page_landscape = first_width > first_height;
+        let sheet_landscape = if grid.0 == grid.1 { page_landscape } else { !page_landscape };
+        let (width, height) = if sheet_landscape { (long, short) } else { (short, long) };
+        let (columns, rows) = if width > height { (grid.1, grid.0) } else { (grid.0, grid.1) };
+
+        let margin = params.margin.unwrap_or(0.0).max(0.0);
+        let cell_width = (width - margin * (columns as f32 + 1.0)) / columns as f32;
+        let cell_height = (height - margin * (rows as f32 + 1.0)) / rows as f32;
+        if cell_width <= 0.0 || cell_height <= 0.0 {
+            return Err("The margin leaves no room for pages".to_string());
+        }
+        // Cells left to right, top to bottom
+        let cells: Vec<[f32; 4]> = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (row, column)))
+            .map(|(row, column)| {
+                let x0 = margin + column as f32 * (cell_width + margin);
+                let y1 = height - margin - row as f32 * (cell_height + margin);
+                [x0, y1 - cell_height, x0 + cell_width, y1]
+            })
+            .collect();
+
+        // Page indices per sheet; None leaves a cell blank
+        let page_count = forms.len();
+        let order: Vec<Option<usize>> = if params.booklet {
+            let padded = page_count.div_ceil(4) * 4;
+            (0..padded / 4)
+                .flat_map(|i| [padded - 2 * i, 2 * i + 1, 2 * i + 2, padded - 2 * i - 1])
+                .map(|page| (page <= page_count).then(|| page - 1))
+                .collect()
+        } else {
+            (0..page_count).map(Some).collect()
+        };
+
+        let sheets: Vec<Sheet> = order
+            .chunks(cells.len())
+            .map(|slots| Sheet {
+                width,
+                height,
+                placements: slots
+                    .iter()
+                    .zip(&cells)
+                    .filter_map(|(slot, cell)| slot.map(|index| (forms[index].id, fit_matrix(&forms[index], *cell))))
+                    .collect(),
+                frames: if params.borders { cells[..slots.len()].to_vec() } else { Vec::new() },
+            })
+            .collect();
+        replace_pages_with_sheets(&mut doc, &sheets)?;
+
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        Ok::<String, String>(format!("Placed {} pages on {} sheets", page_count, sheets.len()))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TextStamp {
+    template: String,   // Text with {page}, {total}, {filename}, {date} and {bates} placeholders
+    position: String,   // "top-left", "top", "top-right", "bottom-left", "bottom" or "bottom-right" ("top-center" etc. also work)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StampParams {
+    stamps: Vec<TextStamp>,
+    page_ranges: Option<String>,   // e.g. "2-" to skip a cover page; all pages when omitted
+    font_size: Option<f32>,        // Points; default 10
+    margin: Option<f32>,           // Distance from the page edges in points; default 36
+    bates_prefix: Option<String>,  // Put before the {bates} counter, e.g. "ACME"
+    bates_start: Option<u64>,      // First {bates} number; default 1
+    bates_digits: Option<usize>,   // Zero padding of the {bates} counter, 1 to 20; default 6
+}
+
+#[tauri::command]
+pub async fn stamp_pdf_text(
+    input_path: String,
+    output_path: String,
+    params: StampParams,
+    password: Option<String>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        if params.stamps.is_empty() {
+            return Err("Add at least one header or footer".to_string());
+        }
+        let font_size = params.font_size.unwrap_or(10.0);
+        if font_size <= 0.0 {
+            return Err(format!("Invalid font size: {}", font_size));
+        }
+        let margin = params.margin.unwrap_or(36.0).max(0.0);
+
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let pages = doc.get_pages();
+        let total = pages.len() as u32;
+        let mut targets: Vec<u32> = match &params.page_ranges {
+            Some(spec) => parse_page_ranges(spec, total)?
+                .into_iter()
+                .flat_map(|(start, end)| start..=end)
+                .collect(),
+            None => (1..=total).collect(),
+        };
+        targets.sort_unstable();
+        targets.dedup();
+
+        let filename = std::path::Path::new(&input_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let prefix = params.bates_prefix.unwrap_or_default();
+        let digits = params.bates_digits.unwrap_or(6).clamp(1, 20);
+        let bates_start = params.bates_start.unwrap_or(1);
+
+        let font_id = standard_font(&mut doc, "Helvetica");
+        for (index, page_number) in targets.iter().enumerate() {
+            let page_id = pages[page_number];
+            let font = add_page_resource(&mut doc, page_id, "Font", "FStamp", Object::Reference(font_id))?;
+            let visible = visible_box(&doc, page_id);
+            let rotation = page_rotation(&doc, page_id);
+            let (width, height) = if rotation == 90 || rotation == 270 {
+                (visible[3] - visible[1], visible[2] - visible[0])
+            } else {
+                (visible[2] - visible[0], visible[3] - visible[1])
+            };
+            let bates = format!("{}{:0digits$}", prefix, bates_start + index as u64, digits = digits);
+
+            let mut operations = vec![
+                Operation::new("q", vec![]),
+                Operation::new("BT", vec![]),
+                Operation::new("g", vec![0.into()]),
+                Operation::new("Tf", vec![Object::Name(font.into_bytes()), Object::Real(font_size)]),
+            ];
+            for stamp in &params.stamps {
+                let text = stamp
+                    .template
+                    .replace("{page}", &page_number.to_string())
+                    .replace("{total}", &total.to_string())
+                    .replace("{filename}", &filename)
+                    .replace("{date}", &date)
+                    .replace("{bates}", &bates);
+                let encoded = encode_win_ansi(&text);
+                let text_width = helvetica_text_width(&encoded, font_size);
+
+                // Positions are on the page as displayed; baselines sit a cap height inside the margin.
+                // Same anchor names as images get, so plain "top" and "bottom" are centred
+                let (vertical, horizontal) = stamp.position.split_once('-').unwrap_or((&stamp.position, "center"));
+                let y = match vertical {
+                    "top" => margin + font_size * 0.75,
+                    "bottom" => height - margin,
+                    _ => return Err(format!("Unknown stamp position: {}", stamp.position)),
+                };
+                let x = match horizontal {
+                    "left" => margin,
+                    "center" => (width - text_width) / 2.0,
+                    "right" => width - margin - text_width,
+                    _ => return Err(format!("Unknown stamp position: {}", stamp.position)),
+                };
+
+                let matrix = upright_text_matrix(visible, rotation, x, y);
+                operations.extend([
+                    Operation::new("Tm", matrix.iter().map(|&v| Object::Real(v)).collect()),
+                    Operation::new("Tj", vec![Object::string_literal(encoded)]),
+                ]);
+            }
+            operations.extend([Operation::new("ET", vec![]), Operation::new("Q", vec![])]);
+
+            let content = Content { operations }
+                .encode()
+                .map_err(|e| format!("Failed to encode page content: {}", e))?;
+            add_page_content(&mut doc, page_id, content, false)?;
+        }
+
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        let uses_bates = params.stamps.iter().any(|stamp| stamp.template.contains("{bates}"));
+        Ok::<String, String>(match (uses_bates, targets.len()) {
+            (true, count) if count > 0 => format!(
+                "Stamped {} pages (Bates {}{:0digits$} to {}{:0digits$})",
+                count,
+                prefix,
+                bates_start,
+                prefix,
+                bates_start + count as u64 - 1,
+                digits = digits
+            ),
+            (_, count) => format!("Stamped {} pages", count),
+        })
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdfWatermarkParams {
+    kind: String,                    // "text", "image" (PNG/JPEG logo) or "pdf" (first page of a PDF logo)
+    text: Option<String>,
+    font_size: Option<f32>,          // Points; defaults to text spanning 60% of the page width
+    color: Option<String>,           // Hex, e.g. "#FF0000"; default grey
+    image_path: Option<String>,      // Logo for "image" and "pdf" watermarks
+    scale: Option<f32>,              // Logo width as a fraction of the page width; default 0.3
+    opacity: f32,                    // 0.0 - 1.0
+    rotation: f32,                   // Degrees clockwise, e.g. -45 for a diagonal rising to the right
+    position: String,                // Anchor ("top-left" ... "bottom-right", "center")
+    margin: Option<f32>,             // Points; default 36
+    beneath: bool,                   // Draw under the page content instead of over it
+    page_numbers: Option<Vec<u32>>,  // 1-based; all pages when omitted
+}
+
+/// What a PDF watermark draws, already added to the document
+enum WatermarkMark {
+    Text { encoded: Vec<u8>, font: ObjectId, color: [f32; 3] },
+    Image(EmbeddedImage),
+    Form(PageForm),
+}
+
+#[tauri::command]
+pub async fn watermark_pdf(
+    input_path: String,
+    output_path: String,
+    params: PdfWatermarkParams,
+    password: Option<String>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let pages = doc.get_pages();
+        let targets = resolve_page_numbers(&doc, params.page_numbers.clone())?;
+
+        let mark = match params.kind.as_str() {
+            "text" => {
+                let text = params.text.as_deref().filter(|t| !t.is_empty())
+                    .ok_or("Watermark text cannot be empty")?;
+                if let Some(font_size) = params.font_size.filter(|&size| size <= 0.0) {
+                    return Err(format!("Invalid font size: {}", font_size));
+                }
+                let color = parse_hex_color(params.color.as_deref().unwrap_or("#808080"))?;
+                WatermarkMark::Text {
+                    encoded: encode_win_ansi(text),
+                    font: standard_font(&mut doc, "Helvetica"),
+                    color: [color[0], color[1], color[2]].map(|c| c as f32 / 255.0),
+                }
+            }
+            "image" => {
+                let logo_path = params.image_path.as_deref().ok_or("No watermark image selected")?;
+                WatermarkMark::Image(embed_image_file(&mut doc, logo_path)?)
+            }
+            "pdf" => {
+                let logo_path = params.image_path.as_deref().ok_or("No watermark PDF selected")?;
+                let mut logo = load_pdf(logo_path, None)?;
+                let first_page = *logo.get_pages().values().next().ok_or("The watermark PDF has no pages")?;
+                let form = page_form(&mut logo, first_page)?;
+                let id = import_object(&mut doc, &logo, Object::Reference(form.id), &mut BTreeMap::new())
+                    .as_reference()
+                    .map_err(|e| format!("Failed to copy the watermark PDF: {}", e))?;
+                WatermarkMark::Form(PageForm { id, ..form })
+            }
+            _ => return Err("Watermark kind must be 'text', 'image' or 'pdf'".to_string()),
+        };
+
+        let opacity = params.opacity.clamp(0.0, 1.0);
+        let graphics_state = doc.add_object(dictionary! {
+            "Type" => "ExtGState",
+            "ca" => Object::Real(opacity),
+            "CA" => Object::Real(opacity),
+        });
+        let margin = params.margin.unwrap_or(36.0).max(0.0);
+
+        for page_number in &targets {
+            let page_id = pages[page_number];
+            let visible = visible_box(&doc, page_id);
+            let page_rotation = page_rotation(&doc, page_id);
+            let (page_width, page_height) = if page_rotation == 90 || page_rotation == 270 {
+                (visible[3] - visible[1], visible[2] - visible[0])
+            } else {
+                (visible[2] - visible[0], visible[3] - visible[1])
+            };
+
+            // The mark's own operations, drawn centred on the origin, and its unrotated size
+            let logo_width = page_width * params.scale.unwrap_or(0.3).clamp(0.01, 1.0);
+            let (mark_operations, width, height) = match &mark {
+                WatermarkMark::Text { encoded, font, color } => {
+                    let font_size = params.font_size
+                        .unwrap_or_else(|| 0.6 * page_width / helvetica_text_width(encoded, 1.0).max(0.001));
+                    let width = helvetica_text_width(encoded, font_size);
+                    let font = add_page_resource(&mut doc, page_id, "Font", "FWm", Object::Reference(*font))?;
+                    let operations = vec![
+                        Operation::new("BT", vec![]),
+                        Operation::new("rg", color.iter().map(|&c| Object::Real(c)).collect()),
+                        Operation::new("Tf", vec![Object::Name(font.into_bytes()), Object::Real(font_size)]),
+                        // Centre on roughly half the cap height
+                        Operation::new("Td", vec![Object::Real(-width / 2.0), Object::Real(-font_size * 0.36)]),
+                        Operation::new("Tj", vec![Object::string_literal(encoded.clone())]),
+                        Operation::new("ET", vec![]),
+                    ];
+                    (operations, width, font_size)
+                }
+                WatermarkMark::Image(image) => {
+                    let (image_width, image_height) = image.display_size();
+                    let height = logo_width * image_height as f32 / image_width.max(1) as f32;
+                    let name = add_page_resource(&mut doc, page_id, "XObject", "ImWm", Object::Reference(image.id))?;
+                    let matrix = placement_matrix(image.orientation, -logo_width / 2.0, -height / 2.0, logo_width, height);
+                    let operations = vec![
+                        Operation::new("cm", matrix.iter().map(|&v| Object::Real(v)).collect()),
+                        Operation::new("Do", vec![Object::Name(name.into_bytes())]),
+                    ];
+                    (operations, logo_width, height)
+                }
+                WatermarkMark::Form(form) => {
+                    let (form_width, form_height) = form.displayed_size();
+                    let height = logo_width * form_height / form_width.max(0.001);
+                    let name = add_page_resource(&mut doc, page_id, "XObject", "FmWm", Object::Reference(form.id))?;
+                    let matrix = fit_matrix(form, [-logo_width / 2.0, -height / 2.0, logo_width / 2.0, height / 2.0]);
+                    let operations = vec![
+                        Operation::new("cm", matrix.iter().map(|&v| Object::Real(v)).collect()),
+                        Operation::new("Do", vec![Object::Name(name.into_bytes())]),
+                    ];
+                    (operations, logo_width, height)
+                }
+            };
+
+            // Anchor the rotated mark's bounding box on the page as displayed
+            let radians = params.rotation.to_radians();
+            let bounds_width = (width * radians.cos()).abs() + (height * radians.sin()).abs();
+            let bounds_height = (width * radians.sin()).abs() + (height * radians.cos()).abs();
+            let (left, top) = anchor_position(
+                &params.position,
+                (page_width.round() as u32, page_height.round() as u32),
+                (bounds_width.ceil() as u32, bounds_height.ceil() as u32),
+                margin.round() as u32,
+            )?;
+            let (center_x, center_y) = displayed_to_user(
+                visible,
+                page_rotation,
+                left as f32 + bounds_width / 2.0,
+                top as f32 + bounds_height / 2.0,
+            );
+
+            // Counter-clockwise in user space: the page's own rotation, less the clockwise watermark rotation
+            let angle = (page_rotation as f32 - params.rotation).to_radians();
+            let (sin, cos) = angle.sin_cos();
+            let gs = add_page_resource(&mut doc, page_id, "ExtGState", "GSWm", Object::Reference(graphics_state))?;
+
+            let mut operations = vec![
+                Operation::new("q", vec![]),
+                Operation::new("gs", vec![Object::Name(gs.into_bytes())]),
+                Operation::new("cm", [cos, sin, -sin, cos, center_x, center_y].iter().map(|&v| Object::Real(v)).collect()),
+            ];
+            operations.extend(mark_operations);
+            operations.push(Operation::new("Q", vec![]));
+
+            let content = Content { operations }
+                .encode()
+                .map_err(|e| format!("Failed to encode page content: {}", e))?;
+            add_page_content(&mut doc, page_id, content, params.beneath)?;
+        }
+
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        Ok::<String, String>(format!("Watermarked {} pages", targets.len()))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RedactionArea {
+    page_number: u32,  // 1-based
+    rect: [f32; 4],    // [x0, y0, x1, y1] in PDF user space, as returned by search_pdf_text
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RedactParams {
+    areas: Option<Vec<RedactionArea>>,
+    search_terms: Option<Vec<String>>,  // Every occurrence is redacted
+    case_sensitive: bool,
+    fill_color: Option<String>,         // Hex colour of the boxes drawn over redacted areas; default black
+    flatten: bool,                      // Also turn redacted pages into images (requires Ghostscript)
+    scrub_metadata: bool,               // Remove document info, XMP metadata and other hidden data
+    dpi: Option<u32>,                   // Resolution of flattened pages; default 150
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RedactionResult {
+    pages: usize,
+    areas: usize,
+    glyphs_removed: usize,
+    images_redacted: usize,
+    paths_removed: usize,
+    annotations_removed: usize,
+}
+
+#[tauri::command]
+pub async fn redact_pdf(
+    input_path: String,
+    output_path: String,
+    params: RedactParams,
+    password: Option<String>,
+) -> Result<RedactionResult, String> {
+    tokio::task::spawn_blocking(move || {
+        let bytes = std::fs::read(&input_path)
+            .map_err(|e| format!("Failed to read PDF file: {}", e))?;
+        let (mut doc, security) = load_protected(&bytes, password.as_deref())?;
+        // Search terms are found in the text of the decrypted file
+        let bytes = if security.is_some() {
+            let mut decrypted = Vec::new();
+            doc.save_to(&mut decrypted)
+                .map_err(|e| format!("Failed to write decrypted PDF: {}", e))?;
+            decrypted
+        } else {
+            bytes
+        };
+        let pages = doc.get_pages();
+        let page_count = pages.len() as u32;
+
+        let mut areas: BTreeMap<u32, Vec<[f32; 4]>> = BTreeMap::new();
+        for area in params.areas.iter().flatten() {
+            if area.page_number == 0 || area.page_number > page_count {
+                return Err(format!("Page {} is out of range (document has {} pages)", area.page_number, page_count));
+            }
+            let [x0, y0, x1, y1] = area.rect;
+            areas.entry(area.page_number).or_default().push([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]);
+        }
+
+        let terms: Vec<&String> = params.search_terms.iter().flatten().filter(|t| !t.trim().is_empty()).collect();
+        let term_pattern = if terms.is_empty() {
+            None
+        } else {
+            let pattern = terms.iter().map(|t| regex::escape(t)).collect::<Vec<_>>().join("|");
+            Some(
+                regex::RegexBuilder::new(&pattern)
+                    .case_insensitive(!params.case_sensitive)
+                    .build()
+                    .map_err(|e| format!("Invalid search term: {}", e))?,
+            )
+        };
+        if let Some(pattern) = &term_pattern {
+            let page_text = extract_page_text(&bytes, None, true)?;
+            for found in search_pages(&page_text, pattern, 0) {
+                // A little slack so accents and descenders at the edges go too
+                let rects = found.rects.iter().map(|r| [r.x0 - 0.5, r.y0 - 0.5, r.x1 + 0.5, r.y1 + 0.5]);
+                areas.entry(found.page_number).or_default().extend(rects);
+            }
+        }
+        if areas.is_empty() {
+            return Err(if term_pattern.is_some() {
+                "None of the search terms were found".to_string()
+            } else {
+                "No redaction areas given".to_string()
+            });
+        }
+
+        let fill = parse_hex_color(params.fill_color.as_deref().unwrap_or("#000000"))?;
+        let fill = [fill[0], fill[1], fill[2]].map(|c| Object::Real(c as f32 / 255.0));
+        let mut counts = RedactionCounts::default();
+        let mut redacted_images = Vec::new();
+        for (page_number, page_areas) in &areas {
+            let page_id = pages[page_number];
+            let page_counts = redact_page(&mut doc, page_id, page_areas)?;
+            redacted_images.extend(page_counts.image_ids.iter().map(|&id| (*page_number, id)));
+            counts.add(page_counts);
+
+            let mut operations = vec![Operation::new("q", vec![]), Operation::new("rg", fill.to_vec())];
+            for [x0, y0, x1, y1] in page_areas {
+                operations.push(Operation::new(
+                    "re",
+                    vec![Object::Real(*x0), Object::Real(*y0), Object::Real(x1 - x0), Object::Real(y1 - y0)],
+                ));
+            }
+            operations.extend([Operation::new("f", vec![]), Operation::new("Q", vec![])]);
+            let content = Content { operations }
+                .encode()
+                .map_err(|e| format!("Failed to encode page content: {}", e))?;
+            add_page_content(&mut doc, page_id, content, false)?;
+        }
+        // The original content streams and images must not survive in the file
+        doc.prune_objects();
+
+        // Check the text and images really are gone before writing anything
+        let mut redacted = Vec::new();
+        doc.save_to(&mut redacted)
+            .map_err(|e| format!("Failed to save PDF: {}", e))?;
+        // Search terms are checked on every page with the plain extractor extract_text uses, which
+        // also reads text the glyph collector above may have missed
+        if let Some(pattern) = &term_pattern {
+            let page_texts = pdf_extract::extract_text_from_mem_by_pages(&redacted)
+                .map_err(|e| format!("Redaction could not be verified: failed to extract text: {}", e))?;
+            for (index, text) in page_texts.iter().enumerate() {
+                let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                if let Some(found) = pattern.find(&text) {
+                    return Err(format!(
+                        "Redaction could not be verified: \"{}\" is still readable on page {}",
+                        found.as_str(),
+                        index + 1
+                    ));
+                }
+            }
+        }
+        let page_numbers: Vec<u32> = areas.keys().copied().collect();
+        for page in extract_page_text(&redacted, Some(&page_numbers), true)? {
+            let page_areas = &areas[&page.page_number];
+            let words = page.blocks.iter().flat_map(|b| &b.lines).flat_map(|l| &l.words);
+            for word in words {
+                for (character, glyph) in word.text.chars().zip(&word.char_boxes) {
+                    let glyph = [glyph.x0, glyph.y0, glyph.x1, glyph.y1];
+                    if !character.is_whitespace() && page_areas.iter().any(|area| covers_glyph(area, &glyph, 0.5)) {
+                        return Err(format!(
+                            "Redaction could not be verified: text \"{}\" is still readable on page {}",
+                            word.text, page.page_number
+                        ));
+                    }
+                }
+            }
+        }
+        // A redacted image may only remain where another placement still shows it whole
+        let shown: HashSet<ObjectId> = pages.values().flat_map(|&page_id| drawn_xobjects(&doc, page_id)).collect();
+        if let Some((page_number, _)) = redacted_images.iter().find(|(_, id)| doc.objects.contains_key(id) && !shown.contains(id)) {
+            return Err(format!(
+                "Redaction could not be verified: an image redacted on page {} is still in the file",
+                page_number
+            ));
+        }
+
+        if params.flatten {
+            let redacted_path = get_temp_path("pdf_redacted", "pdf");
+            std::fs::write(&redacted_path, &redacted)
+                .map_err(|e| format!("Failed to write redacted PDF: {}", e))?;
+            let scratch_dir = std::path::PathBuf::from(get_temp_path("pdf_redact", "d"));
+            let dpi = params.dpi.unwrap_or(150);
+
+            let result = render_pages(&redacted_path, &page_numbers, dpi, RenderFormat::Png, &scratch_dir, "redact")
+                .and_then(|images| {
+                    for (page_number, image_path) in page_numbers.iter().zip(&images) {
+                        let image = image::open(image_path)
+                            .map_err(|e| format!("Failed to read rendered page: {}", e))?;
+                        let image = embed_image(&mut doc, image)?;
+                        flatten_page_to_image(&mut doc, pages[page_number], &image)?;
+                    }
+                    Ok(())
+                });
+
+            let _ = std::fs::remove_dir_all(&scratch_dir);
+            let _ = std::fs::remove_file(&redacted_path);
+            result?;
+            doc.prune_objects();
+        }
+
+        if params.scrub_metadata {
+            scrub_metadata(&mut doc);
+        }
+
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        Ok::<RedactionResult, String>(RedactionResult {
+            pages: areas.len(),
+            areas: areas.values().map(Vec::len).sum(),
+            glyphs_removed: counts.glyphs,
+            images_redacted: counts.images,
+            paths_removed: counts.paths,
+            annotations_removed: counts.annotations,
+        })
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn list_pdf_form_fields(input_path: String, password: Option<String>) -> Result<Vec<FormField>, String> {
+    tokio::task::spawn_blocking(move || {
+        let doc = load_pdf(&input_path, password.as_deref())?;
+        Ok::<Vec<FormField>, String>(list_form_fields(&doc))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FormFillParams {
+    values: HashMap<String, serde_json::Value>,  // Field name -> text, true/false for checkboxes, option(s) for choices
+    flatten: bool,                               // Draw the filled fields into the pages and remove the form
+}
+
+#[tauri::command]
+pub async fn fill_pdf_form(
+    input_path: String,
+    output_path: String,
+    params: FormFillParams,
+    password: Option<String>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let filled = fill_form_fields(&mut doc, &params.values)?;
+        if params.flatten {
+            flatten_form(&mut doc)?;
+        }
+
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        Ok::<String, String>(if params.flatten {
+            format!("Filled {} fields and flattened the form", filled)
+        } else {
+            format!("Filled {} fields", filled)
+        })
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchFillParams {
+    csv_path: String,               // Header row of field names, then one row per output PDF
+    flatten: bool,
+    name_template: Option<String>,  // Placeholders: {<column>}, {name} and {index}; default "{name}_{index}"
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchFillResult {
+    files: Vec<String>,
+    ignored_columns: Vec<String>,  // CSV columns that aren't form fields (still usable in the name template)
+}
+
+#[tauri::command]
+pub async fn batch_fill_pdf_form(
+    input_path: String,
+    output_dir: Option<String>,
+    params: BatchFillParams,
+    password: Option<String>,
+) -> Result<BatchFillResult, String> {
+    tokio::task::spawn_blocking(move || {
+        let (doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let text = std::fs::read_to_string(&params.csv_path)
+            .map_err(|e| format!("Failed to read CSV file: {}", e))?;
+        let mut rows = parse_csv(&text)?.into_iter();
+        let header: Vec<String> = rows.next().ok_or("The CSV file is empty")?.iter().map(|h| h.trim().to_string()).collect();
+        let rows: Vec<Vec<String>> = rows.collect();
+        if rows.is_empty() {
+            return Err("The CSV file has no data rows".to_string());
+        }
+
+        let field_names: HashSet<String> = list_form_fields(&doc).into_iter().map(|f| f.name).collect();
+        if field_names.is_empty() {
+            return Err("PDF has no form fields".to_string());
+        }
+        let ignored_columns: Vec<String> = header.iter().filter(|h| !field_names.contains(*h)).cloned().collect();
+        if ignored_columns.len() == header.len() {
+            return Err("None of the CSV columns match a form field".to_string());
+        }
+
+        let input = std::path::Path::new(&input_path);
+        let name = input.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+        let output_dir = match output_dir {
+            Some(dir) => std::path::PathBuf::from(dir),
+            None => input.parent().map(|p| p.to_path_buf()).unwrap_or_default(),
+        };
+        std::fs::create_dir_all(&output_dir)
+            .map_err(|e| format!("Failed to create output directory: {}", e))?;
+
+        let template = params.name_template.as_deref().unwrap_or("{name}_{index}");
+        let index_width = rows.len().to_string().len();
+        let mut files = Vec::new();
+        let mut used_names = HashSet::new();
+        for (i, row) in rows.iter().enumerate() {
+            let index = format!("{:0width$}", i + 1, width = index_width);
+            let cells: Vec<(&String, &str)> = header.iter().zip(row.iter().map(String::as_str).chain(std::iter::repeat(""))).collect();
+
+            // An empty cell clears its field; as text it isn't an option of any choice or checkbox
+            let values: HashMap<String, serde_json::Value> = cells
+                .iter()
+                .filter(|(column, _)| field_names.contains(*column))
+                .map(|(column, cell)| {
+                    let value = if cell.is_empty() { serde_json::Value::Null } else { serde_json::Value::String(cell.to_string()) };
+                    ((*column).clone(), value)
+                })
+                .collect();
+            let mut filled = doc.clone();
+            fill_form_fields(&mut filled, &values).map_err(|e| format!("Row {}: {}", i + 1, e))?;
+            if params.flatten {
+                flatten_form(&mut filled)?;
+            }
+
+            // Columns come first, so a "name" column isn't shadowed by the file name
+            let mut file_name = template.to_string();
+            for (column, cell) in &cells {
+                file_name = file_name.replace(&format!("{{{}}}", column), cell.trim());
+            }
+            let mut file_name = sanitize_file_name(&file_name.replace("{name}", name).replace("{index}", &index));
+            // Rows sharing a name don't overwrite each other
+            if !used_names.insert(file_name.clone()) {
+                file_name = format!("{}_{}", file_name, index);
+                used_names.insert(file_name.clone());
+            }
+
+            let path = output_dir.join(format!("{}.pdf", file_name));
+            save_pdf(&mut filled, &path, security.as_ref())?;
+            files.push(path.to_string_lossy().to_string());
+        }
+
+        Ok::<BatchFillResult, String>(BatchFillResult { files, ignored_columns })
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn list_pdf_annotations(input_path: String, password: Option<String>) -> Result<Vec<AnnotationInfo>, String> {
+    tokio::task::spawn_blocking(move || {
+        let doc = load_pdf(&input_path, password.as_deref())?;
+        Ok::<Vec<AnnotationInfo>, String>(list_annotations(&doc))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewPdfAnnotation {
+    page_number: u32,           // 1-based
+    kind: String,               // "highlight", "note" or "free_text"
+    rects: Vec<[f32; 4]>,       // PDF user space. Highlight: one per line (e.g. search hit rects); note: icon at the first's top-left; free text: the box
+    contents: Option<String>,   // Comment text; the text shown for free text
+    author: Option<String>,
+    color: Option<String>,      // Hex; default yellow, black for free text
+    font_size: Option<f32>,     // Free text only; default 12
+}
+
+#[tauri::command]
+pub async fn add_pdf_annotations(
+    input_path: String,
+    output_path: String,
+    annotations: Vec<NewPdfAnnotation>,
+    password: Option<String>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let pages = doc.get_pages();
+        if annotations.is_empty() {
+            return Err("No annotations to add".to_string());
+        }
+
+        for annotation in &annotations {
+            let page_id = *pages.get(&annotation.page_number).ok_or_else(|| {
+                format!("Page {} is out of range (document has {} pages)", annotation.page_number, pages.len())
+            })?;
+            let rects: Vec<[f32; 4]> = annotation
+                .rects
+                .iter()
+                .map(|&[x0, y0, x1, y1]| [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)])
+                .collect();
+            let first = *rects.first().ok_or("Each annotation needs a position")?;
+            let default_color = if annotation.kind == "free_text" { "#000000" } else { "#FFFF00" };
+            let color = parse_hex_color(annotation.color.as_deref().unwrap_or(default_color))?;
+            let color = [color[0], color[1], color[2]].map(|c| c as f32 / 255.0);
+            let contents = annotation.contents.as_deref();
+            let author = annotation.author.as_deref();
+
+            match annotation.kind.as_str() {
+                "highlight" => add_highlight(&mut doc, page_id, &rects, color, contents, author)?,
+                "note" => add_note(&mut doc, page_id, first[0], first[3], color, contents, author)?,
+                "free_text" => {
+                    let text = contents.filter(|t| !t.is_empty()).ok_or("Free text annotations need text")?;
+                    if first[2] - first[0] < 1.0 || first[3] - first[1] < 1.0 {
+                        return Err("The free text box is too small".to_string());
+                    }
+                    let font_size = annotation.font_size.unwrap_or(12.0).max(1.0);
+                    add_free_text(&mut doc, page_id, first, text, font_size, color, author)?
+                }
+                other => return Err(format!("Unsupported annotation kind: {}", other)),
+            };
+        }
+
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        Ok::<String, String>(format!("Added {} annotations", annotations.len()))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnnotationFilter {
+    kinds: Option<Vec<String>>,      // e.g. ["highlight", "note", "link"]; all but links when omitted
+    page_numbers: Option<Vec<u32>>,  // 1-based; all pages when omitted
+}
+
+impl AnnotationFilter {
+    /// Whether an annotation is picked. Form field widgets never are, and links only when asked for.
+    fn matches(&self, annotation: &lopdf::Dictionary) -> bool {
+        let subtype = annotation.get(b"Subtype").and_then(Object::as_name).unwrap_or(b"");
+        match &self.kinds {
+            _ if subtype == b"Widget" => false,
+            Some(kinds) => kinds.iter().any(|kind| kind_matches(subtype, kind)),
+            None => subtype != b"Link",
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn remove_pdf_annotations(
+    input_path: String,
+    output_path: String,
+    filter: AnnotationFilter,
+    password: Option<String>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let pages = doc.get_pages();
+        let targets = resolve_page_numbers(&doc, filter.page_numbers.clone())?;
+
+        let mut removed = 0;
+        for page_number in &targets {
+            removed += remove_annotations(&mut doc, pages[page_number], |a| filter.matches(a))?;
+        }
+        doc.prune_objects();
+
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        Ok::<String, String>(format!("Removed {} annotations", removed))
+    })
+    .await
+    .map_err(|e| format!("Task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn flatten_pdf_annotations(
+    input_path: String,
+    output_path: String,
+    filter: AnnotationFilter,
+    password: Option<String>,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        let (mut doc, security) = load_protected_pdf(&input_path, password.as_deref())?;
+        let pages = doc.get_pages();
+        let targets = resolve_page_numbers(&doc, filter.page_numbers.clone())?;
+
+        let mut flattened = 0;
+        for page_number in &targets {
+            flattened += flatten_annotations(&mut doc, pages[page_number], |a| filter.matches(a))?;
+        }
+        doc.prune_objects();
+
+        save_pdf(&mut doc, &output_path, security.as_ref())?;
+
+        Ok::<String, String>(format!("Flattened {} annotations", flattened))
     })
     .await
     .map_err(|e| format!("Task failed: {}", e))?
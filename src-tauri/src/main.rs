@@ -56,6 +56,18 @@ fn main() {
             commands::image::crop_image,
             commands::image::compress_image,
             commands::image::estimate_compressed_size,
+            commands::image::watermark_image_preview,
+            commands::image::watermark_image,
+            commands::image::watermark_images,
+            commands::image::annotate_image_preview,
+            commands::image::annotate_image,
+            commands::image::compare_images,
+            commands::image::compare_images_heatmap_preview,
+            commands::image::compress_image_to_quality,
+            commands::image::find_duplicate_images,
+            commands::image::stitch_images_preview,
+            commands::image::stitch_images,
+            commands::image::create_contact_sheet,
             commands::pdf::merge_pdfs,
             commands::pdf::merge_pdfs_with_pages,
             commands::pdf::rotate_pdf,
@@ -65,6 +77,34 @@ fn main() {
             commands::pdf::get_pdf_page_dimensions,
             commands::pdf::compress_pdf,
             commands::pdf::estimate_pdf_compressed_size,
+            commands::pdf::split_pdf,
+            commands::pdf::reorganize_pdf,
+            commands::pdf::render_pdf_page_preview,
+            commands::pdf::get_pdf_thumbnails,
+            commands::pdf::export_pdf_pages_as_images,
+            commands::pdf::images_to_pdf,
+            commands::pdf::extract_text_by_page,
+            commands::pdf::search_pdf,
+            commands::pdf::ocr_image,
+            commands::pdf::ocr_pdf_text,
+            commands::pdf::make_pdf_searchable,
+            commands::pdf::encrypt_pdf,
+            commands::pdf::decrypt_pdf,
+            commands::pdf::set_pdf_metadata,
+            commands::pdf::get_pdf_page_geometry,
+            commands::pdf::crop_pdf,
+            commands::pdf::resize_pdf,
+            commands::pdf::nup_pdf,
+            commands::pdf::stamp_pdf_text,
+            commands::pdf::watermark_pdf,
+            commands::pdf::redact_pdf,
+            commands::pdf::list_pdf_form_fields,
+            commands::pdf::fill_pdf_form,
+            commands::pdf::batch_fill_pdf_form,
+            commands::pdf::list_pdf_annotations,
+            commands::pdf::add_pdf_annotations,
+            commands::pdf::remove_pdf_annotations,
+            commands::pdf::flatten_pdf_annotations,
             commands::video::trim_video,
             commands::video::strip_audio,
             commands::video::scale_video,
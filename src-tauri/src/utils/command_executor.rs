@@ -85,6 +85,32 @@ impl CommandExecutor for GhostscriptExecutor {
     }
 }
 
+/// Tesseract OCR command executor
+pub struct TesseractExecutor;
+
+impl CommandExecutor for TesseractExecutor {
+    fn execute(&self, args: &[&str]) -> Result<Output, String> {
+        Command::new("tesseract")
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to execute tesseract: {}. Make sure Tesseract OCR is installed.", e))
+    }
+
+    fn execute_strings(&self, args: Vec<String>) -> Result<Output, String> {
+        Command::new("tesseract")
+            .args(&args)
+            .output()
+            .map_err(|e| format!("Failed to execute tesseract: {}. Make sure Tesseract OCR is installed.", e))
+    }
+
+    fn check_available(&self) -> bool {
+        Command::new("tesseract")
+            .arg("--version")
+            .output()
+            .is_ok()
+    }
+}
+
 /// Validates command output and returns error message if failed
 pub fn validate_output(output: &Output) -> Result<(), String> {
     if !output.status.success() {
@@ -0,0 +1,210 @@
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+use imageproc::drawing::{draw_filled_circle_mut, draw_polygon_mut, draw_text_mut, text_size};
+use imageproc::geometric_transformations::{rotate_about_center, Interpolation};
+use imageproc::point::Point;
+
+/// Fonts tried, in order, when no explicit font file is given
+const SYSTEM_FONT_PATHS: &[&str] = &[
+    "/System/Library/Fonts/Helvetica.ttc",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+
+/// Loads a TrueType/OpenType font, falling back to a system font when no path is given
+pub fn load_font(font_path: Option<&str>) -> Result<FontVec, String> {
+    if let Some(path) = font_path {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read font {}: {}", path, e))?;
+        return FontVec::try_from_vec(bytes)
+            .map_err(|e| format!("Failed to parse font {}: {}", path, e));
+    }
+
+    for path in SYSTEM_FONT_PATHS {
+        if let Ok(bytes) = std::fs::read(path) {
+            if let Ok(font) = FontVec::try_from_vec(bytes) {
+                return Ok(font);
+            }
+        }
+    }
+
+    Err("No usable system font found. Please choose a font file.".to_string())
+}
+
+/// Parses a hex colour ("#RRGGBB", "#RRGGBBAA" or "#RGB") into RGBA
+pub fn parse_hex_color(color: &str) -> Result<Rgba<u8>, String> {
+    let hex = color.trim().trim_start_matches('#');
+    let expanded: String = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 | 8 => hex.to_string(),
+        _ => return Err(format!("Invalid colour: {}", color)),
+    };
+
+    let channel = |i: usize| {
+        u8::from_str_radix(&expanded[i..i + 2], 16)
+            .map_err(|_| format!("Invalid colour: {}", color))
+    };
+
+    let alpha = if expanded.len() == 8 { channel(6)? } else { 255 };
+    Ok(Rgba([channel(0)?, channel(2)?, channel(4)?, alpha]))
+}
+
+/// Line height in pixels for the given font and size
+pub fn line_height(font: &FontVec, font_size: f32) -> u32 {
+    font.as_scaled(PxScale::from(font_size)).height().ceil() as u32
+}
+
+/// Renders a single line of text onto a transparent, tightly sized RGBA canvas
+pub fn render_text(text: &str, font: &FontVec, font_size: f32, color: Rgba<u8>) -> RgbaImage {
+    let scale = PxScale::from(font_size);
+    let (text_width, _) = text_size(scale, font, text);
+    let width = text_width + (font_size / 4.0).ceil() as u32;
+    let height = line_height(font, font_size).max(1);
+
+    // Draw coverage into a mask first so anti-aliased edges keep the exact colour
+    let mut mask = GrayImage::new(width.max(1), height);
+    draw_text_mut(&mut mask, Luma([255]), 0, 0, scale, font, text);
+
+    RgbaImage::from_fn(mask.width(), mask.height(), |x, y| {
+        let coverage = mask.get_pixel(x, y)[0] as u16;
+        let alpha = (coverage * color[3] as u16 / 255) as u8;
+        Rgba([color[0], color[1], color[2], alpha])
+    })
+}
+
+/// Rotates an RGBA image clockwise, growing the canvas so no corners are clipped
+pub fn rotate_expanded(image: &RgbaImage, degrees: f32) -> RgbaImage {
+    if degrees % 360.0 == 0.0 {
+        return image.clone();
+    }
+
+    let (width, height) = image.dimensions();
+    let diagonal = ((width as f32).hypot(height as f32)).ceil() as u32;
+    let mut padded = RgbaImage::new(diagonal, diagonal);
+    image::imageops::overlay(
+        &mut padded,
+        image,
+        ((diagonal - width) / 2) as i64,
+        ((diagonal - height) / 2) as i64,
+    );
+
+    let rotated = rotate_about_center(
+        &padded,
+        degrees.to_radians(),
+        Interpolation::Bilinear,
+        Rgba([0, 0, 0, 0]),
+    );
+    trim_transparent(&rotated)
+}
+
+/// Crops away fully transparent borders
+pub fn trim_transparent(image: &RgbaImage) -> RgbaImage {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel[3] > 0 {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+
+    if min_x > max_x || min_y > max_y {
+        return image.clone();
+    }
+
+    image::imageops::crop_imm(image, min_x, min_y, max_x - min_x + 1, max_y - min_y + 1).to_image()
+}
+
+/// Scales the alpha channel of every pixel by `opacity` (0.0 - 1.0)
+pub fn apply_opacity(image: &mut RgbaImage, opacity: f32) {
+    let opacity = opacity.clamp(0.0, 1.0);
+    for pixel in image.pixels_mut() {
+        pixel[3] = (pixel[3] as f32 * opacity).round() as u8;
+    }
+}
+
+/// Computes the top-left corner for placing an item of `item` size inside `canvas`
+/// at a named anchor such as "top-left", "center" or "bottom-right"
+pub fn anchor_position(
+    anchor: &str,
+    canvas: (u32, u32),
+    item: (u32, u32),
+    margin: u32,
+) -> Result<(i64, i64), String> {
+    let (canvas_w, canvas_h) = (canvas.0 as i64, canvas.1 as i64);
+    let (item_w, item_h) = (item.0 as i64, item.1 as i64);
+    let margin = margin as i64;
+
+    let left = margin;
+    let center_x = (canvas_w - item_w) / 2;
+    let right = canvas_w - item_w - margin;
+    let top = margin;
+    let center_y = (canvas_h - item_h) / 2;
+    let bottom = canvas_h - item_h - margin;
+
+    let position = match anchor {
+        "top-left" => (left, top),
+        "top" => (center_x, top),
+        "top-right" => (right, top),
+        "left" => (left, center_y),
+        "center" => (center_x, center_y),
+        "right" => (right, center_y),
+        "bottom-left" => (left, bottom),
+        "bottom" => (center_x, bottom),
+        "bottom-right" => (right, bottom),
+        _ => return Err(format!("Unsupported position: {}", anchor)),
+    };
+
+    Ok(position)
+}
+
+/// Draws a line segment of the given thickness with round caps
+pub fn draw_thick_line(
+    canvas: &mut RgbaImage,
+    from: (f32, f32),
+    to: (f32, f32),
+    thickness: f32,
+    color: Rgba<u8>,
+) {
+    let radius = (thickness / 2.0).max(0.5);
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx.hypot(dy);
+
+    if length >= 1.0 {
+        // Offset both endpoints perpendicular to the segment to get a quad
+        let (nx, ny) = (-dy / length * radius, dx / length * radius);
+        let quad = [
+            Point::new((from.0 + nx).round() as i32, (from.1 + ny).round() as i32),
+            Point::new((to.0 + nx).round() as i32, (to.1 + ny).round() as i32),
+            Point::new((to.0 - nx).round() as i32, (to.1 - ny).round() as i32),
+            Point::new((from.0 - nx).round() as i32, (from.1 - ny).round() as i32),
+        ];
+        if quad[0] != quad[3] {
+            draw_polygon_mut(canvas, &quad, color);
+        }
+    }
+
+    let cap_radius = radius.round() as i32;
+    draw_filled_circle_mut(canvas, (from.0.round() as i32, from.1.round() as i32), cap_radius, color);
+    draw_filled_circle_mut(canvas, (to.0.round() as i32, to.1.round() as i32), cap_radius, color);
+}
+
+/// Draws connected thick line segments through `points`, optionally closing the path
+pub fn draw_thick_polyline(
+    canvas: &mut RgbaImage,
+    points: &[(f32, f32)],
+    thickness: f32,
+    color: Rgba<u8>,
+    closed: bool,
+) {
+    for pair in points.windows(2) {
+        draw_thick_line(canvas, pair[0], pair[1], thickness, color);
+    }
+    if closed && points.len() > 2 {
+        draw_thick_line(canvas, points[points.len() - 1], points[0], thickness, color);
+    }
+}
@@ -0,0 +1,156 @@
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+/// Perceptual hash algorithms, all producing 64-bit fingerprints
+#[derive(Debug, Clone, Copy)]
+pub enum HashAlgorithm {
+    Average,
+    Difference,
+    Perceptual,
+}
+
+impl HashAlgorithm {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "ahash" | "average" => Ok(HashAlgorithm::Average),
+            "dhash" | "difference" => Ok(HashAlgorithm::Difference),
+            "phash" | "perceptual" => Ok(HashAlgorithm::Perceptual),
+            _ => Err(format!("Unsupported hash algorithm: {}", name)),
+        }
+    }
+
+    pub fn hash(&self, img: &DynamicImage) -> u64 {
+        match self {
+            HashAlgorithm::Average => average_hash(img),
+            HashAlgorithm::Difference => difference_hash(img),
+            HashAlgorithm::Perceptual => perceptual_hash(img),
+        }
+    }
+}
+
+/// Number of differing bits between two hashes
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// aHash: each bit is set when an 8x8 greyscale pixel is brighter than the mean
+pub fn average_hash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(8, 8, FilterType::Triangle).to_luma8();
+    let mean = small.pixels().map(|p| p[0] as u32).sum::<u32>() / 64;
+
+    small
+        .pixels()
+        .enumerate()
+        .fold(0u64, |hash, (i, p)| if p[0] as u32 > mean { hash | 1 << i } else { hash })
+}
+
+/// dHash: each bit records whether a pixel is brighter than its right neighbour (9x8 grid)
+pub fn difference_hash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+
+    for y in 0..8 {
+        for x in 0..8 {
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1 << (y * 8 + x);
+            }
+        }
+    }
+
+    hash
+}
+
+/// pHash: thresholds the low-frequency 8x8 block of a 32x32 DCT against its median
+pub fn perceptual_hash(img: &DynamicImage) -> u64 {
+    const SIZE: usize = 32;
+    let small = img.resize_exact(SIZE as u32, SIZE as u32, FilterType::Triangle).to_luma8();
+    let pixels: Vec<f64> = small.pixels().map(|p| p[0] as f64).collect();
+
+    // Precompute the DCT-II basis once; only the first 8 frequencies are needed
+    let basis: Vec<Vec<f64>> = (0..8)
+        .map(|u| {
+            (0..SIZE)
+                .map(|x| ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / (2 * SIZE) as f64).cos())
+                .collect()
+        })
+        .collect();
+
+    // Rows first, then columns
+    let mut rows = vec![[0.0f64; 8]; SIZE];
+    for (y, row) in rows.iter_mut().enumerate() {
+        for (u, coefficient) in row.iter_mut().enumerate() {
+            *coefficient = (0..SIZE).map(|x| pixels[y * SIZE + x] * basis[u][x]).sum();
+        }
+    }
+
+    let mut coefficients = [0.0f64; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            coefficients[v * 8 + u] = (0..SIZE).map(|y| rows[y][u] * basis[v][y]).sum();
+        }
+    }
+
+    // Skip the DC term when choosing the threshold, it only encodes overall brightness
+    let mut sorted: Vec<f64> = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+
+    coefficients
+        .iter()
+        .enumerate()
+        .fold(0u64, |hash, (i, &c)| if c > median { hash | 1 << i } else { hash })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    const ALGORITHMS: [HashAlgorithm; 3] = [HashAlgorithm::Average, HashAlgorithm::Difference, HashAlgorithm::Perceptual];
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let value = ((x * 255 / width + y * 64 / height) % 256) as u8;
+            Rgb([value, value / 2, 255 - value])
+        }))
+    }
+
+    #[test]
+    fn hamming_distance_counts_differing_bits() {
+        assert_eq!(hamming_distance(0, 0), 0);
+        assert_eq!(hamming_distance(0b1011, 0b0001), 2);
+        assert_eq!(hamming_distance(0, u64::MAX), 64);
+        assert_eq!(hamming_distance(u64::MAX, 1 << 63), 63);
+    }
+
+    #[test]
+    fn identical_images_hash_identically() {
+        let image = gradient(120, 80);
+        for algorithm in ALGORITHMS {
+            assert_eq!(algorithm.hash(&image), algorithm.hash(&image.clone()));
+        }
+    }
+
+    #[test]
+    fn resized_copies_stay_close() {
+        let (original, smaller) = (gradient(400, 300), gradient(400, 300).thumbnail(100, 75));
+        for algorithm in ALGORITHMS {
+            assert!(hamming_distance(algorithm.hash(&original), algorithm.hash(&smaller)) <= 6);
+        }
+    }
+
+    #[test]
+    fn different_images_are_far_apart() {
+        let (image, flipped) = (gradient(200, 200), gradient(200, 200).fliph());
+        for algorithm in ALGORITHMS {
+            assert!(hamming_distance(algorithm.hash(&image), algorithm.hash(&flipped)) > 10);
+        }
+    }
+
+    #[test]
+    fn algorithm_names() {
+        assert!(matches!(HashAlgorithm::from_name("pHash"), Ok(HashAlgorithm::Perceptual)));
+        assert!(matches!(HashAlgorithm::from_name("difference"), Ok(HashAlgorithm::Difference)));
+        assert!(HashAlgorithm::from_name("md5").is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use image::{ImageBuffer, Luma, Rgb, RgbImage};
+use imageproc::filter::gaussian_blur_f32;
+
+/// PSNR reported for identical images (the true value is infinite)
+pub const MAX_PSNR: f64 = 100.0;
+
+/// Colour difference (CIE76 ΔE) that is just noticeable to most viewers
+const JUST_NOTICEABLE_DELTA_E: f64 = 2.3;
+
+/// ΔE at which the heatmap saturates to full red
+const HEATMAP_MAX_DELTA_E: f32 = 20.0;
+
+/// Per-pixel colour difference map
+pub type DeltaEMap = ImageBuffer<Luma<f32>, Vec<f32>>;
+
+/// Full-reference quality scores between a reference image and a distorted copy
+pub struct QualityScores {
+    pub psnr: f64,
+    pub ssim: f64,
+    pub perceptual_distance: f64,
+    pub max_delta_e: f64,
+}
+
+/// Computes PSNR over all RGB channels, in dB
+pub fn psnr(reference: &RgbImage, distorted: &RgbImage) -> f64 {
+    let sum_sq: f64 = reference
+        .as_raw()
+        .iter()
+        .zip(distorted.as_raw())
+        .map(|(&a, &b)| {
+            let d = a as f64 - b as f64;
+            d * d
+        })
+        .sum();
+
+    let mse = sum_sq / reference.as_raw().len().max(1) as f64;
+    if mse == 0.0 {
+        return MAX_PSNR;
+    }
+    (10.0 * (255.0 * 255.0 / mse).log10()).min(MAX_PSNR)
+}
+
+/// Computes mean SSIM on luma using 8x8 windows with a stride of 4
+pub fn ssim(reference: &RgbImage, distorted: &RgbImage) -> f64 {
+    const WINDOW: u32 = 8;
+    const STRIDE: u32 = 4;
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let luma_a = luma_plane(reference);
+    let luma_b = luma_plane(distorted);
+    let (width, height) = reference.dimensions();
+
+    // Images smaller than one window are scored as a single window
+    let window_w = WINDOW.min(width);
+    let window_h = WINDOW.min(height);
+
+    let mut total = 0.0;
+    let mut count = 0usize;
+    let mut y = 0;
+    while y + window_h <= height {
+        let mut x = 0;
+        while x + window_w <= width {
+            let n = (window_w * window_h) as f64;
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for wy in y..y + window_h {
+                for wx in x..x + window_w {
+                    let index = (wy * width + wx) as usize;
+                    let (a, b) = (luma_a[index], luma_b[index]);
+                    sum_a += a;
+                    sum_b += b;
+                    sum_aa += a * a;
+                    sum_bb += b * b;
+                    sum_ab += a * b;
+                }
+            }
+
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let var_a = sum_aa / n - mean_a * mean_a;
+            let var_b = sum_bb / n - mean_b * mean_b;
+            let covariance = sum_ab / n - mean_a * mean_b;
+
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            count += 1;
+            x += STRIDE;
+        }
+        y += STRIDE;
+    }
+
+    if count == 0 {
+        1.0
+    } else {
+        total / count as f64
+    }
+}
+
+/// Per-pixel CIE76 ΔE between the two images, lightly blurred to model visual masking
+pub fn delta_e_map(reference: &RgbImage, distorted: &RgbImage) -> DeltaEMap {
+    let (width, height) = reference.dimensions();
+    let map = ImageBuffer::from_fn(width, height, |x, y| {
+        let a = rgb_to_lab(reference.get_pixel(x, y));
+        let b = rgb_to_lab(distorted.get_pixel(x, y));
+        let delta = ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) + (a.2 - b.2).powi(2)).sqrt();
+        Luma([delta])
+    });
+
+    if width < 3 || height < 3 {
+        return map;
+    }
+    gaussian_blur_f32(&map, 1.0)
+}
+
+/// Computes PSNR, SSIM and a butteraugli-style perceptual distance together.
+///
+/// The perceptual distance is the 99th percentile of the blurred ΔE map divided by the
+/// just-noticeable difference, so values below 1.0 are generally invisible. The ΔE map
+/// is returned alongside so callers can render a heatmap without recomputing it.
+pub fn compare(reference: &RgbImage, distorted: &RgbImage) -> (QualityScores, DeltaEMap) {
+    let delta_e = delta_e_map(reference, distorted);
+    let mut values: Vec<f32> = delta_e.as_raw().clone();
+    values.sort_by(|a, b| a.total_cmp(b));
+
+    let percentile_99 = values
+        .get(((values.len() as f64 * 0.99) as usize).min(values.len().saturating_sub(1)))
+        .copied()
+        .unwrap_or(0.0) as f64;
+    let max_delta_e = values.last().copied().unwrap_or(0.0) as f64;
+
+    let scores = QualityScores {
+        psnr: psnr(reference, distorted),
+        ssim: ssim(reference, distorted),
+        perceptual_distance: percentile_99 / JUST_NOTICEABLE_DELTA_E,
+        max_delta_e,
+    };
+    (scores, delta_e)
+}
+
+/// Renders a ΔE map as a heatmap over a dimmed greyscale copy of the reference
+pub fn render_heatmap(reference: &RgbImage, delta_e: &DeltaEMap) -> RgbImage {
+    RgbImage::from_fn(reference.width(), reference.height(), |x, y| {
+        let t = (delta_e.get_pixel(x, y)[0] / HEATMAP_MAX_DELTA_E).clamp(0.0, 1.0);
+        let Rgb([r, g, b]) = *reference.get_pixel(x, y);
+        let grey = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) * 0.35;
+
+        if t <= 0.0 {
+            return Rgb([grey as u8; 3]);
+        }
+
+        // Blue -> green -> yellow -> red ramp
+        let (hr, hg, hb) = if t < 1.0 / 3.0 {
+            let s = t * 3.0;
+            (0.0, s, 1.0 - s)
+        } else if t < 2.0 / 3.0 {
+            (t * 3.0 - 1.0, 1.0, 0.0)
+        } else {
+            (1.0, 1.0 - (t * 3.0 - 2.0), 0.0)
+        };
+
+        let alpha = t.sqrt();
+        let mix = |channel: f32| (grey * (1.0 - alpha) + channel * 255.0 * alpha).round() as u8;
+        Rgb([mix(hr), mix(hg), mix(hb)])
+    })
+}
+
+/// Rec. 601 luma for every pixel, as f64
+fn luma_plane(image: &RgbImage) -> Vec<f64> {
+    image
+        .pixels()
+        .map(|p| 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64)
+        .collect()
+}
+
+/// Converts an sRGB pixel to CIELAB (D65)
+fn rgb_to_lab(pixel: &Rgb<u8>) -> (f32, f32, f32) {
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(pixel[0]), linear(pixel[1]), linear(pixel[2]));
+
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| Rgb([(x * 7 % 256) as u8, (y * 13 % 256) as u8, ((x ^ y) % 256) as u8]))
+    }
+
+    #[test]
+    fn identical_images_score_perfectly() {
+        for (width, height) in [(64, 48), (5, 3)] {
+            let image = pattern(width, height);
+            let (scores, delta_e) = compare(&image, &image);
+            assert!((scores.ssim - 1.0).abs() < 1e-12);
+            assert_eq!(scores.psnr, MAX_PSNR);
+            assert_eq!(scores.perceptual_distance, 0.0);
+            assert_eq!(scores.max_delta_e, 0.0);
+            assert!(delta_e.pixels().all(|p| p[0] == 0.0));
+        }
+    }
+
+    #[test]
+    fn distortion_lowers_the_scores() {
+        let image = pattern(64, 64);
+        let noisy = RgbImage::from_fn(64, 64, |x, y| {
+            let Rgb([r, g, b]) = *image.get_pixel(x, y);
+            let noise = if (x + y) % 2 == 0 { 40 } else { 0 };
+            Rgb([r.saturating_add(noise), g.saturating_add(noise), b.saturating_add(noise)])
+        });
+
+        let (scores, _) = compare(&image, &noisy);
+        assert!(scores.ssim < 0.99);
+        assert!(scores.psnr < 40.0);
+        assert!(scores.perceptual_distance > 1.0);
+    }
+
+    #[test]
+    fn psnr_of_a_uniform_offset() {
+        let black = RgbImage::new(10, 10);
+        let grey = RgbImage::from_pixel(10, 10, Rgb([16, 16, 16]));
+        // MSE is 256, so PSNR is 10 * log10(255^2 / 256)
+        assert!((psnr(&black, &grey) - 24.0484).abs() < 1e-3);
+    }
+}
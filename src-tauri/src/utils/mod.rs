@@ -2,4 +2,21 @@ pub mod file_metadata;
 pub mod path_utils;
 pub mod compression;
 pub mod command_executor;
-pub mod window_utils;
\ No newline at end of file
+pub mod window_utils;
+pub mod drawing;
+pub mod image_quality;
+pub mod image_hash;
+pub mod pdf_merge;
+pub mod pdf_strings;
+pub mod pdf_pages;
+pub mod pdf_render;
+pub mod pdf_images;
+pub mod pdf_text;
+pub mod pdf_content;
+pub mod ocr;
+pub mod pdf_encryption;
+pub mod pdf_metadata;
+pub mod pdf_imposition;
+pub mod pdf_redaction;
+pub mod pdf_forms;
+pub mod pdf_annotations;
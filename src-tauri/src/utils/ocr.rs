@@ -0,0 +1,116 @@
+use lopdf::content::{Content, Operation};
+use lopdf::Object;
+
+use crate::utils::command_executor::{validate_output, CommandExecutor, TesseractExecutor};
+use crate::utils::pdf_content::{encode_win_ansi, helvetica_text_width};
+use crate::utils::pdf_pages::upright_text_matrix;
+
+/// A word recognised by Tesseract, with its box in image pixels
+#[derive(Debug, Clone)]
+pub struct OcrWord {
+    pub text: String,
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+    pub line: (u32, u32, u32), // Block, paragraph and line numbers, for grouping words into lines
+}
+
+/// Recognises the plain text of an image
+pub fn recognize_text(image_path: &str, language: &str) -> Result<String, String> {
+    let output = TesseractExecutor.execute(&[image_path, "stdout", "-l", language])?;
+    validate_output(&output)?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim_end().to_string())
+}
+
+/// Recognises the words of an image along with their positions
+pub fn recognize_words(image_path: &str, language: &str) -> Result<Vec<OcrWord>, String> {
+    let output = TesseractExecutor.execute(&[image_path, "stdout", "-l", language, "tsv"])?;
+    validate_output(&output)?;
+
+    // Columns: level page_num block_num par_num line_num word_num left top width height conf text
+    let tsv = String::from_utf8_lossy(&output.stdout);
+    let words = tsv
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.splitn(12, '\t').collect();
+            if fields.len() < 12 || fields[0] != "5" || fields[11].trim().is_empty() {
+                return None;
+            }
+            let number = |i: usize| fields[i].parse::<f32>().ok();
+            Some(OcrWord {
+                text: fields[11].trim().to_string(),
+                left: number(6)?,
+                top: number(7)?,
+                width: number(8)?,
+                height: number(9)?,
+                line: (fields[2].parse().ok()?, fields[3].parse().ok()?, fields[4].parse().ok()?),
+            })
+        })
+        .collect();
+
+    Ok(words)
+}
+
+/// Joins recognised words back into text, one line per OCR line and a blank line between blocks
+pub fn words_to_text(words: &[OcrWord]) -> String {
+    let mut text = String::new();
+    let mut previous: Option<(u32, u32, u32)> = None;
+    for word in words {
+        match previous {
+            Some(line) if line == word.line => text.push(' '),
+            Some(line) if line.0 == word.line.0 && line.1 == word.line.1 => text.push('\n'),
+            Some(_) => text.push_str("\n\n"),
+            None => {}
+        }
+        text.push_str(&word.text);
+        previous = Some(word.line);
+    }
+    text
+}
+
+/// Builds an invisible text layer (render mode 3) placing each word over its spot on a page.
+/// `image_size` is the pixel size of the rendered page, `visible_box` its CropBox and `rotation`
+/// its /Rotate, so words line up however the page is rotated. Text is set in the WinAnsi-encoded
+/// Helvetica resource `font`, stretched to each word's width.
+pub fn invisible_text_layer(
+    words: &[OcrWord],
+    image_size: (u32, u32),
+    visible_box: [f32; 4],
+    rotation: i64,
+    font: &str,
+) -> Result<Vec<u8>, String> {
+    let [x0, y0, x1, y1] = visible_box;
+    let sideways = rotation == 90 || rotation == 270;
+    let displayed_width = if sideways { y1 - y0 } else { x1 - x0 };
+    let scale = displayed_width / image_size.0.max(1) as f32;
+
+    let mut operations = vec![
+        Operation::new("BT", vec![]),
+        Operation::new("Tr", vec![3.into()]),
+    ];
+    for word in words {
+        let encoded = encode_win_ansi(&word.text);
+        let font_size = word.height * scale;
+        let natural_width = helvetica_text_width(&encoded, font_size);
+        if font_size <= 0.0 || natural_width <= 0.0 {
+            continue;
+        }
+
+        // Baseline a fifth of the box height above its bottom, so the glyph boxes cover the word
+        let matrix = upright_text_matrix(visible_box, rotation, word.left * scale, (word.top + word.height * 0.8) * scale);
+        let horizontal_scale = 100.0 * word.width * scale / natural_width;
+        operations.extend([
+            Operation::new("Tf", vec![Object::Name(font.as_bytes().to_vec()), Object::Real(font_size)]),
+            Operation::new("Tz", vec![Object::Real(horizontal_scale)]),
+            Operation::new("Tm", matrix.iter().map(|&v| Object::Real(v)).collect()),
+            Operation::new("Tj", vec![Object::string_literal(encoded)]),
+        ]);
+    }
+    operations.push(Operation::new("ET", vec![]));
+
+    Content { operations }
+        .encode()
+        .map_err(|e| format!("Failed to encode text layer: {}", e))
+}
@@ -1,5 +1,8 @@
 use std::path::Path;
 
+/// File extensions treated as images when scanning folders
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "bmp", "tif", "tiff", "ico"];
+
 /// Generates an output path for compressed/modified files
 /// 
 /// # Arguments
@@ -31,3 +34,55 @@ pub fn get_temp_path(prefix: &str, extension: &str) -> String {
     let file_name = format!("{}_{}.{}", prefix, timestamp, extension);
     temp_dir.join(file_name).to_string_lossy().to_string()
 }
+
+/// Lists image files in a directory (sorted by path), optionally descending into subdirectories
+pub fn collect_image_files(dir: &str, recursive: bool) -> Result<Vec<String>, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![std::path::PathBuf::from(dir)];
+
+    while let Some(current) = pending.pop() {
+        let entries = std::fs::read_dir(&current)
+            .map_err(|e| format!("Failed to read directory {}: {}", current.display(), e))?;
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+                continue;
+            }
+
+            let is_image = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+                .unwrap_or(false);
+            if is_image {
+                files.push(path.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Replaces characters that are not allowed in file names on common platforms
+pub fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let trimmed = cleaned.trim().trim_end_matches('.');
+    if trimmed.is_empty() {
+        "untitled".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
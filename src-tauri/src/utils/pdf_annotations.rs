@@ -0,0 +1,488 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::utils::pdf_content::{add_page_content, add_page_resource, encode_win_ansi, helvetica_text_width, standard_font};
+use crate::utils::pdf_metadata::{format_pdf_date, parse_pdf_date};
+use crate::utils::pdf_pages::page_rotation;
+use crate::utils::pdf_strings::{decode_text_string, encode_text_string};
+
+// Annotation flags (F)
+const HIDDEN: i64 = 1 << 1;
+const PRINT: i64 = 1 << 2;
+const NO_ZOOM: i64 = 1 << 3;
+const NO_ROTATE: i64 = 1 << 4;
+const NO_VIEW: i64 = 1 << 5;
+
+/// Size in points of a sticky note's icon
+const NOTE_ICON_SIZE: f32 = 20.0;
+
+/// An annotation on a page, as listed to the user
+#[derive(Debug, Clone, Serialize)]
+pub struct AnnotationInfo {
+    pub page_number: u32,
+    pub index: usize,              // Position in the page's annotation list
+    pub kind: String,              // Subtype: "Highlight", "Text" (sticky note), "FreeText", "Link", "Stamp"...
+    pub contents: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub modified: Option<String>,  // RFC 3339 when the date could be read, as written otherwise
+    pub rect: [f32; 4],            // PDF user space
+    pub color: Option<String>,     // Hex, e.g. "#FFFF00"
+    pub uri: Option<String>,       // Web address a link opens
+}
+
+/// A page's annotation array, following an indirect reference
+pub fn page_annotations(doc: &Document, page_id: ObjectId) -> Vec<Object> {
+    doc.get_dictionary(page_id)
+        .and_then(|page| page.get_deref(b"Annots", doc))
+        .and_then(Object::as_array)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// A rectangle array (Rect, BBox) as normalised [x0, y0, x1, y1]
+pub fn rect_values(doc: &Document, rect: &Object) -> Option<[f32; 4]> {
+    let values: Vec<f32> = doc
+        .dereference(rect)
+        .ok()?
+        .1
+        .as_array()
+        .ok()?
+        .iter()
+        .filter_map(|v| doc.dereference(v).ok().and_then(|(_, v)| v.as_float().ok()))
+        .collect();
+    match values.as_slice() {
+        [a, b, c, d] => Some([a.min(*c), b.min(*d), a.max(*c), b.max(*d)]),
+        _ => None,
+    }
+}
+
+/// Whether an annotation's subtype matches a kind given by the user: a subtype name in any
+/// case ("Highlight", "freetext"), "free_text", or "note" for sticky notes
+pub fn kind_matches(subtype: &[u8], kind: &str) -> bool {
+    let kind: String = kind.chars().filter(|c| !matches!(c, '_' | '-' | ' ')).collect::<String>().to_lowercase();
+    let kind = if kind == "note" { "text".to_string() } else { kind };
+    String::from_utf8_lossy(subtype).to_lowercase() == kind
+}
+
+fn subtype(annotation: &Dictionary) -> &[u8] {
+    annotation.get(b"Subtype").and_then(Object::as_name).unwrap_or(b"")
+}
+
+/// Lists the annotations of every page. Form field widgets and pop-up windows are left out,
+/// as they belong to form fields and other annotations.
+pub fn list_annotations(doc: &Document) -> Vec<AnnotationInfo> {
+    let text = |annotation: &Dictionary, key: &[u8]| {
+        annotation.get_deref(key, doc).and_then(Object::as_str).ok().map(decode_text_string)
+    };
+
+    let mut annotations = Vec::new();
+    for (page_number, page_id) in doc.get_pages() {
+        for (index, annotation) in page_annotations(doc, page_id).iter().enumerate() {
+            let Ok((_, Object::Dictionary(annotation))) = doc.dereference(annotation) else {
+                continue;
+            };
+            if matches!(subtype(annotation), b"Widget" | b"Popup") {
+                continue;
+            }
+
+            let modified = text(annotation, b"M").map(|date| match parse_pdf_date(&date) {
+                Some(parsed) => parsed.to_rfc3339(),
+                None => date,
+            });
+            let uri = annotation
+                .get_deref(b"A", doc)
+                .and_then(Object::as_dict)
+                .and_then(|action| action.get_deref(b"URI", doc))
+                .and_then(Object::as_str)
+                .ok()
+                .map(|uri| String::from_utf8_lossy(uri).to_string());
+
+            annotations.push(AnnotationInfo {
+                page_number,
+                index,
+                kind: String::from_utf8_lossy(subtype(annotation)).to_string(),
+                contents: text(annotation, b"Contents"),
+                author: text(annotation, b"T"),
+                subject: text(annotation, b"Subj"),
+                modified,
+                rect: annotation.get(b"Rect").ok().and_then(|r| rect_values(doc, r)).unwrap_or_default(),
+                color: annotation_color(doc, annotation),
+                uri,
+            });
+        }
+    }
+    annotations
+}
+
+/// The annotation's /C colour as hex; grey and CMYK colours are converted to RGB
+fn annotation_color(doc: &Document, annotation: &Dictionary) -> Option<String> {
+    let components: Vec<f32> = annotation
+        .get_deref(b"C", doc)
+        .and_then(Object::as_array)
+        .ok()?
+        .iter()
+        .filter_map(|c| c.as_float().ok())
+        .collect();
+    let rgb = match components.as_slice() {
+        [gray] => [*gray; 3],
+        [r, g, b] => [*r, *g, *b],
+        [c, m, y, k] => [(1.0 - c) * (1.0 - k), (1.0 - m) * (1.0 - k), (1.0 - y) * (1.0 - k)],
+        _ => return None,
+    };
+    let [r, g, b] = rgb.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    Some(format!("#{:02X}{:02X}{:02X}", r, g, b))
+}
+
+/// Common entries of a new markup annotation
+fn markup_annotation(subtype: &str, rect: [f32; 4], color: [f32; 3], contents: Option<&str>, author: Option<&str>) -> Dictionary {
+    let mut annotation = dictionary! {
+        "Type" => "Annot",
+        "Subtype" => subtype,
+        "Rect" => rect.iter().map(|&v| Object::Real(v)).collect::<Vec<_>>(),
+        "C" => color.iter().map(|&v| Object::Real(v)).collect::<Vec<_>>(),
+        "F" => PRINT,
+        "M" => Object::string_literal(format_pdf_date(&chrono::Local::now().fixed_offset())),
+        "NM" => Object::string_literal(format!("{:032x}", rand::random::<u128>())),
+    };
+    if let Some(contents) = contents.filter(|c| !c.is_empty()) {
+        annotation.set("Contents", Object::String(encode_text_string(contents), lopdf::StringFormat::Literal));
+    }
+    if let Some(author) = author.filter(|a| !a.is_empty()) {
+        annotation.set("T", Object::String(encode_text_string(author), lopdf::StringFormat::Literal));
+    }
+    annotation
+}
+
+/// Adds the annotation to the page's annotation list
+fn attach_annotation(doc: &mut Document, page_id: ObjectId, mut annotation: Dictionary) -> Result<ObjectId, String> {
+    annotation.set("P", Object::Reference(page_id));
+    let id = doc.add_object(annotation);
+    let mut annotations = page_annotations(doc, page_id);
+    annotations.push(Object::Reference(id));
+    doc.get_dictionary_mut(page_id)
+        .map_err(|e| format!("Failed to get page object: {}", e))?
+        .set("Annots", Object::Array(annotations));
+    Ok(id)
+}
+
+/// An appearance stream: a form XObject drawing `operations` into `bbox`
+fn appearance_stream(
+    doc: &mut Document,
+    bbox: [f32; 4],
+    matrix: Option<[f32; 6]>,
+    resources: Dictionary,
+    operations: Vec<Operation>,
+) -> Result<ObjectId, String> {
+    let content = Content { operations }
+        .encode()
+        .map_err(|e| format!("Failed to encode annotation appearance: {}", e))?;
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Form",
+        "BBox" => bbox.iter().map(|&v| Object::Real(v)).collect::<Vec<_>>(),
+        "Resources" => resources,
+    };
+    if let Some(matrix) = matrix {
+        dict.set("Matrix", matrix.iter().map(|&v| Object::Real(v)).collect::<Vec<_>>());
+    }
+    Ok(doc.add_object(Stream::new(dict, content)))
+}
+
+fn reals(values: &[f32]) -> Vec<Object> {
+    values.iter().map(|&v| Object::Real(v)).collect()
+}
+
+/// Adds a highlight over the given rectangles (one per line of text), blended like a marker pen
+pub fn add_highlight(
+    doc: &mut Document,
+    page_id: ObjectId,
+    rects: &[[f32; 4]],
+    color: [f32; 3],
+    contents: Option<&str>,
+    author: Option<&str>,
+) -> Result<ObjectId, String> {
+    let bounds = rects.iter().skip(1).fold(rects[0], |b, r| [b[0].min(r[0]), b[1].min(r[1]), b[2].max(r[2]), b[3].max(r[3])]);
+
+    // Quadrilaterals run top-left, top-right, bottom-left, bottom-right, as viewers expect
+    let quad_points: Vec<f32> = rects.iter().flat_map(|&[x0, y0, x1, y1]| [x0, y1, x1, y1, x0, y0, x1, y0]).collect();
+
+    let mut operations = vec![
+        Operation::new("gs", vec![Object::Name(b"GS0".to_vec())]),
+        Operation::new("rg", reals(&color)),
+    ];
+    for [x0, y0, x1, y1] in rects {
+        operations.push(Operation::new("re", reals(&[*x0, *y0, x1 - x0, y1 - y0])));
+    }
+    operations.push(Operation::new("f", vec![]));
+    let resources = dictionary! {
+        "ExtGState" => dictionary! { "GS0" => dictionary! { "Type" => "ExtGState", "BM" => "Multiply" } },
+    };
+    let appearance = appearance_stream(doc, bounds, None, resources, operations)?;
+
+    let mut annotation = markup_annotation("Highlight", bounds, color, contents, author);
+    annotation.set("QuadPoints", reals(&quad_points));
+    annotation.set("AP", dictionary! { "N" => appearance });
+    attach_annotation(doc, page_id, annotation)
+}
+
+/// Adds a sticky note whose icon's top-left corner is at (x, y)
+pub fn add_note(
+    doc: &mut Document,
+    page_id: ObjectId,
+    x: f32,
+    y: f32,
+    color: [f32; 3],
+    contents: Option<&str>,
+    author: Option<&str>,
+) -> Result<ObjectId, String> {
+    let size = NOTE_ICON_SIZE;
+    let rect = [x, y - size, x + size, y];
+
+    // A speech bubble with three lines of "text"
+    let operations = vec![
+        Operation::new("rg", reals(&color)),
+        Operation::new("G", vec![Object::Integer(0)]),
+        Operation::new("w", vec![Object::Real(0.6)]),
+        Operation::new("re", reals(&[1.0, 5.0, size - 2.0, size - 6.0])),
+        Operation::new("B", vec![]),
+        Operation::new("m", reals(&[4.0, 5.0])),
+        Operation::new("l", reals(&[3.0, 1.0])),
+        Operation::new("l", reals(&[9.0, 5.0])),
+        Operation::new("b", vec![]),
+        Operation::new("m", reals(&[4.0, 15.0])),
+        Operation::new("l", reals(&[size - 4.0, 15.0])),
+        Operation::new("m", reals(&[4.0, 12.0])),
+        Operation::new("l", reals(&[size - 4.0, 12.0])),
+        Operation::new("m", reals(&[4.0, 9.0])),
+        Operation::new("l", reals(&[size - 8.0, 9.0])),
+        Operation::new("S", vec![]),
+    ];
+    let appearance = appearance_stream(doc, [0.0, 0.0, size, size], None, Dictionary::new(), operations)?;
+
+    let mut annotation = markup_annotation("Text", rect, color, contents, author);
+    annotation.set("Name", Object::Name(b"Comment".to_vec()));
+    // Icons keep their size and stay upright, like in viewers
+    annotation.set("F", PRINT | NO_ZOOM | NO_ROTATE);
+    annotation.set("AP", dictionary! { "N" => appearance });
+    attach_annotation(doc, page_id, annotation)
+}
+
+/// Adds a text box with a thin border, its text wrapped to the box and upright on rotated pages
+pub fn add_free_text(
+    doc: &mut Document,
+    page_id: ObjectId,
+    rect: [f32; 4],
+    text: &str,
+    font_size: f32,
+    color: [f32; 3],
+    author: Option<&str>,
+) -> Result<ObjectId, String> {
+    let rotation = page_rotation(doc, page_id);
+    let (mut width, mut height) = (rect[2] - rect[0], rect[3] - rect[1]);
+    if rotation == 90 || rotation == 270 {
+        std::mem::swap(&mut width, &mut height);
+    }
+    // Turn the box so its text reads upright once the page is rotated clockwise for display
+    let matrix = match rotation {
+        90 => [0.0, 1.0, -1.0, 0.0, 0.0, 0.0],
+        180 => [-1.0, 0.0, 0.0, -1.0, 0.0, 0.0],
+        270 => [0.0, -1.0, 1.0, 0.0, 0.0, 0.0],
+        _ => [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+    };
+
+    let padding = 3.0;
+    let mut lines: Vec<Vec<u8>> = Vec::new();
+    for paragraph in text.lines() {
+        let mut current: Vec<u8> = Vec::new();
+        for word in encode_win_ansi(paragraph).split(|&b| b == b' ') {
+            let candidate = if current.is_empty() { word.to_vec() } else { [current.as_slice(), b" ", word].concat() };
+            if !current.is_empty() && helvetica_text_width(&candidate, font_size) > width - 2.0 * padding {
+                lines.push(std::mem::replace(&mut current, word.to_vec()));
+            } else {
+                current = candidate;
+            }
+        }
+        lines.push(current);
+    }
+
+    let mut operations = vec![
+        Operation::new("RG", reals(&color)),
+        Operation::new("w", vec![Object::Real(0.75)]),
+        Operation::new("re", reals(&[0.375, 0.375, width - 0.75, height - 0.75])),
+        Operation::new("S", vec![]),
+        Operation::new("re", reals(&[1.0, 1.0, width - 2.0, height - 2.0])),
+        Operation::new("W", vec![]),
+        Operation::new("n", vec![]),
+        Operation::new("BT", vec![]),
+        Operation::new("rg", reals(&color)),
+        Operation::new("Tf", vec![Object::Name(b"Helv".to_vec()), Object::Real(font_size)]),
+    ];
+    for (index, line) in lines.iter().enumerate() {
+        let baseline = height - padding - font_size * 0.9 - index as f32 * font_size * 1.2;
+        operations.push(Operation::new("Tm", reals(&[1.0, 0.0, 0.0, 1.0, padding, baseline])));
+        operations.push(Operation::new("Tj", vec![Object::string_literal(line.clone())]));
+    }
+    operations.push(Operation::new("ET", vec![]));
+
+    let font = standard_font(doc, "Helvetica");
+    let resources = dictionary! { "Font" => dictionary! { "Helv" => font } };
+    let appearance = appearance_stream(doc, [0.0, 0.0, width, height], Some(matrix), resources, operations)?;
+
+    let [r, g, b] = color;
+    let mut annotation = markup_annotation("FreeText", rect, color, Some(text), author);
+    annotation.set("DA", Object::string_literal(format!("/Helv {} Tf {} {} {} rg", font_size, r, g, b)));
+    annotation.set("DR", dictionary! { "Font" => dictionary! { "Helv" => font } });
+    if rotation != 0 {
+        annotation.set("Rotate", rotation);
+    }
+    annotation.set("AP", dictionary! { "N" => appearance });
+    attach_annotation(doc, page_id, annotation)
+}
+
+/// Removes the page annotations `select` picks, with their pop-up windows. Returns how many were removed.
+pub fn remove_annotations(doc: &mut Document, page_id: ObjectId, select: impl Fn(&Dictionary) -> bool) -> Result<usize, String> {
+    let (selected, kept) = split_annotations(doc, page_id, &select);
+    set_annotations(doc, page_id, kept)?;
+    Ok(selected.len())
+}
+
+/// Draws the current appearance of the page annotations `select` picks into the page content
+/// and removes them. Hidden ones and ones without an appearance are removed without drawing.
+/// Returns how many were drawn.
+pub fn flatten_annotations(doc: &mut Document, page_id: ObjectId, select: impl Fn(&Dictionary) -> bool) -> Result<usize, String> {
+    let (selected, kept) = split_annotations(doc, page_id, &select);
+
+    let mut operations = Vec::new();
+    for annotation in &selected {
+        let flags = annotation.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+        if flags & (HIDDEN | NO_VIEW) != 0 {
+            continue;
+        }
+        let rect = annotation.get(b"Rect").ok().and_then(|r| rect_values(doc, r));
+        let Some((appearance_id, rect)) = annotation_appearance(doc, annotation).zip(rect) else {
+            continue;
+        };
+        let Some(matrix) = appearance_matrix(doc, appearance_id, rect) else {
+            continue;
+        };
+
+        let name = add_page_resource(doc, page_id, "XObject", "FmFl", Object::Reference(appearance_id))?;
+        operations.extend([
+            Operation::new("q", vec![]),
+            Operation::new("cm", reals(&matrix)),
+            Operation::new("Do", vec![Object::Name(name.into_bytes())]),
+            Operation::new("Q", vec![]),
+        ]);
+    }
+
+    let drawn = operations.len() / 4;
+    if !operations.is_empty() {
+        let content = Content { operations }
+            .encode()
+            .map_err(|e| format!("Failed to encode page content: {}", e))?;
+        add_page_content(doc, page_id, content, false)?;
+    }
+    set_annotations(doc, page_id, kept)?;
+    Ok(drawn)
+}
+
+/// Splits a page's annotations into the selected ones and the rest, dropping the pop-ups
+/// of selected annotations from the rest
+fn split_annotations(doc: &Document, page_id: ObjectId, select: &impl Fn(&Dictionary) -> bool) -> (Vec<Dictionary>, Vec<Object>) {
+    let annotations = page_annotations(doc, page_id);
+    let mut selected = Vec::new();
+    let mut selected_ids = HashSet::new();
+    let mut rest = Vec::new();
+    for annotation in annotations {
+        match doc.dereference(&annotation) {
+            Ok((id, Object::Dictionary(dict))) if subtype(dict) != b"Popup" && select(dict) => {
+                selected_ids.extend(id);
+                selected.push(dict.clone());
+            }
+            _ => rest.push(annotation),
+        }
+    }
+
+    let kept = rest
+        .into_iter()
+        .filter(|annotation| {
+            let parent = doc
+                .dereference(annotation)
+                .ok()
+                .and_then(|(_, a)| a.as_dict().ok())
+                .filter(|a| subtype(a) == b"Popup")
+                .and_then(|a| a.get(b"Parent").and_then(Object::as_reference).ok());
+            !parent.is_some_and(|parent| selected_ids.contains(&parent))
+        })
+        .collect();
+    (selected, kept)
+}
+
+fn set_annotations(doc: &mut Document, page_id: ObjectId, annotations: Vec<Object>) -> Result<(), String> {
+    let page = doc
+        .get_dictionary_mut(page_id)
+        .map_err(|e| format!("Failed to get page object: {}", e))?;
+    if annotations.is_empty() {
+        page.remove(b"Annots");
+    } else {
+        page.set("Annots", Object::Array(annotations));
+    }
+    Ok(())
+}
+
+/// The annotation's normal appearance stream for its current state, as an object that can be drawn
+fn annotation_appearance(doc: &mut Document, annotation: &Dictionary) -> Option<ObjectId> {
+    let normal = annotation.get_deref(b"AP", doc).and_then(Object::as_dict).ok()?.get(b"N").ok()?.clone();
+    let appearance = match doc.dereference(&normal).ok()? {
+        (_, Object::Dictionary(states)) => {
+            let state = annotation.get(b"AS").and_then(Object::as_name).ok()?;
+            states.get(state).ok()?.clone()
+        }
+        _ => normal,
+    };
+
+    let id = match appearance {
+        Object::Reference(id) => id,
+        Object::Stream(stream) => doc.add_object(stream),
+        _ => return None,
+    };
+    // Appearance streams are drawn as form XObjects, which must say so
+    let Ok(Object::Stream(stream)) = doc.get_object_mut(id) else {
+        return None;
+    };
+    stream.dict.set("Type", Object::Name(b"XObject".to_vec()));
+    stream.dict.set("Subtype", Object::Name(b"Form".to_vec()));
+    Some(id)
+}
+
+/// Matrix fitting an appearance's transformed bounding box into the annotation rectangle
+fn appearance_matrix(doc: &Document, appearance_id: ObjectId, rect: [f32; 4]) -> Option<[f32; 6]> {
+    let Ok(Object::Stream(stream)) = doc.get_object(appearance_id) else {
+        return None;
+    };
+    let bbox = rect_values(doc, stream.dict.get(b"BBox").ok()?)?;
+    let m: Vec<f32> = stream
+        .dict
+        .get(b"Matrix")
+        .and_then(Object::as_array)
+        .map(|m| m.iter().filter_map(|v| v.as_float().ok()).collect())
+        .unwrap_or_default();
+    let m = <[f32; 6]>::try_from(m.as_slice()).unwrap_or([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    let corners = [(bbox[0], bbox[1]), (bbox[0], bbox[3]), (bbox[2], bbox[1]), (bbox[2], bbox[3])]
+        .map(|(x, y)| (x * m[0] + y * m[2] + m[4], x * m[1] + y * m[3] + m[5]));
+    let min_x = corners.iter().map(|p| p.0).fold(f32::INFINITY, f32::min);
+    let max_x = corners.iter().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max);
+    let min_y = corners.iter().map(|p| p.1).fold(f32::INFINITY, f32::min);
+    let max_y = corners.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+    if max_x - min_x <= 0.0 || max_y - min_y <= 0.0 {
+        return None;
+    }
+
+    let scale_x = (rect[2] - rect[0]) / (max_x - min_x);
+    let scale_y = (rect[3] - rect[1]) / (max_y - min_y);
+    Some([scale_x, 0.0, 0.0, scale_y, rect[0] - min_x * scale_x, rect[1] - min_y * scale_y])
+}
@@ -0,0 +1,137 @@
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::utils::pdf_merge::find_inherited_attribute;
+
+/// Helvetica advance widths (1/1000 em) for ASCII 32-126
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // space - /
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, // 0 - ?
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, // @ - O
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, // P - _
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, // ` - o
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // p - ~
+];
+
+/// Gives the page its own direct `/Resources` dictionary (copying inherited or shared
+/// ones, including their category dictionaries) so entries can be added safely
+pub fn page_resources_mut(doc: &mut Document, page_id: ObjectId) -> Result<&mut Dictionary, String> {
+    let mut resources = find_inherited_attribute(doc, page_id, b"Resources")
+        .and_then(|value| doc.dereference(value).ok())
+        .and_then(|(_, value)| value.as_dict().ok().cloned())
+        .unwrap_or_default();
+
+    for (_, category) in resources.iter_mut() {
+        if let Ok((_, Object::Dictionary(entries))) = doc.dereference(category) {
+            *category = Object::Dictionary(entries.clone());
+        }
+    }
+
+    let page = doc
+        .get_dictionary_mut(page_id)
+        .map_err(|e| format!("Failed to get page object: {}", e))?;
+    page.set("Resources", Object::Dictionary(resources));
+    page.get_mut(b"Resources")
+        .and_then(Object::as_dict_mut)
+        .map_err(|e| format!("Failed to get page resources: {}", e))
+}
+
+/// Adds a resource (font, XObject, ExtGState...) to the page under a fresh name
+/// starting with `prefix`, returning that name
+pub fn add_page_resource(
+    doc: &mut Document,
+    page_id: ObjectId,
+    category: &str,
+    prefix: &str,
+    object: Object,
+) -> Result<String, String> {
+    let resources = page_resources_mut(doc, page_id)?;
+    if !matches!(resources.get(category.as_bytes()), Ok(Object::Dictionary(_))) {
+        resources.set(category, Dictionary::new());
+    }
+    let entries = resources
+        .get_mut(category.as_bytes())
+        .and_then(Object::as_dict_mut)
+        .map_err(|e| format!("Failed to get page resources: {}", e))?;
+
+    let name = (1..)
+        .map(|n| format!("{}{}", prefix, n))
+        .find(|name| !entries.has(name.as_bytes()))
+        .unwrap_or_else(|| prefix.to_string());
+    entries.set(name.clone(), object);
+    Ok(name)
+}
+
+/// Adds a content stream to the page, either painted over the existing content or beneath it.
+/// Existing content is wrapped in q/Q first, so graphics state it leaves behind can't leak into the overlay.
+pub fn add_page_content(doc: &mut Document, page_id: ObjectId, mut content: Vec<u8>, beneath: bool) -> Result<(), String> {
+    // Keep the last token from running into the next stream's first one
+    if !content.ends_with(b"\n") {
+        content.push(b'\n');
+    }
+
+    let existing: Vec<Object> = match doc.get_dictionary(page_id).and_then(|page| page.get(b"Contents")) {
+        Ok(Object::Reference(id)) => vec![Object::Reference(*id)],
+        Ok(Object::Array(items)) => items.clone(),
+        _ => Vec::new(),
+    };
+
+    let new_id = doc.add_object(Stream::new(Dictionary::new(), content));
+    let contents: Vec<Object> = if beneath {
+        std::iter::once(Object::Reference(new_id)).chain(existing).collect()
+    } else if existing.is_empty() {
+        vec![Object::Reference(new_id)]
+    } else {
+        let save_id = doc.add_object(Stream::new(Dictionary::new(), b"q\n".to_vec()));
+        let restore_id = doc.add_object(Stream::new(Dictionary::new(), b"\nQ\n".to_vec()));
+        std::iter::once(Object::Reference(save_id))
+            .chain(existing)
+            .chain([Object::Reference(restore_id), Object::Reference(new_id)])
+            .collect()
+    };
+
+    doc.get_dictionary_mut(page_id)
+        .map_err(|e| format!("Failed to get page object: {}", e))?
+        .set("Contents", Object::Array(contents));
+    Ok(())
+}
+
+/// Adds one of the 14 standard Type 1 fonts (e.g. "Helvetica") with WinAnsi encoding
+pub fn standard_font(doc: &mut Document, base_font: &str) -> ObjectId {
+    doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => base_font,
+        "Encoding" => "WinAnsiEncoding",
+    })
+}
+
+/// Encodes text for a WinAnsi-encoded simple font; characters it can't represent become '?'
+pub fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            '\u{20AC}' => 0x80,
+            '\u{2018}' => 0x91,
+            '\u{2019}' => 0x92,
+            '\u{201C}' => 0x93,
+            '\u{201D}' => 0x94,
+            '\u{2022}' => 0x95,
+            '\u{2013}' => 0x96,
+            '\u{2014}' => 0x97,
+            '\u{2026}' => 0x85,
+            c if (c as u32) < 0x80 || (0xA0..=0xFF).contains(&(c as u32)) => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Width in points of WinAnsi-encoded text set in Helvetica at `size`
+pub fn helvetica_text_width(encoded: &[u8], size: f32) -> f32 {
+    let units: u32 = encoded
+        .iter()
+        .map(|&b| match b {
+            32..=126 => HELVETICA_WIDTHS[(b - 32) as usize] as u32,
+            _ => 556,
+        })
+        .sum();
+    units as f32 * size / 1000.0
+}
@@ -0,0 +1,858 @@
+use aes::cipher::block_padding::{NoPadding, Pkcs7};
+use aes::cipher::{BlockCipher, BlockDecryptMut, BlockEncryptMut, KeyInit, KeyIvInit};
+use aes::{Aes128, Aes192, Aes256};
+use lopdf::xref::XrefEntry;
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, ObjectStream, Reader, StringFormat};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::collections::{BTreeSet, HashSet};
+
+/// Error for an encrypted PDF opened without its password; the UI asks for one when it sees this
+pub const PASSWORD_REQUIRED: &str = "Password required";
+/// Error for a password that is neither the user nor the owner password
+pub const INCORRECT_PASSWORD: &str = "Incorrect password";
+
+const PASSWORD_PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08, 0x2E, 0x2E, 0x00,
+    0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// Encryption schemes of the standard security handler that can be written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EncryptionAlgorithm {
+    Rc4,    // 128-bit RC4 (revision 3), for old readers only
+    Aes128, // AESV2 (revision 4)
+    Aes256, // AESV3 (revision 6)
+}
+
+impl EncryptionAlgorithm {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().replace('-', "").as_str() {
+            "rc4" | "rc4128" => Ok(EncryptionAlgorithm::Rc4),
+            "aes128" => Ok(EncryptionAlgorithm::Aes128),
+            "aes256" | "aes" => Ok(EncryptionAlgorithm::Aes256),
+            _ => Err(format!("Unsupported encryption algorithm: {}", name)),
+        }
+    }
+}
+
+/// What someone opening the document with the user password is allowed to do
+#[derive(Debug, Clone, Copy)]
+pub struct Permissions {
+    pub print: bool,
+    pub copy: bool,
+    pub modify: bool,
+    pub annotate: bool,
+}
+
+impl Permissions {
+    /// The /P value: reserved bits set, plus the granted permission bits
+    fn flags(&self) -> i32 {
+        // Extracting text for accessibility is always allowed
+        let mut flags: u32 = 0xFFFF_F0C0 | 1 << 9;
+        if self.print {
+            flags |= 1 << 2 | 1 << 11; // Print, in high quality
+        }
+        if self.modify {
+            flags |= 1 << 3 | 1 << 10; // Modify, assemble pages
+        }
+        if self.copy {
+            flags |= 1 << 4;
+        }
+        if self.annotate {
+            flags |= 1 << 5 | 1 << 8; // Annotate, fill in forms
+        }
+        flags as i32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CryptMethod {
+    Identity,
+    Rc4,
+    AesV2,
+    AesV3,
+}
+
+/// The file key and methods used to encrypt or decrypt a document's strings and streams
+#[derive(Debug, Clone)]
+struct SecurityHandler {
+    file_key: Vec<u8>,
+    string_method: CryptMethod,
+    stream_method: CryptMethod,
+    encrypt_metadata: bool,
+}
+
+impl SecurityHandler {
+    /// Per-object key (Algorithm 1); AES-256 uses the file key directly
+    fn object_key(&self, id: ObjectId, method: CryptMethod) -> Vec<u8> {
+        if method == CryptMethod::AesV3 {
+            return self.file_key.clone();
+        }
+        let mut material = self.file_key.clone();
+        material.extend_from_slice(&id.0.to_le_bytes()[..3]);
+        material.extend_from_slice(&id.1.to_le_bytes()[..2]);
+        if method == CryptMethod::AesV2 {
+            material.extend_from_slice(b"sAlT");
+        }
+        let length = (self.file_key.len() + 5).min(16);
+        md5::compute(&material)[..length].to_vec()
+    }
+
+    fn encrypt_bytes(&self, id: ObjectId, data: &[u8], method: CryptMethod) -> Result<Vec<u8>, String> {
+        let key = self.object_key(id, method);
+        match method {
+            CryptMethod::Identity => Ok(data.to_vec()),
+            CryptMethod::Rc4 => Ok(rc4(&key, data)),
+            CryptMethod::AesV2 | CryptMethod::AesV3 => {
+                // PKCS#7 padding and a random IV in front of the ciphertext
+                let iv: [u8; 16] = rand::random();
+                let mut encrypted = iv.to_vec();
+                encrypted.extend(aes_cbc_encrypt(&key, &iv, data, true)?);
+                Ok(encrypted)
+            }
+        }
+    }
+
+    fn decrypt_bytes(&self, id: ObjectId, data: &[u8], method: CryptMethod) -> Result<Vec<u8>, String> {
+        let key = self.object_key(id, method);
+        match method {
+            CryptMethod::Identity => Ok(data.to_vec()),
+            CryptMethod::Rc4 => Ok(rc4(&key, data)),
+            CryptMethod::AesV2 | CryptMethod::AesV3 => {
+                if data.len() < 16 {
+                    return Ok(Vec::new());
+                }
+                let iv: [u8; 16] = data[..16].try_into().unwrap_or_default();
+                aes_cbc_decrypt(&key, &iv, &data[16..], true)
+                    .map_err(|e| format!("Failed to decrypt object {} {}: {}", id.0, id.1, e))
+            }
+        }
+    }
+
+    /// Encrypts or decrypts every string and stream of an object in place
+    fn apply(&self, id: ObjectId, object: &mut Object, encrypt: bool) -> Result<(), String> {
+        let string_method = self.string_method;
+        let crypt = |data: &[u8], method: CryptMethod| {
+            if encrypt {
+                self.encrypt_bytes(id, data, method)
+            } else {
+                self.decrypt_bytes(id, data, method)
+            }
+        };
+
+        match object {
+            Object::Stream(stream) => {
+                // Cross-reference streams are never encrypted
+                if stream.dict.type_is(b"XRef") {
+                    return Ok(());
+                }
+                for (_, value) in stream.dict.iter_mut() {
+                    apply_to_strings(value, &mut |data| crypt(data, string_method))?;
+                }
+                if self.encrypt_metadata || !stream.dict.type_is(b"Metadata") {
+                    let content = crypt(&stream.content, self.stream_method)?;
+                    stream.set_content(content);
+                }
+                Ok(())
+            }
+            _ => apply_to_strings(object, &mut |data| crypt(data, string_method)),
+        }
+    }
+}
+
+/// How an encrypted PDF was protected, kept so an edited copy can be encrypted the same way
+/// without knowing its other password
+#[derive(Debug, Clone)]
+pub struct PdfSecurity {
+    handler: SecurityHandler,
+    encrypt_dict: Dictionary,
+    file_id: Object, // Trailer /ID, which keys before AES-256 are tied to
+}
+
+fn apply_to_strings<F>(object: &mut Object, crypt: &mut F) -> Result<(), String>
+where
+    F: FnMut(&[u8]) -> Result<Vec<u8>, String>,
+{
+    match object {
+        Object::String(data, _) => *data = crypt(data)?,
+        Object::Array(items) => {
+            for item in items {
+                apply_to_strings(item, crypt)?;
+            }
+        }
+        Object::Dictionary(dict) => {
+            for (_, value) in dict.iter_mut() {
+                apply_to_strings(value, crypt)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut state: [u8; 256] = std::array::from_fn(|i| i as u8);
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+        state.swap(i, j as usize);
+    }
+
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|&byte| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(state[i as usize]);
+            state.swap(i as usize, j as usize);
+            byte ^ state[state[i as usize].wrapping_add(state[j as usize]) as usize]
+        })
+        .collect()
+}
+
+/// AES-CBC encryption, PKCS#7-padded when `pad` is set and otherwise over whole blocks only
+fn aes_cbc_encrypt(key: &[u8], iv: &[u8; 16], data: &[u8], pad: bool) -> Result<Vec<u8>, String> {
+    fn encrypt<C: BlockEncryptMut + BlockCipher + KeyInit>(key: &[u8], iv: &[u8; 16], data: &[u8], pad: bool) -> Result<Vec<u8>, String> {
+        let encryptor = cbc::Encryptor::<C>::new_from_slices(key, iv)
+            .map_err(|e| format!("Invalid AES key: {}", e))?;
+        Ok(if pad {
+            encryptor.encrypt_padded_vec_mut::<Pkcs7>(data)
+        } else {
+            encryptor.encrypt_padded_vec_mut::<NoPadding>(data)
+        })
+    }
+
+    if !pad && !data.len().is_multiple_of(16) {
+        return Err("AES data must be a whole number of blocks".to_string());
+    }
+    match key.len() {
+        16 => encrypt::<Aes128>(key, iv, data, pad),
+        24 => encrypt::<Aes192>(key, iv, data, pad),
+        32 => encrypt::<Aes256>(key, iv, data, pad),
+        n => Err(format!("Invalid AES key length: {} bytes", n)),
+    }
+}
+
+/// AES-CBC decryption; with `pad` set, PKCS#7 padding is checked and removed
+fn aes_cbc_decrypt(key: &[u8], iv: &[u8; 16], data: &[u8], pad: bool) -> Result<Vec<u8>, String> {
+    fn decrypt<C: BlockDecryptMut + BlockCipher + KeyInit>(key: &[u8], iv: &[u8; 16], data: &[u8], pad: bool) -> Result<Vec<u8>, String> {
+        let decryptor = cbc::Decryptor::<C>::new_from_slices(key, iv)
+            .map_err(|e| format!("Invalid AES key: {}", e))?;
+        let decrypted = if pad {
+            decryptor.decrypt_padded_vec_mut::<Pkcs7>(data)
+        } else {
+            decryptor.decrypt_padded_vec_mut::<NoPadding>(data)
+        };
+        decrypted.map_err(|_| "Invalid AES padding".to_string())
+    }
+
+    match key.len() {
+        16 => decrypt::<Aes128>(key, iv, data, pad),
+        24 => decrypt::<Aes192>(key, iv, data, pad),
+        32 => decrypt::<Aes256>(key, iv, data, pad),
+        n => Err(format!("Invalid AES key length: {} bytes", n)),
+    }
+}
+
+/// Passwords up to revision 4 are Latin-1 bytes, padded or cut to 32
+fn padded_password(password: &str) -> Vec<u8> {
+    let mut bytes: Vec<u8> = password.chars().map(|c| if (c as u32) < 256 { c as u8 } else { b'?' }).take(32).collect();
+    bytes.extend_from_slice(&PASSWORD_PADDING[..32 - bytes.len()]);
+    bytes
+}
+
+/// Revision 6 passwords are UTF-8, at most 127 bytes
+fn utf8_password(password: &str) -> &[u8] {
+    let mut end = password.len().min(127);
+    while !password.is_char_boundary(end) {
+        end -= 1;
+    }
+    &password.as_bytes()[..end]
+}
+
+/// File key from the user password (Algorithm 2)
+fn legacy_file_key(
+    password: &[u8],
+    owner_hash: &[u8],
+    permissions: i32,
+    file_id: &[u8],
+    revision: i64,
+    key_length: usize,
+    encrypt_metadata: bool,
+) -> Vec<u8> {
+    let mut material = password.to_vec();
+    material.extend_from_slice(owner_hash);
+    material.extend_from_slice(&permissions.to_le_bytes());
+    material.extend_from_slice(file_id);
+    if revision >= 4 && !encrypt_metadata {
+        material.extend_from_slice(&[0xFF; 4]);
+    }
+
+    let mut key = md5::compute(&material).0.to_vec();
+    if revision >= 3 {
+        for _ in 0..50 {
+            key = md5::compute(&key[..key_length]).0.to_vec();
+        }
+    }
+    key.truncate(key_length);
+    key
+}
+
+/// RC4 key derived from the owner password (Algorithm 3, steps a-d)
+fn legacy_owner_key(owner_password: &str, revision: i64, key_length: usize) -> Vec<u8> {
+    let mut key = md5::compute(padded_password(owner_password)).0.to_vec();
+    if revision >= 3 {
+        for _ in 0..50 {
+            key = md5::compute(&key).0.to_vec();
+        }
+    }
+    key.truncate(key_length);
+    key
+}
+
+/// The /O entry (Algorithm 3)
+fn legacy_owner_hash(owner_password: &str, user_password: &str, revision: i64, key_length: usize) -> Vec<u8> {
+    let key = legacy_owner_key(owner_password, revision, key_length);
+    let mut hash = rc4(&key, &padded_password(user_password));
+    if revision >= 3 {
+        for round in 1..=19u8 {
+            let round_key: Vec<u8> = key.iter().map(|b| b ^ round).collect();
+            hash = rc4(&round_key, &hash);
+        }
+    }
+    hash
+}
+
+/// The /U entry (Algorithms 4 and 5)
+fn legacy_user_hash(file_key: &[u8], file_id: &[u8], revision: i64) -> Vec<u8> {
+    if revision == 2 {
+        return rc4(file_key, &PASSWORD_PADDING);
+    }
+    let mut material = PASSWORD_PADDING.to_vec();
+    material.extend_from_slice(file_id);
+    let mut hash = rc4(file_key, &md5::compute(&material).0);
+    for round in 1..=19u8 {
+        let round_key: Vec<u8> = file_key.iter().map(|b| b ^ round).collect();
+        hash = rc4(&round_key, &hash);
+    }
+    hash.resize(32, 0);
+    hash
+}
+
+/// Password hash of revisions 5 and 6 (Algorithm 2.B; revision 5 is a single SHA-256)
+fn password_hash(password: &[u8], salt: &[u8], user_key: &[u8], revision: i64) -> Result<Vec<u8>, String> {
+    let mut k = Sha256::new().chain_update(password).chain_update(salt).chain_update(user_key).finalize().to_vec();
+    if revision == 5 {
+        return Ok(k);
+    }
+
+    let mut round = 0usize;
+    let mut last_byte = 0u8;
+    while round < 64 || last_byte as usize > round - 32 {
+        let block = [password, &k, user_key].concat().repeat(64);
+        let iv: [u8; 16] = k[16..32].try_into().unwrap_or_default();
+        let e = aes_cbc_encrypt(&k[..16], &iv, &block, false)?;
+        k = match e[..16].iter().map(|&b| b as u32).sum::<u32>() % 3 {
+            0 => Sha256::digest(&e).to_vec(),
+            1 => Sha384::digest(&e).to_vec(),
+            _ => Sha512::digest(&e).to_vec(),
+        };
+        last_byte = e.last().copied().unwrap_or(0);
+        round += 1;
+    }
+    k.truncate(32);
+    Ok(k)
+}
+
+fn string_entry<'a>(dict: &'a Dictionary, key: &[u8]) -> &'a [u8] {
+    dict.get(key).and_then(Object::as_str).unwrap_or(&[])
+}
+
+fn first_file_id(doc: &Document) -> Vec<u8> {
+    doc.trailer
+        .get(b"ID")
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|ids| ids.first())
+        .and_then(|id| id.as_str().ok())
+        .map(|id| id.to_vec())
+        .unwrap_or_default()
+}
+
+/// Reads the /Encrypt dictionary and checks `password` against it, as user or owner password
+fn open_security_handler(doc: &Document, password: &str) -> Result<PdfSecurity, String> {
+    let encrypt = doc
+        .trailer
+        .get(b"Encrypt")
+        .map_err(|e| format!("Failed to read encryption dictionary: {}", e))
+        .and_then(|value| {
+            doc.dereference(value)
+                .map_err(|e| format!("Failed to read encryption dictionary: {}", e))
+        })
+        .and_then(|(_, value)| value.as_dict().map_err(|e| format!("Failed to read encryption dictionary: {}", e)))?;
+
+    let filter = encrypt.get(b"Filter").and_then(Object::as_name).unwrap_or(b"Standard");
+    if filter != b"Standard" {
+        return Err(format!(
+            "PDF uses the {} security handler, only password protection is supported",
+            String::from_utf8_lossy(filter)
+        ));
+    }
+
+    let version = encrypt.get(b"V").and_then(Object::as_i64).unwrap_or(0);
+    let revision = encrypt.get(b"R").and_then(Object::as_i64).unwrap_or(2);
+    let encrypt_metadata = encrypt.get(b"EncryptMetadata").and_then(Object::as_bool).unwrap_or(true);
+
+    let crypt_filter = |key: &[u8]| -> Result<CryptMethod, String> {
+        if version < 4 {
+            return Ok(CryptMethod::Rc4);
+        }
+        let name = encrypt.get(key).and_then(Object::as_name).unwrap_or(b"Identity");
+        if name == b"Identity" {
+            return Ok(CryptMethod::Identity);
+        }
+        let method = encrypt
+            .get(b"CF")
+            .and_then(Object::as_dict)
+            .and_then(|filters| filters.get(name))
+            .and_then(Object::as_dict)
+            .and_then(|filter| filter.get(b"CFM"))
+            .and_then(Object::as_name)
+            .unwrap_or(b"None");
+        match method {
+            b"None" => Ok(CryptMethod::Identity),
+            b"V2" => Ok(CryptMethod::Rc4),
+            b"AESV2" => Ok(CryptMethod::AesV2),
+            b"AESV3" => Ok(CryptMethod::AesV3),
+            other => Err(format!("Unsupported PDF encryption method: {}", String::from_utf8_lossy(other))),
+        }
+    };
+    let string_method = crypt_filter(b"StrF")?;
+    let stream_method = crypt_filter(b"StmF")?;
+
+    let owner_hash = string_entry(encrypt, b"O");
+    let user_hash = string_entry(encrypt, b"U");
+
+    let file_key = match revision {
+        2..=4 => {
+            let key_length = match version {
+                1 => 5,
+                _ => encrypt.get(b"Length").and_then(Object::as_i64).map(|bits| bits as usize / 8).unwrap_or(if version == 4 { 16 } else { 5 }),
+            }
+            .clamp(5, 16);
+            let permissions = encrypt.get(b"P").and_then(Object::as_i64).unwrap_or(0) as i32;
+            let file_id = first_file_id(doc);
+            let compare_length = if revision == 2 { 32 } else { 16 };
+
+            let user_key = |padded: &[u8]| -> Option<Vec<u8>> {
+                let key = legacy_file_key(padded, owner_hash, permissions, &file_id, revision, key_length, encrypt_metadata);
+                let expected = legacy_user_hash(&key, &file_id, revision);
+                (user_hash.len() >= compare_length && expected[..compare_length] == user_hash[..compare_length]).then_some(key)
+            };
+
+            user_key(&padded_password(password)).or_else(|| {
+                // As the owner password, /O decrypts to the padded user password (Algorithm 7)
+                let owner_key = legacy_owner_key(password, revision, key_length);
+                let mut padded_user = owner_hash.to_vec();
+                if revision == 2 {
+                    padded_user = rc4(&owner_key, &padded_user);
+                } else {
+                    for round in (0..=19u8).rev() {
+                        let round_key: Vec<u8> = owner_key.iter().map(|b| b ^ round).collect();
+                        padded_user = rc4(&round_key, &padded_user);
+                    }
+                }
+                user_key(&padded_user)
+            })
+        }
+        5 | 6 => {
+            let password = utf8_password(password);
+            if owner_hash.len() < 48 || user_hash.len() < 48 {
+                return Err("PDF encryption dictionary is damaged".to_string());
+            }
+            let unwrap_key = |hash: Vec<u8>, encrypted_key: &[u8]| aes_cbc_decrypt(&hash, &[0; 16], encrypted_key, false);
+
+            if password_hash(password, &user_hash[32..40], &[], revision)? == user_hash[..32] {
+                let hash = password_hash(password, &user_hash[40..48], &[], revision)?;
+                Some(unwrap_key(hash, string_entry(encrypt, b"UE"))?)
+            } else if password_hash(password, &owner_hash[32..40], &user_hash[..48], revision)? == owner_hash[..32] {
+                let hash = password_hash(password, &owner_hash[40..48], &user_hash[..48], revision)?;
+                Some(unwrap_key(hash, string_entry(encrypt, b"OE"))?)
+            } else {
+                None
+            }
+        }
+        _ => return Err(format!("Unsupported PDF encryption revision: {}", revision)),
+    };
+
+    let file_key = file_key.ok_or(if password.is_empty() { PASSWORD_REQUIRED } else { INCORRECT_PASSWORD })?;
+    Ok(PdfSecurity {
+        handler: SecurityHandler {
+            file_key,
+            string_method,
+            stream_method,
+            encrypt_metadata,
+        },
+        encrypt_dict: encrypt.clone(),
+        file_id: doc.trailer.get(b"ID").cloned().unwrap_or(Object::Null),
+    })
+}
+
+/// Loads a PDF, decrypting it with `password` (user or owner) if it is encrypted.
+/// Files that only restrict permissions open without a password.
+pub fn load_decrypted(bytes: &[u8], password: Option<&str>) -> Result<Document, String> {
+    load_protected(bytes, password).map(|(doc, _)| doc)
+}
+
+/// Like `load_decrypted`, also returning the security settings of an encrypted file for `reencrypt_document`
+pub fn load_protected(bytes: &[u8], password: Option<&str>) -> Result<(Document, Option<PdfSecurity>), String> {
+    let doc = Document::load_mem(bytes)
+        .map_err(|e| format!("Failed to load PDF: {}", e))?;
+    if !doc.is_encrypted() {
+        return Ok((doc, None));
+    }
+    let (doc, security) = decrypt_with_security(&doc, bytes, password.unwrap_or(""))?;
+    Ok((doc, Some(security)))
+}
+
+/// The PDF's bytes, decrypted first if needed, for parsers that can't decrypt themselves
+pub fn decrypted_bytes(bytes: Vec<u8>, password: Option<&str>) -> Result<Vec<u8>, String> {
+    let doc = Document::load_mem(&bytes)
+        .map_err(|e| format!("Failed to load PDF: {}", e))?;
+    if !doc.is_encrypted() {
+        return Ok(bytes);
+    }
+
+    let mut decrypted = Vec::new();
+    decrypt_loaded(&doc, &bytes, password.unwrap_or(""))?
+        .save_to(&mut decrypted)
+        .map_err(|e| format!("Failed to write decrypted PDF: {}", e))?;
+    Ok(decrypted)
+}
+
+/// Decrypts a document that `Document::load_mem(bytes)` reported as encrypted
+pub fn decrypt_loaded(doc: &Document, bytes: &[u8], password: &str) -> Result<Document, String> {
+    decrypt_with_security(doc, bytes, password).map(|(doc, _)| doc)
+}
+
+fn decrypt_with_security(doc: &Document, bytes: &[u8], password: &str) -> Result<(Document, PdfSecurity), String> {
+    let security = open_security_handler(doc, password)?;
+    let handler = &security.handler;
+    let encrypt_id = doc.trailer.get(b"Encrypt").and_then(Object::as_reference).ok();
+
+    // A plain load can't unpack encrypted object streams, so it drops them (or reads garbage)
+    // along with everything packed inside; those are read again below once decrypted
+    let mut containers = BTreeSet::new();
+    let mut packed = HashSet::new();
+    for (&number, entry) in doc.reference_table.entries.iter() {
+        if let XrefEntry::Compressed { container, .. } = entry {
+            containers.insert(*container);
+            packed.insert((number, 0));
+        }
+    }
+
+    let mut doc = doc.clone();
+    doc.objects.retain(|id, object| {
+        let object_stream = matches!(object, Object::Stream(stream) if stream.dict.type_is(b"ObjStm"));
+        !packed.contains(id) && !containers.contains(&id.0) && !object_stream
+    });
+    for (&id, object) in doc.objects.iter_mut() {
+        if Some(id) != encrypt_id {
+            handler.apply(id, object, false)?;
+        }
+    }
+
+    let mut reader = Reader { buffer: bytes, document: Document::new() };
+    reader.document.reference_table = doc.reference_table.clone();
+    for container in containers {
+        let id = (container, 0);
+        // Like lopdf, skip object streams that can't be read rather than failing the whole file
+        let Ok(mut object) = reader.get_object(id) else { continue };
+        let Ok(stream) = object.as_stream_mut() else { continue };
+        let content = handler.decrypt_bytes(id, &stream.content, handler.stream_method)?;
+        stream.set_content(content);
+        let Ok(unpacked) = ObjectStream::new(stream) else { continue };
+
+        // Objects inside were decrypted along with their stream; keep only those the
+        // cross-reference table still places in it
+        for (packed_id, packed_object) in unpacked.objects {
+            if matches!(
+                doc.reference_table.get(packed_id.0),
+                Some(XrefEntry::Compressed { container: c, .. }) if *c == container
+            ) {
+                doc.objects.insert(packed_id, packed_object);
+            }
+        }
+        doc.objects.insert(id, object);
+    }
+
+    doc.trailer.remove(b"Encrypt");
+    if let Some(id) = encrypt_id {
+        doc.objects.remove(&id);
+    }
+    Ok((doc, security))
+}
+
+/// Encrypts every string and stream of an unencrypted document with the standard security handler.
+/// An empty owner password falls back to the user password.
+pub fn encrypt_document(
+    doc: &mut Document,
+    user_password: &str,
+    owner_password: &str,
+    algorithm: EncryptionAlgorithm,
+    permissions: Permissions,
+) -> Result<(), String> {
+    if doc.is_encrypted() {
+        return Err("PDF is already encrypted; remove its password first".to_string());
+    }
+    let owner_password = if owner_password.is_empty() { user_password } else { owner_password };
+    if owner_password.is_empty() {
+        return Err("A user or owner password is required".to_string());
+    }
+
+    // Keys are tied to the first file identifier, so make sure there is one
+    let mut file_id = first_file_id(doc);
+    if file_id.is_empty() {
+        file_id = rand::random::<[u8; 16]>().to_vec();
+        doc.trailer.set("ID", vec![
+            Object::String(file_id.clone(), StringFormat::Hexadecimal),
+            Object::String(file_id.clone(), StringFormat::Hexadecimal),
+        ]);
+    }
+
+    let permission_flags = permissions.flags();
+    let hex = |bytes: Vec<u8>| Object::String(bytes, StringFormat::Hexadecimal);
+
+    let (handler, encrypt_dict) = match algorithm {
+        EncryptionAlgorithm::Rc4 | EncryptionAlgorithm::Aes128 => {
+            let revision = if algorithm == EncryptionAlgorithm::Rc4 { 3 } else { 4 };
+            let owner_hash = legacy_owner_hash(owner_password, user_password, revision, 16);
+            let file_key = legacy_file_key(&padded_password(user_password), &owner_hash, permission_flags, &file_id, revision, 16, true);
+            let user_hash = legacy_user_hash(&file_key, &file_id, revision);
+
+            let mut dict = dictionary! {
+                "Filter" => "Standard",
+                "V" => if revision == 3 { 2 } else { 4 },
+                "R" => revision,
+                "Length" => 128,
+                "O" => hex(owner_hash),
+                "U" => hex(user_hash),
+                "P" => permission_flags as i64,
+            };
+            let method = if revision == 3 {
+                CryptMethod::Rc4
+            } else {
+                dict.set("CF", dictionary! {
+                    "StdCF" => dictionary! { "AuthEvent" => "DocOpen", "CFM" => "AESV2", "Length" => 16 },
+                });
+                dict.set("StmF", "StdCF");
+                dict.set("StrF", "StdCF");
+                CryptMethod::AesV2
+            };
+            let handler = SecurityHandler { file_key, string_method: method, stream_method: method, encrypt_metadata: true };
+            (handler, dict)
+        }
+        EncryptionAlgorithm::Aes256 => {
+            let file_key = rand::random::<[u8; 32]>().to_vec();
+
+            // Hash, then 8 bytes validation salt and 8 bytes key salt (Algorithms 8 and 9)
+            let user_salts: [u8; 16] = rand::random();
+            let user_password = utf8_password(user_password);
+            let mut user_hash = password_hash(user_password, &user_salts[..8], &[], 6)?;
+            user_hash.extend_from_slice(&user_salts);
+            let user_key = aes_cbc_encrypt(&password_hash(user_password, &user_salts[8..], &[], 6)?, &[0; 16], &file_key, false)?;
+
+            let owner_salts: [u8; 16] = rand::random();
+            let owner_password = utf8_password(owner_password);
+            let mut owner_hash = password_hash(owner_password, &owner_salts[..8], &user_hash, 6)?;
+            owner_hash.extend_from_slice(&owner_salts);
+            let owner_key = aes_cbc_encrypt(&password_hash(owner_password, &owner_salts[8..], &user_hash, 6)?, &[0; 16], &file_key, false)?;
+
+            // Permissions sealed with the file key (Algorithm 10)
+            let mut perms = [0u8; 16];
+            perms[..4].copy_from_slice(&permission_flags.to_le_bytes());
+            perms[4..8].copy_from_slice(&[0xFF; 4]);
+            perms[8..12].copy_from_slice(b"Tadb");
+            perms[12..].copy_from_slice(&rand::random::<[u8; 4]>());
+            // A single block with a zero IV is plain ECB
+            let perms = aes_cbc_encrypt(&file_key, &[0; 16], &perms, false)?;
+
+            let dict = dictionary! {
+                "Filter" => "Standard",
+                "V" => 5,
+                "R" => 6,
+                "Length" => 256,
+                "CF" => dictionary! {
+                    "StdCF" => dictionary! { "AuthEvent" => "DocOpen", "CFM" => "AESV3", "Length" => 32 },
+                },
+                "StmF" => "StdCF",
+                "StrF" => "StdCF",
+                "O" => hex(owner_hash),
+                "U" => hex(user_hash),
+                "OE" => hex(owner_key),
+                "UE" => hex(user_key),
+                "P" => permission_flags as i64,
+                "Perms" => hex(perms),
+            };
+            let handler = SecurityHandler {
+                file_key,
+                string_method: CryptMethod::AesV3,
+                stream_method: CryptMethod::AesV3,
+                encrypt_metadata: true,
+            };
+            (handler, dict)
+        }
+    };
+
+    seal_document(doc, &handler, encrypt_dict)
+}
+
+/// Encrypts an unencrypted (typically edited) document with the passwords, permissions and
+/// algorithm of the file `security` was read from
+pub fn reencrypt_document(doc: &mut Document, security: &PdfSecurity) -> Result<(), String> {
+    if doc.is_encrypted() {
+        return Err("PDF is already encrypted".to_string());
+    }
+    // Keys before AES-256 are derived from the source's file identifier
+    if !matches!(security.file_id, Object::Null) {
+        doc.trailer.set("ID", security.file_id.clone());
+    }
+    seal_document(doc, &security.handler, security.encrypt_dict.clone())
+}
+
+/// Encrypts every object and installs the /Encrypt dictionary, raising the header version as the revision needs
+fn seal_document(doc: &mut Document, handler: &SecurityHandler, encrypt_dict: Dictionary) -> Result<(), String> {
+    let revision = encrypt_dict.get(b"R").and_then(Object::as_i64).unwrap_or(2);
+
+    for (&id, object) in doc.objects.iter_mut() {
+        handler.apply(id, object, true)?;
+    }
+
+    let encrypt_id = doc.add_object(encrypt_dict);
+    doc.trailer.set("Encrypt", encrypt_id);
+
+    let minimum_version = match revision {
+        2 => "1.1",
+        3 => "1.4",
+        4 => "1.6",
+        _ => "1.7",
+    };
+    if doc.version.parse::<f32>().unwrap_or(0.0) < minimum_version.parse::<f32>().unwrap_or(0.0) {
+        doc.version = minimum_version.to_string();
+    }
+    if revision >= 5 {
+        // AES-256 is part of PDF 2.0, announced to 1.7 readers as an Adobe extension level
+        let catalog = doc.catalog_mut().map_err(|e| format!("Failed to get document catalog: {}", e))?;
+        let adobe = dictionary! {
+            "BaseVersion" => Object::Name(b"1.7".to_vec()),
+            "ExtensionLevel" => if revision == 5 { 3 } else { 8 },
+        };
+        match catalog.get_mut(b"Extensions") {
+            Ok(Object::Dictionary(extensions)) => extensions.set("ADBE", adobe),
+            _ => catalog.set("Extensions", dictionary! { "ADBE" => adobe }),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::Stream;
+
+    const CONTENT: &[u8] = b"BT /F1 12 Tf 72 720 Td (Secret text) Tj ET";
+
+    fn sample_document() -> Document {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let content_id = doc.add_object(Stream::new(dictionary! {}, CONTENT.to_vec()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Contents" => content_id,
+        });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        let info_id = doc.add_object(dictionary! { "Title" => Object::string_literal("Quarterly report") });
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+        doc
+    }
+
+    fn encrypted(user_password: &str, owner_password: &str, algorithm: EncryptionAlgorithm) -> Vec<u8> {
+        let permissions = Permissions { print: true, copy: false, modify: false, annotate: true };
+        let mut doc = sample_document();
+        encrypt_document(&mut doc, user_password, owner_password, algorithm, permissions).unwrap();
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn assert_readable(doc: &Document) {
+        let page_id = doc.get_pages()[&1];
+        assert_eq!(doc.get_page_content(page_id).unwrap(), CONTENT);
+        let info = doc.trailer.get(b"Info").and_then(Object::as_reference).unwrap();
+        let title = doc.get_dictionary(info).unwrap().get(b"Title").unwrap();
+        assert_eq!(title, &Object::String(b"Quarterly report".to_vec(), StringFormat::Literal));
+        assert!(!doc.is_encrypted());
+    }
+
+    #[test]
+    fn round_trips_with_every_algorithm() {
+        for (algorithm, revision) in [
+            (EncryptionAlgorithm::Rc4, 3),
+            (EncryptionAlgorithm::Aes128, 4),
+            (EncryptionAlgorithm::Aes256, 6),
+        ] {
+            let bytes = encrypted("user", "owner", algorithm);
+            assert!(!bytes.windows(CONTENT.len()).any(|window| window == CONTENT));
+
+            let doc = Document::load_mem(&bytes).unwrap();
+            let encrypt = doc.trailer.get(b"Encrypt").and_then(Object::as_reference).unwrap();
+            assert_eq!(doc.get_dictionary(encrypt).unwrap().get(b"R").unwrap().as_i64().unwrap(), revision);
+
+            assert_readable(&load_decrypted(&bytes, Some("user")).unwrap());
+        }
+    }
+
+    #[test]
+    fn owner_password_opens_the_document() {
+        for algorithm in [EncryptionAlgorithm::Rc4, EncryptionAlgorithm::Aes128, EncryptionAlgorithm::Aes256] {
+            let bytes = encrypted("user", "owner", algorithm);
+            assert_readable(&load_decrypted(&bytes, Some("owner")).unwrap());
+        }
+    }
+
+    #[test]
+    fn wrong_or_missing_password_is_rejected() {
+        for algorithm in [EncryptionAlgorithm::Rc4, EncryptionAlgorithm::Aes128, EncryptionAlgorithm::Aes256] {
+            let bytes = encrypted("user", "owner", algorithm);
+            assert_eq!(load_decrypted(&bytes, Some("wrong")).unwrap_err(), INCORRECT_PASSWORD);
+            assert_eq!(load_decrypted(&bytes, None).unwrap_err(), PASSWORD_REQUIRED);
+        }
+    }
+
+    #[test]
+    fn owner_only_protection_opens_without_a_password() {
+        for algorithm in [EncryptionAlgorithm::Rc4, EncryptionAlgorithm::Aes128, EncryptionAlgorithm::Aes256] {
+            let bytes = encrypted("", "owner", algorithm);
+            assert_readable(&load_decrypted(&bytes, None).unwrap());
+        }
+    }
+
+    #[test]
+    fn reencrypting_keeps_both_passwords() {
+        let bytes = encrypted("user", "owner", EncryptionAlgorithm::Aes128);
+        let (mut doc, security) = load_protected(&bytes, Some("user")).unwrap();
+        reencrypt_document(&mut doc, &security.unwrap()).unwrap();
+        let mut reencrypted = Vec::new();
+        doc.save_to(&mut reencrypted).unwrap();
+
+        assert_readable(&load_decrypted(&reencrypted, Some("user")).unwrap());
+        assert_readable(&load_decrypted(&reencrypted, Some("owner")).unwrap());
+    }
+}
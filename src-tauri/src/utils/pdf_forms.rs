@@ -0,0 +1,756 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+use crate::utils::pdf_annotations::{flatten_annotations, page_annotations, rect_values};
+use crate::utils::pdf_content::{encode_win_ansi, helvetica_text_width, standard_font};
+use crate::utils::pdf_merge::find_inherited_attribute;
+use crate::utils::pdf_strings::{decode_text_string, encode_text_string};
+
+// Field flags (Ff), as bit masks
+const READ_ONLY: i64 = 1;
+const REQUIRED: i64 = 1 << 1;
+const MULTILINE: i64 = 1 << 12;
+const RADIO: i64 = 1 << 15;
+const PUSH_BUTTON: i64 = 1 << 16;
+const COMBO: i64 = 1 << 17;
+const EDIT: i64 = 1 << 18;
+const MULTI_SELECT: i64 = 1 << 21;
+const COMB: i64 = 1 << 24;
+
+/// Cell values read as a ticked or cleared checkbox in a CSV
+const CHECKED_WORDS: &[&str] = &["true", "yes", "y", "on", "1", "x", "checked"];
+const UNCHECKED_WORDS: &[&str] = &["false", "no", "n", "off", "0", "", "unchecked"];
+
+/// A fillable form field, as listed to the user
+#[derive(Debug, Clone, Serialize)]
+pub struct FormField {
+    pub name: String,           // Fully qualified, e.g. "address.city"
+    pub kind: String,           // "text", "checkbox", "radio", "combo", "list", "button" or "signature"
+    pub value: Value,           // String, bool (checkbox), list of strings (multi-select list) or null
+    pub options: Vec<String>,   // Values that can be chosen: checkbox/radio states or choice export values
+    pub read_only: bool,
+    pub required: bool,
+    pub multiline: bool,
+    pub max_length: Option<u32>,
+    pub page_numbers: Vec<u32>, // Pages showing the field's widgets
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FieldKind {
+    Text,
+    Checkbox,
+    Radio,
+    Button,
+    Combo,
+    List,
+    Signature,
+}
+
+impl FieldKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            FieldKind::Text => "text",
+            FieldKind::Checkbox => "checkbox",
+            FieldKind::Radio => "radio",
+            FieldKind::Button => "button",
+            FieldKind::Combo => "combo",
+            FieldKind::List => "list",
+            FieldKind::Signature => "signature",
+        }
+    }
+}
+
+/// A terminal field of the form with the widget annotations showing it
+struct Field {
+    id: ObjectId,
+    name: String,
+    kind: FieldKind,
+    flags: i64,
+    widgets: Vec<ObjectId>,
+}
+
+/// Every terminal field in the document's AcroForm, in field tree order
+fn collect_fields(doc: &Document) -> Vec<Field> {
+    let roots = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get_deref(b"AcroForm", doc).ok())
+        .and_then(|acroform| acroform.as_dict().ok())
+        .and_then(|acroform| acroform.get_deref(b"Fields", doc).ok())
+        .and_then(|fields| fields.as_array().ok())
+        .map(|fields| fields.iter().filter_map(|f| f.as_reference().ok()).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut fields = Vec::new();
+    let mut visited = HashSet::new();
+    let mut pending: Vec<(ObjectId, String)> = roots.into_iter().rev().map(|id| (id, String::new())).collect();
+    while let Some((id, parent_name)) = pending.pop() {
+        let Ok(dict) = doc.get_dictionary(id) else {
+            continue;
+        };
+        if !visited.insert(id) {
+            continue;
+        }
+
+        let name = match dict.get(b"T").and_then(Object::as_str) {
+            Ok(part) if parent_name.is_empty() => decode_text_string(part),
+            Ok(part) => format!("{}.{}", parent_name, decode_text_string(part)),
+            Err(_) => parent_name,
+        };
+
+        // Kids with a name are child fields; the others are this field's widgets
+        let kids: Vec<ObjectId> = dict
+            .get_deref(b"Kids", doc)
+            .and_then(Object::as_array)
+            .map(|kids| kids.iter().filter_map(|k| k.as_reference().ok()).collect())
+            .unwrap_or_default();
+        let (child_fields, widgets): (Vec<ObjectId>, Vec<ObjectId>) = kids
+            .into_iter()
+            .partition(|&kid| doc.get_dictionary(kid).is_ok_and(|k| k.has(b"T")));
+        for child in child_fields.into_iter().rev() {
+            pending.push((child, name.clone()));
+        }
+
+        let is_terminal = !widgets.is_empty() || !dict.has(b"Kids");
+        if !is_terminal {
+            continue;
+        }
+        let field_type = find_inherited_attribute(doc, id, b"FT").and_then(|t| t.as_name().ok());
+        let flags = find_inherited_attribute(doc, id, b"Ff").and_then(|f| f.as_i64().ok()).unwrap_or(0);
+        let kind = match field_type {
+            Some(b"Tx") => FieldKind::Text,
+            Some(b"Btn") if flags & PUSH_BUTTON != 0 => FieldKind::Button,
+            Some(b"Btn") if flags & RADIO != 0 => FieldKind::Radio,
+            Some(b"Btn") => FieldKind::Checkbox,
+            Some(b"Ch") if flags & COMBO != 0 => FieldKind::Combo,
+            Some(b"Ch") => FieldKind::List,
+            Some(b"Sig") => FieldKind::Signature,
+            _ => continue,
+        };
+
+        let widgets = if widgets.is_empty() { vec![id] } else { widgets };
+        fields.push(Field { id, name, kind, flags, widgets });
+    }
+    fields
+}
+
+/// Lists the document's form fields with their current values
+pub fn list_form_fields(doc: &Document) -> Vec<FormField> {
+    let widget_pages: HashMap<ObjectId, u32> = doc
+        .get_pages()
+        .into_iter()
+        .flat_map(|(number, page_id)| {
+            page_annotations(doc, page_id).into_iter().filter_map(move |a| a.as_reference().ok().map(|id| (id, number)))
+        })
+        .collect();
+
+    collect_fields(doc)
+        .into_iter()
+        .map(|field| {
+            let value = find_inherited_attribute(doc, field.id, b"V").and_then(|v| doc.dereference(v).ok()).map(|(_, v)| v);
+            let value = match (field.kind, value) {
+                (FieldKind::Checkbox, value) => {
+                    Value::Bool(value.and_then(|v| v.as_name().ok()).is_some_and(|state| state != b"Off"))
+                }
+                (FieldKind::Radio, Some(Object::Name(state))) if state != b"Off" => {
+                    Value::String(String::from_utf8_lossy(state).to_string())
+                }
+                (FieldKind::Text | FieldKind::Combo | FieldKind::List, Some(Object::String(text, _))) => {
+                    Value::String(decode_text_string(text))
+                }
+                (FieldKind::List, Some(Object::Array(items))) => Value::Array(
+                    items.iter().filter_map(|i| i.as_str().ok()).map(|t| Value::String(decode_text_string(t))).collect(),
+                ),
+                _ => Value::Null,
+            };
+
+            let options = match field.kind {
+                FieldKind::Checkbox | FieldKind::Radio => button_states(doc, &field),
+                FieldKind::Combo | FieldKind::List => choice_options(doc, field.id).into_iter().map(|(export, _)| export).collect(),
+                _ => Vec::new(),
+            };
+            let mut page_numbers: Vec<u32> = field.widgets.iter().filter_map(|w| widget_pages.get(w).copied()).collect();
+            page_numbers.sort_unstable();
+            page_numbers.dedup();
+
+            FormField {
+                name: field.name.clone(),
+                kind: field.kind.as_str().to_string(),
+                value,
+                options,
+                read_only: field.flags & READ_ONLY != 0,
+                required: field.flags & REQUIRED != 0,
+                multiline: field.kind == FieldKind::Text && field.flags & MULTILINE != 0,
+                max_length: max_length(doc, field.id),
+                page_numbers,
+            }
+        })
+        .collect()
+}
+
+fn max_length(doc: &Document, field_id: ObjectId) -> Option<u32> {
+    find_inherited_attribute(doc, field_id, b"MaxLen").and_then(|m| m.as_i64().ok()).map(|m| m.max(0) as u32)
+}
+
+/// The "on" appearance states of a checkbox or radio button's widgets, in widget order
+fn button_states(doc: &Document, field: &Field) -> Vec<String> {
+    let mut states = Vec::new();
+    for &widget in &field.widgets {
+        for state in widget_states(doc, widget) {
+            let state = String::from_utf8_lossy(&state).to_string();
+            if !states.contains(&state) {
+                states.push(state);
+            }
+        }
+    }
+    states
+}
+
+fn widget_states(doc: &Document, widget: ObjectId) -> Vec<Vec<u8>> {
+    doc.get_dictionary(widget)
+        .and_then(|w| w.get_deref(b"AP", doc))
+        .and_then(Object::as_dict)
+        .and_then(|ap| ap.get_deref(b"N", doc))
+        .and_then(Object::as_dict)
+        .map(|normal| normal.iter().map(|(state, _)| state.clone()).filter(|s| s != b"Off").collect())
+        .unwrap_or_default()
+}
+
+/// A choice field's options as (export value, displayed text)
+fn choice_options(doc: &Document, field_id: ObjectId) -> Vec<(String, String)> {
+    let options = find_inherited_attribute(doc, field_id, b"Opt")
+        .and_then(|o| doc.dereference(o).ok())
+        .and_then(|(_, o)| o.as_array().ok().cloned())
+        .unwrap_or_default();
+    options
+        .iter()
+        .filter_map(|option| match doc.dereference(option).ok()?.1 {
+            Object::String(text, _) => {
+                let text = decode_text_string(text);
+                Some((text.clone(), text))
+            }
+            Object::Array(pair) => {
+                let text = |i: usize| pair.get(i).and_then(|t| t.as_str().ok()).map(decode_text_string);
+                let export = text(0)?;
+                Some((export.clone(), text(1).unwrap_or(export)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Fills form fields by fully qualified name and regenerates the appearance of text and
+/// choice fields. Returns how many fields were set.
+pub fn fill_form_fields(doc: &mut Document, values: &HashMap<String, Value>) -> Result<usize, String> {
+    let fields = collect_fields(doc);
+    if fields.is_empty() {
+        return Err("PDF has no form fields".to_string());
+    }
+
+    let mut unknown: Vec<&str> = values.keys().filter(|name| !fields.iter().any(|f| &f.name == *name)).map(String::as_str).collect();
+    if !unknown.is_empty() {
+        unknown.sort_unstable();
+        return Err(format!("Form has no field named {}", unknown.join(", ")));
+    }
+
+    let mut needs_viewer_appearances = false;
+    let mut filled = 0;
+    for field in &fields {
+        let Some(value) = values.get(&field.name) else {
+            continue;
+        };
+        match field.kind {
+            FieldKind::Text => {
+                let text = value_text(value);
+                if let Some(limit) = max_length(doc, field.id) {
+                    if text.chars().count() > limit as usize {
+                        return Err(format!("Value for field {} is longer than its limit of {} characters", field.name, limit));
+                    }
+                }
+                set_field_value(doc, field.id, Object::String(encode_text_string(&text), lopdf::StringFormat::Literal))?;
+                needs_viewer_appearances |= !fits_win_ansi(&text);
+                for &widget in &field.widgets {
+                    write_text_appearance(doc, field, widget, std::slice::from_ref(&text), &[])?;
+                }
+            }
+            FieldKind::Checkbox | FieldKind::Radio => {
+                let states = button_states(doc, field);
+                let state = match value {
+                    Value::Bool(true) => states.first().cloned().ok_or_else(|| format!("Field {} has no checked state", field.name))?,
+                    Value::Bool(false) | Value::Null => "Off".to_string(),
+                    other => {
+                        let text = value_text(other);
+                        let word = text.trim().to_lowercase();
+                        if let Some(state) = states.iter().find(|s| **s == text) {
+                            state.clone()
+                        } else if field.kind == FieldKind::Checkbox && CHECKED_WORDS.contains(&word.as_str()) {
+                            states.first().cloned().ok_or_else(|| format!("Field {} has no checked state", field.name))?
+                        } else if UNCHECKED_WORDS.contains(&word.as_str()) || text == "Off" {
+                            "Off".to_string()
+                        } else {
+                            return Err(format!("'{}' is not an option of field {} ({})", text, field.name, states.join(", ")));
+                        }
+                    }
+                };
+                set_field_value(doc, field.id, Object::Name(state.clone().into_bytes()))?;
+                for &widget in &field.widgets {
+                    let shown = if widget_states(doc, widget).iter().any(|s| s == state.as_bytes()) { state.as_bytes() } else { b"Off" };
+                    doc.get_dictionary_mut(widget)
+                        .map_err(|e| format!("Failed to get form widget: {}", e))?
+                        .set("AS", Object::Name(shown.to_vec()));
+                }
+            }
+            FieldKind::Combo | FieldKind::List => {
+                let options = choice_options(doc, field.id);
+                let chosen: Vec<String> = match value {
+                    Value::Array(items) => items.iter().map(value_text).collect(),
+                    Value::Null => Vec::new(),
+                    other => vec![value_text(other)],
+                };
+                if chosen.len() > 1 && (field.kind == FieldKind::Combo || field.flags & MULTI_SELECT == 0) {
+                    return Err(format!("Field {} takes a single value", field.name));
+                }
+
+                // Accept either the export value or the displayed text of an option
+                let mut exports = Vec::new();
+                for choice in &chosen {
+                    match options.iter().find(|(export, display)| export == choice || display == choice) {
+                        Some((export, _)) => exports.push(export.clone()),
+                        None if field.kind == FieldKind::Combo && field.flags & EDIT != 0 => exports.push(choice.clone()),
+                        None => {
+                            let names: Vec<&str> = options.iter().map(|(export, _)| export.as_str()).collect();
+                            return Err(format!("'{}' is not an option of field {} ({})", choice, field.name, names.join(", ")));
+                        }
+                    }
+                }
+
+                let encode = |text: &String| Object::String(encode_text_string(text), lopdf::StringFormat::Literal);
+                let value = match exports.as_slice() {
+                    [] => Object::Null,
+                    [single] => encode(single),
+                    several => Object::Array(several.iter().map(encode).collect()),
+                };
+                set_field_value(doc, field.id, value)?;
+                let mut indices: Vec<usize> = exports.iter().filter_map(|e| options.iter().position(|(export, _)| export == e)).collect();
+                indices.sort_unstable();
+                let field_dict = doc.get_dictionary_mut(field.id).map_err(|e| format!("Failed to get form field: {}", e))?;
+                field_dict.set("I", Object::Array(indices.iter().map(|&i| Object::Integer(i as i64)).collect()));
+
+                let display = |export: &String| {
+                    options.iter().find(|(e, _)| e == export).map_or(export.clone(), |(_, display)| display.clone())
+                };
+                needs_viewer_appearances |= exports.iter().any(|e| !fits_win_ansi(&display(e)));
+                let (lines, selected) = if field.kind == FieldKind::Combo {
+                    (exports.iter().map(display).collect::<Vec<_>>(), Vec::new())
+                } else {
+                    (options.iter().map(|(_, display)| display.clone()).collect(), indices)
+                };
+                for &widget in &field.widgets {
+                    write_text_appearance(doc, field, widget, &lines, &selected)?;
+                }
+            }
+            FieldKind::Button | FieldKind::Signature => {
+                return Err(format!("Field {} is a {} and can't be filled", field.name, field.kind.as_str()));
+            }
+        }
+        filled += 1;
+    }
+
+    // The XFA copy of the form would still show the old values in viewers that prefer it
+    let acroform = acroform_mut(doc)?;
+    acroform.remove(b"XFA");
+    if needs_viewer_appearances {
+        // Text the appearance font can't encode is drawn properly by the viewer instead
+        acroform.set("NeedAppearances", Object::Boolean(true));
+    }
+    Ok(filled)
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn fits_win_ansi(text: &str) -> bool {
+    encode_win_ansi(text).iter().filter(|&&b| b == b'?').count() == text.matches('?').count()
+}
+
+fn set_field_value(doc: &mut Document, field_id: ObjectId, value: Object) -> Result<(), String> {
+    let field = doc
+        .get_dictionary_mut(field_id)
+        .map_err(|e| format!("Failed to get form field: {}", e))?;
+    match value {
+        Object::Null => {
+            field.remove(b"V");
+        }
+        value => field.set("V", value),
+    }
+    Ok(())
+}
+
+fn acroform_mut(doc: &mut Document) -> Result<&mut Dictionary, String> {
+    let acroform = doc
+        .catalog()
+        .and_then(|catalog| catalog.get(b"AcroForm"))
+        .map_err(|_| "PDF has no form fields".to_string())?
+        .clone();
+    match acroform {
+        Object::Reference(id) => doc.get_dictionary_mut(id).map_err(|e| format!("Failed to get form: {}", e)),
+        _ => doc
+            .catalog_mut()
+            .and_then(|catalog| catalog.get_mut(b"AcroForm"))
+            .and_then(Object::as_dict_mut)
+            .map_err(|e| format!("Failed to get form: {}", e)),
+    }
+}
+
+/// The font a field's text is drawn with, from its default appearance (DA) string
+struct AppearanceFont {
+    name: Vec<u8>,
+    font: Object,
+    size: f32,          // 0 means fit to the field
+    first_char: u32,
+    widths: Vec<f32>,   // Per code from first_char, in 1/1000 em; Helvetica metrics when empty
+    color: Vec<Operation>,
+}
+
+impl AppearanceFont {
+    fn text_width(&self, encoded: &[u8], size: f32) -> f32 {
+        if self.widths.is_empty() {
+            return helvetica_text_width(encoded, size);
+        }
+        let units: f32 = encoded
+            .iter()
+            .map(|&b| b as u32)
+            .map(|code| code.checked_sub(self.first_char).and_then(|i| self.widths.get(i as usize)).copied().unwrap_or(500.0))
+            .sum();
+        units * size / 1000.0
+    }
+}
+
+/// Resolves the DA font against the form's default resources. Fonts we can't encode text
+/// for (composite fonts) or can't find are replaced by Helvetica.
+fn appearance_font(doc: &mut Document, field_id: ObjectId, widget: ObjectId) -> Result<AppearanceFont, String> {
+    let acroform = acroform_mut(doc)?.clone();
+    let appearance = doc
+        .get_dictionary(widget)
+        .ok()
+        .and_then(|w| w.get(b"DA").ok())
+        .or_else(|| find_inherited_attribute(doc, field_id, b"DA"))
+        .or_else(|| acroform.get(b"DA").ok())
+        .and_then(|da| da.as_str().ok())
+        .map(<[u8]>::to_vec)
+        .unwrap_or_else(|| b"/Helv 0 Tf 0 g".to_vec());
+    let operations = Content::decode(&appearance).map(|c| c.operations).unwrap_or_default();
+
+    let mut name = b"Helv".to_vec();
+    let mut size = 0.0;
+    let mut color = Vec::new();
+    for operation in operations {
+        if operation.operator == "Tf" {
+            if let Some(font) = operation.operands.first().and_then(|n| n.as_name().ok()) {
+                name = font.to_vec();
+            }
+            size = operation.operands.get(1).and_then(|s| s.as_float().ok()).unwrap_or(0.0);
+        } else if ["g", "rg", "k"].contains(&operation.operator.as_str()) {
+            color = vec![operation];
+        }
+    }
+
+    let resource = acroform
+        .get_deref(b"DR", doc)
+        .and_then(Object::as_dict)
+        .and_then(|dr| dr.get_deref(b"Font", doc))
+        .and_then(Object::as_dict)
+        .and_then(|fonts| fonts.get(&name))
+        .ok()
+        .cloned();
+    let font_dict = resource.as_ref().and_then(|f| doc.dereference(f).ok()).and_then(|(_, f)| f.as_dict().ok().cloned());
+
+    let (font, font_dict) = match (resource, font_dict) {
+        (Some(font), Some(dict)) if dict.get(b"Subtype").and_then(Object::as_name).is_ok_and(|s| s != b"Type0") => (font, dict),
+        _ => {
+            let font_id = standard_font(doc, "Helvetica");
+            name = b"HelvFm".to_vec();
+            (Object::Reference(font_id), Dictionary::new())
+        }
+    };
+
+    let number = |object: &Object| doc.dereference(object).ok().and_then(|(_, v)| v.as_float().ok());
+    let widths = font_dict
+        .get_deref(b"Widths", doc)
+        .and_then(Object::as_array)
+        .map(|widths| widths.iter().map(|w| number(w).unwrap_or(0.0)).collect())
+        .unwrap_or_default();
+    let first_char = font_dict.get(b"FirstChar").ok().and_then(number).unwrap_or(0.0) as u32;
+
+    Ok(AppearanceFont { name, font, size, first_char, widths, color })
+}
+
+/// Draws lines of text into a widget's normal appearance, highlighting the `selected` lines
+/// (list boxes). Single lines are centred vertically; multiline text wraps from the top.
+fn write_text_appearance(
+    doc: &mut Document,
+    field: &Field,
+    widget: ObjectId,
+    lines: &[String],
+    selected: &[usize],
+) -> Result<(), String> {
+    let Some(rect) = doc.get_dictionary(widget).ok().and_then(|w| w.get(b"Rect").ok()).and_then(|r| rect_values(doc, r)) else {
+        return Ok(());
+    };
+    let rotation = doc
+        .get_dictionary(widget)
+        .and_then(|w| w.get_deref(b"MK", doc))
+        .and_then(Object::as_dict)
+        .and_then(|mk| mk.get(b"R"))
+        .and_then(Object::as_i64)
+        .map_or(0, |r| r.rem_euclid(360) / 90 * 90);
+    let (mut width, mut height) = (rect[2] - rect[0], rect[3] - rect[1]);
+    if rotation == 90 || rotation == 270 {
+        std::mem::swap(&mut width, &mut height);
+    }
+
+    let font = appearance_font(doc, field.id, widget)?;
+    let alignment = doc
+        .get_dictionary(widget)
+        .ok()
+        .and_then(|w| w.get(b"Q").ok())
+        .or_else(|| find_inherited_attribute(doc, field.id, b"Q"))
+        .and_then(|q| q.as_i64().ok())
+        .unwrap_or(0);
+    let padding = 2.0;
+    let inner_width = (width - 2.0 * padding).max(1.0);
+    let multiline = field.kind == FieldKind::List || (field.kind == FieldKind::Text && field.flags & MULTILINE != 0);
+    let comb_cells = max_length(doc, field.id).filter(|_| field.kind == FieldKind::Text && field.flags & COMB != 0 && !multiline);
+
+    // Wrap multiline text to the field width, shrinking auto-sized text until it fits
+    let wrap = |size: f32| -> Vec<Vec<u8>> {
+        let mut wrapped = Vec::new();
+        for line in lines.iter().flat_map(|l| l.split('\n')) {
+            let encoded = encode_win_ansi(line.trim_end_matches('\r'));
+            if field.kind != FieldKind::Text || !multiline {
+                wrapped.push(encoded);
+                continue;
+            }
+            let mut current: Vec<u8> = Vec::new();
+            for word in encoded.split(|&b| b == b' ') {
+                let candidate = if current.is_empty() { word.to_vec() } else { [current.as_slice(), b" ", word].concat() };
+                if !current.is_empty() && font.text_width(&candidate, size) > inner_width {
+                    wrapped.push(std::mem::replace(&mut current, word.to_vec()));
+                } else {
+                    current = candidate;
+                }
+            }
+            wrapped.push(current);
+        }
+        wrapped
+    };
+    let size = if font.size > 0.0 {
+        font.size
+    } else if multiline {
+        let mut size = 12.0;
+        while size > 4.0 && wrap(size).len() as f32 * size * 1.15 > height - 2.0 * padding {
+            size -= 0.5;
+        }
+        size
+    } else {
+        let widest = lines.iter().map(|l| font.text_width(&encode_win_ansi(l), 1.0)).fold(0.0, f32::max);
+        let by_height = ((height - 2.0 * padding) * 0.8).min(12.0);
+        let size = match comb_cells {
+            Some(_) => by_height,
+            None if widest > 0.0 => by_height.min(inner_width / widest),
+            None => by_height,
+        };
+        size.max(4.0)
+    };
+    let wrapped = wrap(size);
+    let leading = size * 1.15;
+
+    let real = |values: &[f32]| values.iter().map(|&v| Object::Real(v)).collect::<Vec<_>>();
+    let mut operations = vec![
+        Operation::new("BMC", vec![Object::Name(b"Tx".to_vec())]),
+        Operation::new("q", vec![]),
+        Operation::new("re", real(&[1.0, 1.0, width - 2.0, height - 2.0])),
+        Operation::new("W", vec![]),
+        Operation::new("n", vec![]),
+    ];
+    for &index in selected {
+        let top = height - padding - index as f32 * leading;
+        operations.push(Operation::new("rg", real(&[0.6, 0.75, 0.85])));
+        operations.push(Operation::new("re", real(&[1.0, top - leading, width - 2.0, leading])));
+        operations.push(Operation::new("f", vec![]));
+    }
+    operations.push(Operation::new("BT", vec![]));
+    if font.color.is_empty() {
+        operations.push(Operation::new("g", vec![Object::Integer(0)]));
+    }
+    operations.extend(font.color.iter().cloned());
+    operations.push(Operation::new("Tf", vec![Object::Name(font.name.clone()), Object::Real(size)]));
+
+    for (index, line) in wrapped.iter().enumerate() {
+        let baseline = if multiline {
+            height - padding - size - index as f32 * leading + size * 0.15
+        } else {
+            // Centre roughly on the cap height
+            (height - size * 0.72) / 2.0
+        };
+        match comb_cells {
+            Some(cells) if cells > 0 => {
+                let cell = width / cells as f32;
+                for (i, &code) in line.iter().enumerate() {
+                    let x = cell * i as f32 + (cell - font.text_width(&[code], size)) / 2.0;
+                    operations.push(Operation::new("Tm", real(&[1.0, 0.0, 0.0, 1.0, x, baseline])));
+                    operations.push(Operation::new("Tj", vec![Object::string_literal(vec![code])]));
+                }
+            }
+            _ => {
+                let text_width = font.text_width(line, size);
+                let x = match alignment {
+                    1 => (width - text_width) / 2.0,
+                    2 => width - padding - text_width,
+                    _ => padding,
+                };
+                operations.push(Operation::new("Tm", real(&[1.0, 0.0, 0.0, 1.0, x, baseline])));
+                operations.push(Operation::new("Tj", vec![Object::string_literal(line.clone())]));
+            }
+        }
+    }
+    operations.extend([Operation::new("ET", vec![]), Operation::new("Q", vec![]), Operation::new("EMC", vec![])]);
+
+    let content = Content { operations }
+        .encode()
+        .map_err(|e| format!("Failed to encode field appearance: {}", e))?;
+    let matrix: [f32; 6] = match rotation {
+        90 => [0.0, 1.0, -1.0, 0.0, 0.0, 0.0],
+        180 => [-1.0, 0.0, 0.0, -1.0, 0.0, 0.0],
+        270 => [0.0, -1.0, 1.0, 0.0, 0.0, 0.0],
+        _ => [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+    };
+    let mut fonts = Dictionary::new();
+    fonts.set(font.name.clone(), font.font.clone());
+    let appearance = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => real(&[0.0, 0.0, width, height]),
+            "Matrix" => real(&matrix),
+            "Resources" => dictionary! { "Font" => fonts },
+        },
+        content,
+    );
+    let appearance_id = doc.add_object(appearance);
+
+    let widget = doc
+        .get_dictionary_mut(widget)
+        .map_err(|e| format!("Failed to get form widget: {}", e))?;
+    widget.set("AP", dictionary! { "N" => appearance_id });
+    Ok(())
+}
+
+/// Draws every visible form widget's current appearance into its page's content, then removes
+/// the widgets and the form itself. Returns the number of widgets drawn.
+pub fn flatten_form(doc: &mut Document) -> Result<usize, String> {
+    let mut flattened = 0;
+    for (_, page_id) in doc.get_pages() {
+        let is_widget = |annotation: &Dictionary| annotation.get(b"Subtype").and_then(Object::as_name).is_ok_and(|s| s == b"Widget");
+        flattened += flatten_annotations(doc, page_id, is_widget)?;
+    }
+
+    if let Ok(catalog) = doc.catalog_mut() {
+        catalog.remove(b"AcroForm");
+    }
+    doc.prune_objects();
+    Ok(flattened)
+}
+
+/// Parses CSV text into rows of fields. Quoted fields may contain the delimiter, doubled
+/// quotes and line breaks; files whose header uses semicolons are read with semicolons.
+pub fn parse_csv(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+    let header = text.lines().next().unwrap_or("");
+    let delimiter = if header.contains(';') && !header.contains(',') { ';' } else { ',' };
+
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            c if c == delimiter && !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("Invalid CSV: unterminated quoted field".to_string());
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    // Blank lines carry no data
+    rows.retain(|row| row.iter().any(|f| !f.trim().is_empty()));
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(text: &str) -> Vec<Vec<String>> {
+        parse_csv(text).unwrap()
+    }
+
+    #[test]
+    fn quoted_fields_keep_delimiters_quotes_and_line_breaks() {
+        let text = "name,address,note\r\n\"Doe, Jane\",\"1 Main St\nSpringfield\",\"She said \"\"hi\"\"\"\r\n";
+        assert_eq!(rows(text), vec![
+            vec!["name", "address", "note"],
+            vec!["Doe, Jane", "1 Main St\nSpringfield", "She said \"hi\""],
+        ]);
+    }
+
+    #[test]
+    fn empty_cells_are_kept_in_place() {
+        assert_eq!(rows("a,b,c\n1,,3\n,,x\n\"\",2,"), vec![
+            vec!["a", "b", "c"],
+            vec!["1", "", "3"],
+            vec!["", "", "x"],
+            vec!["", "2", ""],
+        ]);
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        assert_eq!(rows("a,b\n\n1,2\n,\n"), vec![vec!["a", "b"], vec!["1", "2"]]);
+    }
+
+    #[test]
+    fn semicolon_header_switches_delimiter() {
+        assert_eq!(rows("\u{FEFF}name;amount\nJane;1,50\n"), vec![vec!["name", "amount"], vec!["Jane", "1,50"]]);
+    }
+
+    #[test]
+    fn unterminated_quote_is_an_error() {
+        assert!(parse_csv("a,b\n\"open,1\n").is_err());
+    }
+}
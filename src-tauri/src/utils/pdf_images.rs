@@ -0,0 +1,424 @@
+use exif::{In, Reader, Tag};
+use image::{DynamicImage, ImageFormat};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use std::fs::File;
+use std::io::BufReader;
+
+/// An image XObject added to a document
+pub struct EmbeddedImage {
+    pub id: ObjectId,
+    pub width: u32,
+    pub height: u32,
+    /// EXIF orientation (1-8) the image should be displayed with
+    pub orientation: u32,
+}
+
+impl EmbeddedImage {
+    /// Width and height as displayed, i.e. after applying the EXIF orientation
+    pub fn display_size(&self) -> (u32, u32) {
+        if self.orientation >= 5 {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
+    }
+}
+
+/// Adds an image file to `doc` as an image XObject.
+///
+/// JPEGs are embedded as-is with `/DCTDecode`; everything else is decoded and stored
+/// Flate-compressed, with any alpha channel split out into a soft mask.
+pub fn embed_image_file(doc: &mut Document, path: &str) -> Result<EmbeddedImage, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read image {}: {}", path, e))?;
+    let orientation = exif_orientation(path);
+
+    let is_jpeg = matches!(image::guess_format(&bytes), Ok(ImageFormat::Jpeg));
+    if let (true, Some(info)) = (is_jpeg, jpeg_info(&bytes)) {
+        let color_space: &[u8] = match info.components {
+            1 => b"DeviceGray",
+            4 => b"DeviceCMYK",
+            _ => b"DeviceRGB",
+        };
+
+        let mut dict = image_dictionary(info.width, info.height, color_space);
+        dict.set("Filter", Object::Name(b"DCTDecode".to_vec()));
+        if info.components == 4 && info.adobe {
+            // Photoshop writes CMYK JPEGs inverted
+            dict.set("Decode", Object::Array([1, 0, 1, 0, 1, 0, 1, 0].iter().map(|&v| Object::Integer(v)).collect()));
+        }
+
+        let id = doc.add_object(Stream::new(dict, bytes).with_compression(false));
+        return Ok(EmbeddedImage { id, width: info.width, height: info.height, orientation });
+    }
+
+    let img = image::load_from_memory(&bytes).map_err(|e| format!("Failed to decode image {}: {}", path, e))?;
+    let embedded = embed_image(doc, img)?;
+    Ok(EmbeddedImage { orientation, ..embedded })
+}
+
+/// Adds decoded pixels to `doc` as a Flate-compressed image XObject, with any alpha channel as a soft mask
+pub fn embed_image(doc: &mut Document, img: DynamicImage) -> Result<EmbeddedImage, String> {
+    let (width, height) = (img.width(), img.height());
+
+    let (pixels, color_space, alpha) = match img {
+        DynamicImage::ImageLuma8(gray) => (gray.into_raw(), b"DeviceGray".as_slice(), None),
+        img if img.color().has_alpha() => {
+            let rgba = img.to_rgba8();
+            let alpha: Vec<u8> = rgba.pixels().map(|p| p[3]).collect();
+            // Skip the soft mask when the image is fully opaque anyway
+            let alpha = alpha.iter().any(|&a| a < 255).then_some(alpha);
+            let rgb = DynamicImage::ImageRgba8(rgba).to_rgb8().into_raw();
+            (rgb, b"DeviceRGB".as_slice(), alpha)
+        }
+        img if img.color().channel_count() == 1 => (img.to_luma8().into_raw(), b"DeviceGray".as_slice(), None),
+        img => (img.to_rgb8().into_raw(), b"DeviceRGB".as_slice(), None),
+    };
+
+    let mut dict = image_dictionary(width, height, color_space);
+    if let Some(alpha) = alpha {
+        let mask_id = add_flate_stream(doc, image_dictionary(width, height, b"DeviceGray"), alpha)?;
+        dict.set("SMask", Object::Reference(mask_id));
+    }
+    let id = add_flate_stream(doc, dict, pixels)?;
+
+    Ok(EmbeddedImage { id, width, height, orientation: 1 })
+}
+
+/// Transformation matrix (`cm` operands) drawing an image into the box at (x, y) of
+/// displayed size (width, height), honouring its EXIF orientation
+pub fn placement_matrix(orientation: u32, x: f32, y: f32, width: f32, height: f32) -> [f32; 6] {
+    // Maps the image's unit square so its stored top row ends up where the orientation says
+    match orientation {
+        2 => [-width, 0.0, 0.0, height, x + width, y],
+        3 => [-width, 0.0, 0.0, -height, x + width, y + height],
+        4 => [width, 0.0, 0.0, -height, x, y + height],
+        5 => [0.0, -height, -width, 0.0, x + width, y + height],
+        6 => [0.0, -height, width, 0.0, x, y + height],
+        7 => [0.0, height, width, 0.0, x, y],
+        8 => [0.0, height, -width, 0.0, x + width, y],
+        _ => [width, 0.0, 0.0, height, x, y],
+    }
+}
+
+/// EXIF orientation tag of an image file, 1 (upright) when absent
+pub fn exif_orientation(path: &str) -> u32 {
+    let Ok(file) = File::open(path) else {
+        return 1;
+    };
+    Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()
+        .and_then(|exif| exif.get_field(Tag::Orientation, In::PRIMARY).and_then(|f| f.value.get_uint(0)))
+        .filter(|o| (1..=8).contains(o))
+        .unwrap_or(1)
+}
+
+fn image_dictionary(width: u32, height: u32, color_space: &[u8]) -> Dictionary {
+    let mut dict = Dictionary::new();
+    dict.set("Type", Object::Name(b"XObject".to_vec()));
+    dict.set("Subtype", Object::Name(b"Image".to_vec()));
+    dict.set("Width", Object::Integer(width as i64));
+    dict.set("Height", Object::Integer(height as i64));
+    dict.set("ColorSpace", Object::Name(color_space.to_vec()));
+    dict.set("BitsPerComponent", Object::Integer(8));
+    dict
+}
+
+fn add_flate_stream(doc: &mut Document, dict: Dictionary, content: Vec<u8>) -> Result<ObjectId, String> {
+    let mut stream = Stream::new(dict, content);
+    stream.compress().map_err(|e| format!("Failed to compress image data: {}", e))?;
+    Ok(doc.add_object(stream))
+}
+
+/// Frame header details needed to embed a JPEG without decoding it
+struct JpegInfo {
+    width: u32,
+    height: u32,
+    components: u8,
+    adobe: bool,
+}
+
+/// Walks the JPEG marker segments up to the first start-of-frame
+fn jpeg_info(bytes: &[u8]) -> Option<JpegInfo> {
+    let mut adobe = false;
+    let mut pos = 2;
+
+    while pos + 4 <= bytes.len() {
+        if bytes[pos] != 0xFF {
+            return None;
+        }
+        let marker = bytes[pos + 1];
+        // Fill bytes and standalone markers carry no length
+        if marker == 0xFF || (0xD0..=0xD8).contains(&marker) || marker == 0x01 {
+            pos += if marker == 0xFF { 1 } else { 2 };
+            continue;
+        }
+
+        let length = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let segment = bytes.get(pos + 4..pos + 2 + length)?;
+
+        match marker {
+            0xEE if segment.starts_with(b"Adobe") => adobe = true,
+            // SOF0-SOF15, except DHT (C4), JPG (C8) and DAC (CC)
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let header = segment.get(..6)?;
+                return Some(JpegInfo {
+                    height: u16::from_be_bytes([header[1], header[2]]) as u32,
+                    width: u16::from_be_bytes([header[3], header[4]]) as u32,
+                    components: header[5],
+                    adobe,
+                });
+            }
+            0xDA => return None,
+            _ => {}
+        }
+        pos += 2 + length;
+    }
+
+    None
+}
+
+/// An image XObject converted to a standalone file format
+pub struct DecodedImage {
+    pub data: Vec<u8>,
+    pub extension: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Colour spaces image samples can be reconstructed from
+enum ImageColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    Indexed { base: Box<ImageColorSpace>, lookup: Vec<u8> },
+    /// Single spot colour; rendered as ink coverage on white
+    Separation,
+}
+
+impl ImageColorSpace {
+    fn components(&self) -> usize {
+        match self {
+            ImageColorSpace::Gray | ImageColorSpace::Indexed { .. } | ImageColorSpace::Separation => 1,
+            ImageColorSpace::Rgb => 3,
+            ImageColorSpace::Cmyk => 4,
+        }
+    }
+
+    /// Converts one pixel's 8-bit samples to RGB
+    fn to_rgb(&self, samples: &[u8]) -> [u8; 3] {
+        match self {
+            ImageColorSpace::Gray => [samples[0]; 3],
+            ImageColorSpace::Separation => [255 - samples[0]; 3],
+            ImageColorSpace::Rgb => [samples[0], samples[1], samples[2]],
+            ImageColorSpace::Cmyk => {
+                let k = 255 - samples[3] as u32;
+                let channel = |c: u8| ((255 - c as u32) * k / 255) as u8;
+                [channel(samples[0]), channel(samples[1]), channel(samples[2])]
+            }
+            ImageColorSpace::Indexed { base, lookup } => {
+                let n = base.components();
+                let start = samples[0] as usize * n;
+                match lookup.get(start..start + n) {
+                    Some(entry) => base.to_rgb(entry),
+                    None => [0, 0, 0],
+                }
+            }
+        }
+    }
+}
+
+/// Turns an image XObject into a file: JPEG and JPEG 2000 streams are written as-is,
+/// everything else is reconstructed into a PNG (with alpha from `/SMask`)
+pub fn decode_image_xobject(doc: &Document, stream: &Stream) -> Result<DecodedImage, String> {
+    let dict = &stream.dict;
+    let width = image_dimension(dict, b"Width").ok_or("Image has no valid width")?;
+    let height = image_dimension(dict, b"Height").ok_or("Image has no valid height")?;
+    let filters = stream.filters().unwrap_or_default();
+
+    match filters.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["DCTDecode"] | ["DCT"] => {
+            return Ok(DecodedImage { data: stream.content.clone(), extension: "jpg", width, height })
+        }
+        ["JPXDecode"] => return Ok(DecodedImage { data: stream.content.clone(), extension: "jp2", width, height }),
+        _ => {}
+    }
+
+    let rgb = decode_pixels(doc, stream, width, height)?;
+    let alpha = soft_mask(doc, dict, width, height);
+
+    let img = match alpha {
+        Some(alpha) => {
+            let rgba = image::RgbaImage::from_fn(width, height, |x, y| {
+                let [r, g, b] = rgb.get_pixel(x, y).0;
+                image::Rgba([r, g, b, alpha.get_pixel(x, y)[0]])
+            });
+            DynamicImage::ImageRgba8(rgba)
+        }
+        None => DynamicImage::ImageRgb8(rgb),
+    };
+
+    let mut data = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut data), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    Ok(DecodedImage { data, extension: "png", width, height })
+}
+
+/// Reconstructs the image's pixels as RGB from its (Flate/LZW compressed or raw) samples
+fn decode_pixels(doc: &Document, stream: &Stream, width: u32, height: u32) -> Result<image::RgbImage, String> {
+    let dict = &stream.dict;
+    let content = plain_content(stream)?;
+
+    let is_mask = matches!(dict.get(b"ImageMask"), Ok(Object::Boolean(true)));
+    let (color_space, bits) = if is_mask {
+        (ImageColorSpace::Gray, 1)
+    } else {
+        let color_space = dict
+            .get(b"ColorSpace")
+            .map_err(|_| "Image has no colour space".to_string())
+            .and_then(|cs| resolve_color_space(doc, cs, true))?;
+        let bits = dict.get(b"BitsPerComponent").and_then(Object::as_i64).unwrap_or(8) as u32;
+        (color_space, bits)
+    };
+
+    let components = color_space.components();
+    let indexed = matches!(color_space, ImageColorSpace::Indexed { .. });
+    let mut samples = unpack_samples(&content, width as usize * components, height as usize, bits, !indexed)?;
+
+    // A /Decode of [1 0] inverts the samples (stencil masks paint black where the bit is 0)
+    let inverted = match dict.get(b"Decode").and_then(Object::as_array) {
+        Ok(decode) => decode.first().and_then(|v| v.as_float().ok()).unwrap_or(0.0) > 0.5,
+        Err(_) => false,
+    };
+    if inverted && !indexed {
+        samples.iter_mut().for_each(|s| *s = 255 - *s);
+    }
+
+    Ok(image::RgbImage::from_fn(width, height, |x, y| {
+        let start = (y as usize * width as usize + x as usize) * components;
+        image::Rgb(color_space.to_rgb(&samples[start..start + components]))
+    }))
+}
+
+/// The soft mask as 8-bit alpha, resized to the image when the sizes differ
+fn soft_mask(doc: &Document, dict: &Dictionary, width: u32, height: u32) -> Option<image::GrayImage> {
+    let (_, mask) = doc.dereference(dict.get(b"SMask").ok()?).ok()?;
+    let mask = mask.as_stream().ok()?;
+    let mask_width = image_dimension(&mask.dict, b"Width")?;
+    let mask_height = image_dimension(&mask.dict, b"Height")?;
+    let bits = mask.dict.get(b"BitsPerComponent").and_then(Object::as_i64).unwrap_or(8) as u32;
+
+    let samples = unpack_samples(&plain_content(mask).ok()?, mask_width as usize, mask_height as usize, bits, true).ok()?;
+    let alpha = image::GrayImage::from_raw(mask_width, mask_height, samples)?;
+    if (mask_width, mask_height) == (width, height) {
+        Some(alpha)
+    } else {
+        Some(image::imageops::resize(&alpha, width, height, image::imageops::FilterType::Triangle))
+    }
+}
+
+/// A positive /Width or /Height
+fn image_dimension(dict: &Dictionary, key: &[u8]) -> Option<u32> {
+    dict.get(key)
+        .and_then(Object::as_i64)
+        .ok()
+        .and_then(|value| u32::try_from(value).ok())
+        .filter(|&value| value > 0)
+}
+
+/// Stream data with Flate/LZW filters undone
+fn plain_content(stream: &Stream) -> Result<Vec<u8>, String> {
+    if stream.dict.get(b"Filter").is_err() {
+        return Ok(stream.content.clone());
+    }
+
+    // lopdf refuses to decompress image streams, so decode a copy without the subtype
+    let mut plain = stream.clone();
+    plain.dict.remove(b"Subtype");
+    plain.decompressed_content().map_err(|_| {
+        let filters = stream.filters().unwrap_or_default().join(", ");
+        format!("Unsupported image encoding: {}", filters)
+    })
+}
+
+/// Unpacks rows of 1/2/4/8/16-bit samples into one byte per sample.
+/// With `scale`, values are stretched to 0-255; otherwise they are kept (palette indices).
+fn unpack_samples(data: &[u8], samples_per_row: usize, rows: usize, bits: u32, scale: bool) -> Result<Vec<u8>, String> {
+    if !matches!(bits, 1 | 2 | 4 | 8 | 16) {
+        return Err(format!("Unsupported bits per component: {}", bits));
+    }
+    if samples_per_row == 0 || rows == 0 {
+        return Err("Image has no pixels".to_string());
+    }
+
+    // Rows start on byte boundaries
+    let row_bytes = (samples_per_row * bits as usize).div_ceil(8);
+    if row_bytes.checked_mul(rows).is_none_or(|needed| data.len() < needed) {
+        return Err("Image data is truncated".to_string());
+    }
+
+    let max = (1u32 << bits.min(8)) - 1;
+    let mut samples = Vec::with_capacity(samples_per_row * rows);
+    for row in data.chunks(row_bytes).take(rows) {
+        for i in 0..samples_per_row {
+            let value = match bits {
+                16 => row[i * 2] as u32,
+                8 => row[i] as u32,
+                _ => {
+                    let bit_offset = i * bits as usize;
+                    let byte = row[bit_offset / 8] as u32;
+                    (byte >> (8 - bits as usize - bit_offset % 8)) & max
+                }
+            };
+            samples.push(if scale && bits < 8 { (value * 255 / max) as u8 } else { value as u8 });
+        }
+    }
+
+    Ok(samples)
+}
+
+/// `allow_indexed` is false for an indexed space's base, which can't be indexed itself
+/// (and so can't refer back to the indexed space it belongs to)
+fn resolve_color_space(doc: &Document, object: &Object, allow_indexed: bool) -> Result<ImageColorSpace, String> {
+    let (_, object) = doc.dereference(object).map_err(|e| format!("Invalid colour space: {}", e))?;
+
+    let (family, params): (&[u8], &[Object]) = match object {
+        Object::Name(name) => (name, &[]),
+        Object::Array(items) => match items.split_first() {
+            Some((Object::Name(name), rest)) => (name, rest),
+            _ => return Err("Invalid colour space".to_string()),
+        },
+        _ => return Err("Invalid colour space".to_string()),
+    };
+
+    match family {
+        b"DeviceGray" | b"CalGray" | b"G" => Ok(ImageColorSpace::Gray),
+        b"DeviceRGB" | b"CalRGB" | b"RGB" => Ok(ImageColorSpace::Rgb),
+        b"DeviceCMYK" | b"CMYK" => Ok(ImageColorSpace::Cmyk),
+        b"Separation" => Ok(ImageColorSpace::Separation),
+        b"ICCBased" => {
+            // Use the profile's component count; alternates are device spaces of the same size
+            let profile = params
+                .first()
+                .and_then(|p| doc.dereference(p).ok())
+                .and_then(|(_, p)| p.as_stream().ok())
+                .ok_or("Invalid ICC colour space")?;
+            match profile.dict.get(b"N").and_then(Object::as_i64) {
+                Ok(1) => Ok(ImageColorSpace::Gray),
+                Ok(4) => Ok(ImageColorSpace::Cmyk),
+                _ => Ok(ImageColorSpace::Rgb),
+            }
+        }
+        b"Indexed" | b"I" if allow_indexed => {
+            let base = resolve_color_space(doc, params.first().ok_or("Invalid indexed colour space")?, false)?;
+            let lookup = match params.get(2).map(|l| doc.dereference(l)) {
+                Some(Ok((_, Object::String(bytes, _)))) => bytes.clone(),
+                Some(Ok((_, Object::Stream(stream)))) => plain_content(stream)?,
+                _ => return Err("Invalid indexed colour space".to_string()),
+            };
+            Ok(ImageColorSpace::Indexed { base: Box::new(base), lookup })
+        }
+        b"Indexed" | b"I" => Err("Invalid indexed colour space: its base is indexed too".to_string()),
+        other => Err(format!("Unsupported colour space: {}", String::from_utf8_lossy(other))),
+    }
+}
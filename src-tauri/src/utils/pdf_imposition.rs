@@ -0,0 +1,236 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::utils::pdf_content::add_page_content;
+use crate::utils::pdf_merge::find_inherited_attribute;
+use crate::utils::pdf_pages::{clip_box, page_box, page_rotation, visible_box};
+
+/// A page turned into a Form XObject
+pub struct PageForm {
+    pub id: ObjectId,
+    pub bbox: [f32; 4],  // The page's visible area, in the form's coordinates
+    pub rotation: i64,   // The page's /Rotate, which the form itself doesn't carry
+}
+
+impl PageForm {
+    /// Width and height of the page as displayed
+    pub fn displayed_size(&self) -> (f32, f32) {
+        let [x0, y0, x1, y1] = self.bbox;
+        if self.rotation == 90 || self.rotation == 270 { (y1 - y0, x1 - x0) } else { (x1 - x0, y1 - y0) }
+    }
+}
+
+/// A new page drawing forms, each with the matrix placing it
+pub struct Sheet {
+    pub width: f32,
+    pub height: f32,
+    pub placements: Vec<(ObjectId, [f32; 6])>,
+    pub frames: Vec<[f32; 4]>,  // Rectangles to outline, e.g. n-up cell borders
+}
+
+/// Wraps a page's content and resources in a Form XObject clipped to its visible area
+pub fn page_form(doc: &mut Document, page_id: ObjectId) -> Result<PageForm, String> {
+    let bbox = visible_box(doc, page_id);
+    let rotation = page_rotation(doc, page_id);
+    let content = doc
+        .get_page_content(page_id)
+        .map_err(|e| format!("Failed to read page content: {}", e))?;
+
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Form",
+        "BBox" => bbox.iter().map(|&v| Object::Real(v)).collect::<Vec<_>>(),
+    };
+    if let Some(resources) = find_inherited_attribute(doc, page_id, b"Resources") {
+        dict.set("Resources", resources.clone());
+    }
+    // Transparency group pages must stay grouped, or blending changes
+    if let Ok(group) = doc.get_dictionary(page_id).and_then(|page| page.get(b"Group")) {
+        dict.set("Group", group.clone());
+    }
+
+    let mut stream = Stream::new(dict, content);
+    let _ = stream.compress();
+    Ok(PageForm { id: doc.add_object(stream), bbox, rotation })
+}
+
+/// Matrix drawing a page form into `cell` as it is displayed (rotation applied),
+/// scaled to fit and centred
+pub fn fit_matrix(form: &PageForm, cell: [f32; 4]) -> [f32; 6] {
+    // Clockwise page rotation is a counter-clockwise turn of -rotation degrees
+    let (a, b, c, d) = match form.rotation {
+        90 => (0.0, -1.0, 1.0, 0.0),
+        180 => (-1.0, 0.0, 0.0, -1.0),
+        270 => (0.0, 1.0, -1.0, 0.0),
+        _ => (1.0, 0.0, 0.0, 1.0),
+    };
+    let [x0, y0, x1, y1] = form.bbox;
+    let corners = [(x0, y0), (x0, y1), (x1, y0), (x1, y1)].map(|(x, y)| (a * x + c * y, b * x + d * y));
+    let min_x = corners.iter().map(|p| p.0).fold(f32::INFINITY, f32::min);
+    let max_x = corners.iter().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max);
+    let min_y = corners.iter().map(|p| p.1).fold(f32::INFINITY, f32::min);
+    let max_y = corners.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max);
+
+    let (cell_width, cell_height) = (cell[2] - cell[0], cell[3] - cell[1]);
+    let scale = (cell_width / (max_x - min_x)).min(cell_height / (max_y - min_y));
+    let e = cell[0] + (cell_width - (max_x - min_x) * scale) / 2.0 - min_x * scale;
+    let f = cell[1] + (cell_height - (max_y - min_y) * scale) / 2.0 - min_y * scale;
+    [a * scale, b * scale, c * scale, d * scale, e, f]
+}
+
+/// Replaces every page of the document with the given sheets and drops objects only the
+/// old pages used. Annotations don't carry over, as forms can't hold them.
+pub fn replace_pages_with_sheets(doc: &mut Document, sheets: &[Sheet]) -> Result<(), String> {
+    let pages_id = doc
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Pages"))
+        .and_then(Object::as_reference)
+        .map_err(|e| format!("Failed to get page tree: {}", e))?;
+
+    let mut kids = Vec::with_capacity(sheets.len());
+    for sheet in sheets {
+        let mut xobjects = Dictionary::new();
+        let mut operations = Vec::new();
+        for (index, (form_id, matrix)) in sheet.placements.iter().enumerate() {
+            let name = format!("Pg{}", index + 1);
+            xobjects.set(name.clone(), Object::Reference(*form_id));
+            operations.extend([
+                Operation::new("q", vec![]),
+                Operation::new("cm", matrix.iter().map(|&v| Object::Real(v)).collect()),
+                Operation::new("Do", vec![Object::Name(name.into_bytes())]),
+                Operation::new("Q", vec![]),
+            ]);
+        }
+        if !sheet.frames.is_empty() {
+            operations.push(Operation::new("w", vec![Object::Real(0.5)]));
+            for [x0, y0, x1, y1] in &sheet.frames {
+                operations.push(Operation::new(
+                    "re",
+                    vec![Object::Real(*x0), Object::Real(*y0), Object::Real(x1 - x0), Object::Real(y1 - y0)],
+                ));
+            }
+            operations.push(Operation::new("S", vec![]));
+        }
+
+        let content = Content { operations }
+            .encode()
+            .map_err(|e| format!("Failed to encode page content: {}", e))?;
+        let mut stream = Stream::new(Dictionary::new(), content);
+        let _ = stream.compress();
+        let content_id = doc.add_object(stream);
+
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), Object::Real(sheet.width), Object::Real(sheet.height)],
+            "Resources" => dictionary! { "XObject" => xobjects },
+            "Contents" => content_id,
+        });
+        kids.push(Object::Reference(page_id));
+    }
+
+    let pages = doc
+        .get_dictionary_mut(pages_id)
+        .map_err(|e| format!("Failed to get page tree: {}", e))?;
+    for key in [&b"Resources"[..], b"MediaBox", b"CropBox", b"Rotate"] {
+        pages.remove(key);
+    }
+    pages.set("Count", Object::Integer(kids.len() as i64));
+    pages.set("Kids", Object::Array(kids));
+
+    doc.prune_objects();
+    Ok(())
+}
+
+/// Scales a page in place to a `width` x `height` MediaBox (unrotated, like the page's own
+/// boxes), centred. Its content, annotations and other boxes are transformed to match, so
+/// links, form fields and anything pointing at the page keep working.
+pub fn scale_page(doc: &mut Document, page_id: ObjectId, width: f32, height: f32) -> Result<(), String> {
+    let [x0, y0, x1, y1] = visible_box(doc, page_id);
+    let scale = (width / (x1 - x0)).min(height / (y1 - y0));
+    let e = (width - (x1 - x0) * scale) / 2.0 - x0 * scale;
+    let f = (height - (y1 - y0) * scale) / 2.0 - y0 * scale;
+    let transform = |x: f32, y: f32| (x * scale + e, y * scale + f);
+
+    // Clipped to the old visible area, so nothing that was cropped away shows in the margins
+    let content = Content {
+        operations: vec![
+            Operation::new("cm", [scale, 0.0, 0.0, scale, e, f].iter().map(|&v| Object::Real(v)).collect()),
+            Operation::new("re", [x0, y0, x1 - x0, y1 - y0].iter().map(|&v| Object::Real(v)).collect()),
+            Operation::new("W", vec![]),
+            Operation::new("n", vec![]),
+        ],
+    }
+    .encode()
+    .map_err(|e| format!("Failed to encode page content: {}", e))?;
+    add_page_content(doc, page_id, content, true)?;
+
+    let media_box = [0.0, 0.0, width, height];
+    let mut boxes = vec![(&b"MediaBox"[..], media_box), (b"CropBox", media_box)];
+    for key in [&b"TrimBox"[..], b"BleedBox", b"ArtBox"] {
+        if let Some([bx0, by0, bx1, by1]) = page_box(doc, page_id, key) {
+            let (bx0, by0) = transform(bx0, by0);
+            let (bx1, by1) = transform(bx1, by1);
+            boxes.push((key, clip_box([bx0, by0, bx1, by1], media_box)));
+        }
+    }
+
+    let mut annotation_ids = Vec::new();
+    let page = doc
+        .get_dictionary_mut(page_id)
+        .map_err(|e| format!("Failed to get page object: {}", e))?;
+    for (key, rect) in boxes {
+        page.set(key, rect.iter().map(|&v| Object::Real(v)).collect::<Vec<_>>());
+    }
+    match page.get_mut(b"Annots") {
+        Ok(Object::Array(annotations)) => {
+            for annotation in annotations {
+                match annotation {
+                    Object::Reference(id) => annotation_ids.push(*id),
+                    Object::Dictionary(dict) => transform_annotation(dict, &transform),
+                    _ => {}
+                }
+            }
+        }
+        Ok(Object::Reference(id)) => {
+            let id = *id;
+            if let Ok(Object::Array(annotations)) = doc.get_object(id) {
+                annotation_ids.extend(annotations.iter().filter_map(|a| a.as_reference().ok()));
+            }
+        }
+        _ => {}
+    }
+    for id in annotation_ids {
+        if let Ok(dict) = doc.get_dictionary_mut(id) {
+            transform_annotation(dict, &transform);
+        }
+    }
+    Ok(())
+}
+
+/// Moves an annotation's coordinates (Rect, QuadPoints, line and shape vertices, ink paths)
+/// through a transform. Appearance streams follow the Rect on their own.
+fn transform_annotation(dict: &mut Dictionary, transform: &impl Fn(f32, f32) -> (f32, f32)) {
+    fn transform_points(points: &mut [Object], transform: &impl Fn(f32, f32) -> (f32, f32)) {
+        for pair in points.chunks_exact_mut(2) {
+            if let (Ok(x), Ok(y)) = (pair[0].as_float(), pair[1].as_float()) {
+                let (x, y) = transform(x, y);
+                pair[0] = Object::Real(x);
+                pair[1] = Object::Real(y);
+            }
+        }
+    }
+
+    for key in [&b"Rect"[..], b"QuadPoints", b"L", b"Vertices"] {
+        if let Ok(Object::Array(points)) = dict.get_mut(key) {
+            transform_points(points, transform);
+        }
+    }
+    if let Ok(Object::Array(paths)) = dict.get_mut(b"InkList") {
+        for path in paths {
+            if let Object::Array(points) = path {
+                transform_points(points, transform);
+            }
+        }
+    }
+}
@@ -0,0 +1,718 @@
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+use crate::utils::pdf_strings::encode_text_string;
+
+/// Page attributes that may be inherited from ancestor `/Pages` nodes
+const INHERITABLE_PAGE_KEYS: &[&[u8]] = &[b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Outline item keys carried over verbatim (colour and style flags)
+const OUTLINE_STYLE_KEYS: &[&[u8]] = &[b"C", b"F"];
+
+/// Upper bound on outline items read from one document, guarding against cyclic trees
+const MAX_OUTLINE_ITEMS: usize = 100_000;
+
+/// Outline items nested deeper than this are dropped, keeping the recursion over them bounded
+const MAX_OUTLINE_DEPTH: usize = 64;
+
+/// Pages copied from one source document
+pub struct ImportedPages {
+    /// New page ids, in the order the pages were requested
+    pub page_ids: Vec<ObjectId>,
+    /// Source object id -> new object id for everything copied from this source
+    pub id_map: BTreeMap<ObjectId, ObjectId>,
+}
+
+/// A bookmark collected for the merged document
+struct OutlineNode {
+    title: Object,
+    target: Option<OutlineTarget>,
+    style: Vec<(Vec<u8>, Object)>,
+    open: bool,
+    children: Vec<OutlineNode>,
+}
+
+/// Where a bookmark leads, already expressed in the merged document's objects
+enum OutlineTarget {
+    Dest(Object),
+    Action(Object),
+}
+
+/// Where a link annotation keeps its destination
+enum LinkDestination {
+    Annotation(ObjectId),
+    InlineAction(ObjectId),
+    Action(ObjectId),
+}
+
+/// Builds a new document from pages of other documents.
+///
+/// Every object reachable from an imported page (content streams, fonts, images,
+/// annotations...) is deep-copied with fresh object ids, inherited attributes are
+/// pinned onto each page, and `finish` writes a proper page tree and catalog along
+/// with any collected bookmarks and form fields.
+pub struct PdfMerger {
+    document: Document,
+    pages_id: ObjectId,
+    page_ids: Vec<ObjectId>,
+    outlines: Vec<OutlineNode>,
+    form_fields: Vec<Object>,
+    form_field_names: HashSet<Vec<u8>>,
+    form_defaults: Dictionary,
+}
+
+impl Default for PdfMerger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PdfMerger {
+    pub fn new() -> Self {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+
+        PdfMerger {
+            document,
+            pages_id,
+            page_ids: Vec::new(),
+            outlines: Vec::new(),
+            form_fields: Vec::new(),
+            form_field_names: HashSet::new(),
+            form_defaults: Dictionary::new(),
+        }
+    }
+
+    /// Appends every page of `source`
+    pub fn append_document(&mut self, source: &Document) -> Result<ImportedPages, String> {
+        let page_numbers: Vec<u32> = source.get_pages().keys().copied().collect();
+        self.append_pages(source, &page_numbers)
+    }
+
+    /// Appends the given 1-based pages of `source` in order; pages may repeat
+    pub fn append_pages(&mut self, source: &Document, page_numbers: &[u32]) -> Result<ImportedPages, String> {
+        let source_pages = source.get_pages();
+        self.raise_version(&source.version);
+
+        let mut id_map: BTreeMap<ObjectId, ObjectId> = BTreeMap::new();
+        let mut selected = Vec::new();
+
+        // Reserve ids for the selected pages first so references between them (links, popups) survive
+        for &page_number in page_numbers {
+            let source_id = *source_pages
+                .get(&page_number)
+                .ok_or_else(|| format!("Page {} does not exist (document has {} pages)", page_number, source_pages.len()))?;
+            // A repeated page gets its own copy; references resolve to the first one
+            let new_id = self.document.new_object_id();
+            id_map.entry(source_id).or_insert(new_id);
+            selected.push((source_id, new_id));
+        }
+
+        let mut pending = VecDeque::new();
+        for &(source_id, new_id) in &selected {
+            let mut page = flatten_inherited_attributes(source, source_id)?;
+            page.remove(b"Parent");
+            let mut page = remap_dictionary(&mut self.document, source, page, &mut id_map, &mut pending);
+            page.set("Parent", Object::Reference(self.pages_id));
+            self.document.objects.insert(new_id, Object::Dictionary(page));
+            self.page_ids.push(new_id);
+        }
+        copy_pending(&mut self.document, source, &mut id_map, &mut pending);
+
+        let mut imported = ImportedPages {
+            page_ids: selected.into_iter().map(|(_, new_id)| new_id).collect(),
+            id_map,
+        };
+        self.resolve_named_link_destinations(source, &mut imported);
+        Ok(imported)
+    }
+
+    /// Deep-copies an arbitrary object from `source` into the merged document
+    pub fn import_object(&mut self, source: &Document, object: Object, id_map: &mut BTreeMap<ObjectId, ObjectId>) -> Object {
+        import_object(&mut self.document, source, object, id_map)
+    }
+
+    /// Collects the bookmarks of `source` whose targets survived the import.
+    /// With `group_title`, they are nested under one bookmark pointing at the first imported page.
+    pub fn add_outlines(&mut self, source: &Document, imported: &mut ImportedPages, group_title: Option<&str>) {
+        let first_item = outline_root(source).and_then(|root| root.get(b"First").and_then(Object::as_reference).ok());
+        let items = match first_item {
+            Some(first_id) => self.read_outline_level(source, first_id, &mut imported.id_map, &mut HashSet::new(), 0),
+            None => Vec::new(),
+        };
+
+        match group_title {
+            Some(title) => {
+                let Some(&first_page) = imported.page_ids.first() else {
+                    return;
+                };
+                self.outlines.push(OutlineNode {
+                    title: Object::String(encode_text_string(title), lopdf::StringFormat::Literal),
+                    target: Some(OutlineTarget::Dest(Object::Array(vec![
+                        Object::Reference(first_page),
+                        Object::Name(b"Fit".to_vec()),
+                    ]))),
+                    style: Vec::new(),
+                    open: false,
+                    children: items,
+                });
+            }
+            None => self.outlines.extend(items),
+        }
+    }
+
+    /// Adds the AcroForm fields of `source` whose widgets were imported, renaming
+    /// top-level fields whose names are already taken by an earlier document
+    pub fn add_form_fields(&mut self, source: &Document, imported: &mut ImportedPages) {
+        let acroform = source
+            .catalog()
+            .ok()
+            .and_then(|catalog| catalog.get_deref(b"AcroForm", source).ok())
+            .and_then(|acroform| acroform.as_dict().ok());
+        let Some(acroform) = acroform else {
+            return;
+        };
+
+        if let Ok(fields) = acroform.get_deref(b"Fields", source).and_then(Object::as_array) {
+            for field in fields {
+                // Fields whose widgets all sit on pages that weren't imported are dropped
+                let Some(&new_id) = field.as_reference().ok().and_then(|id| imported.id_map.get(&id)) else {
+                    continue;
+                };
+                self.claim_field_name(new_id);
+                self.form_fields.push(Object::Reference(new_id));
+            }
+        }
+
+        // Default appearance settings: the first document wins, resources are unioned
+        for key in [b"DA".as_slice(), b"Q".as_slice()] {
+            if let (false, Ok(value)) = (self.form_defaults.has(key), acroform.get(key)) {
+                self.form_defaults.set(key.to_vec(), value.clone());
+            }
+        }
+        if let Ok(Object::Boolean(true)) = acroform.get(b"NeedAppearances") {
+            self.form_defaults.set("NeedAppearances", Object::Boolean(true));
+        }
+        if let Ok(resources) = acroform.get(b"DR") {
+            let resources = self.import_object(source, resources.clone(), &mut imported.id_map);
+            self.merge_default_resources(resources);
+        }
+    }
+
+    /// Inserts an empty page of the given size (in points) at `index` in the output
+    pub fn insert_blank_page(&mut self, index: usize, width: f32, height: f32) -> ObjectId {
+        let content_id = self.document.add_object(lopdf::Stream::new(Dictionary::new(), Vec::new()));
+
+        let mut page = Dictionary::new();
+        page.set("Type", Object::Name(b"Page".to_vec()));
+        page.set("Parent", Object::Reference(self.pages_id));
+        page.set(
+            "MediaBox",
+            Object::Array(vec![0.into(), 0.into(), Object::Real(width), Object::Real(height)]),
+        );
+        page.set("Resources", Object::Dictionary(Dictionary::new()));
+        page.set("Contents", Object::Reference(content_id));
+
+        let page_id = self.document.add_object(Object::Dictionary(page));
+        self.page_ids.insert(index.min(self.page_ids.len()), page_id);
+        page_id
+    }
+
+    /// Writes the page tree, catalog, bookmarks and form and returns the finished document
+    pub fn finish(mut self) -> Result<Document, String> {
+        if self.page_ids.is_empty() {
+            return Err("No pages to write".to_string());
+        }
+
+        let kids: Vec<Object> = self.page_ids.iter().map(|&id| Object::Reference(id)).collect();
+        let mut pages = Dictionary::new();
+        pages.set("Type", Object::Name(b"Pages".to_vec()));
+        pages.set("Count", Object::Integer(kids.len() as i64));
+        pages.set("Kids", Object::Array(kids));
+        self.document.objects.insert(self.pages_id, Object::Dictionary(pages));
+
+        let mut catalog = Dictionary::new();
+        catalog.set("Type", Object::Name(b"Catalog".to_vec()));
+        catalog.set("Pages", Object::Reference(self.pages_id));
+
+        let outlines = std::mem::take(&mut self.outlines);
+        if !outlines.is_empty() {
+            let outlines_id = self.document.new_object_id();
+            let (first, last, count) = self.write_outline_level(outlines, outlines_id);
+            let mut root = Dictionary::new();
+            root.set("Type", Object::Name(b"Outlines".to_vec()));
+            root.set("First", Object::Reference(first));
+            root.set("Last", Object::Reference(last));
+            root.set("Count", Object::Integer(count));
+            self.document.objects.insert(outlines_id, Object::Dictionary(root));
+            catalog.set("Outlines", Object::Reference(outlines_id));
+        }
+
+        if !self.form_fields.is_empty() {
+            let mut acroform = std::mem::take(&mut self.form_defaults);
+            acroform.set("Fields", Object::Array(std::mem::take(&mut self.form_fields)));
+            let acroform_id = self.document.add_object(Object::Dictionary(acroform));
+            catalog.set("AcroForm", Object::Reference(acroform_id));
+        }
+
+        let catalog_id = self.document.add_object(Object::Dictionary(catalog));
+        self.document.trailer.set("Root", Object::Reference(catalog_id));
+
+        self.document.trailer.set("Size", Object::Integer(self.document.max_id as i64 + 1));
+        Ok(self.document)
+    }
+
+    /// Keeps the output version at least as high as every input
+    fn raise_version(&mut self, version: &str) {
+        let parse = |v: &str| v.trim().parse::<f32>().unwrap_or(0.0);
+        if parse(version) > parse(&self.document.version) {
+            self.document.version = version.trim().to_string();
+        }
+    }
+
+    /// Named destinations belong to the source catalog, so links using them are
+    /// rewritten to explicit destinations pointing at the imported pages
+    fn resolve_named_link_destinations(&mut self, source: &Document, imported: &mut ImportedPages) {
+        let mut annotation_ids = Vec::new();
+        for &page_id in &imported.page_ids {
+            let Ok(page) = self.document.get_dictionary(page_id) else {
+                continue;
+            };
+            if let Ok((_, Object::Array(annots))) = page.get(b"Annots").and_then(|a| self.document.dereference(a)) {
+                annotation_ids.extend(annots.iter().filter_map(|a| a.as_reference().ok()));
+            }
+        }
+
+        for annotation_id in annotation_ids {
+            let Some((location, name)) = self.named_link_destination(annotation_id) else {
+                continue;
+            };
+            let Some(explicit) = resolve_named_destination(source, &name) else {
+                continue;
+            };
+            let explicit = self.import_object(source, explicit, &mut imported.id_map);
+
+            let (target, key): (Option<&mut Dictionary>, &[u8]) = match location {
+                LinkDestination::Annotation(id) => (self.document.get_dictionary_mut(id).ok(), b"Dest"),
+                LinkDestination::InlineAction(id) => (
+                    self.document
+                        .get_dictionary_mut(id)
+                        .ok()
+                        .and_then(|annotation| annotation.get_mut(b"A").ok())
+                        .and_then(|action| action.as_dict_mut().ok()),
+                    b"D",
+                ),
+                LinkDestination::Action(id) => (self.document.get_dictionary_mut(id).ok(), b"D"),
+            };
+            if let Some(target) = target {
+                target.set(key.to_vec(), explicit);
+            }
+        }
+    }
+
+    /// Finds a link annotation's named destination, either in `/Dest` or a GoTo action's `/D`
+    fn named_link_destination(&self, annotation_id: ObjectId) -> Option<(LinkDestination, Object)> {
+        let annotation = self.document.get_dictionary(annotation_id).ok()?;
+        if annotation.get(b"Subtype").and_then(Object::as_name).ok()? != b"Link" {
+            return None;
+        }
+
+        let (location, dest) = match (annotation.get(b"Dest"), annotation.get(b"A")) {
+            (Ok(dest), _) => (LinkDestination::Annotation(annotation_id), dest),
+            (_, Ok(Object::Dictionary(action))) if is_goto(action) => {
+                (LinkDestination::InlineAction(annotation_id), action.get(b"D").ok()?)
+            }
+            (_, Ok(Object::Reference(action_id))) => {
+                let action = self.document.get_dictionary(*action_id).ok()?;
+                if !is_goto(action) {
+                    return None;
+                }
+                (LinkDestination::Action(*action_id), action.get(b"D").ok()?)
+            }
+            _ => return None,
+        };
+
+        match dest {
+            Object::Name(_) | Object::String(..) => Some((location, dest.clone())),
+            _ => None,
+        }
+    }
+
+    /// Reads one level of the outline (following /Next) with its descendants
+    fn read_outline_level(
+        &mut self,
+        source: &Document,
+        first_id: ObjectId,
+        id_map: &mut BTreeMap<ObjectId, ObjectId>,
+        visited: &mut HashSet<ObjectId>,
+        depth: usize,
+    ) -> Vec<OutlineNode> {
+        let mut nodes = Vec::new();
+        let mut current = Some(first_id);
+
+        while let Some(item_id) = current {
+            if visited.len() >= MAX_OUTLINE_ITEMS || !visited.insert(item_id) {
+                break;
+            }
+            let Ok(item) = source.get_dictionary(item_id) else {
+                break;
+            };
+            current = item.get(b"Next").and_then(Object::as_reference).ok();
+
+            let children = match item.get(b"First").and_then(Object::as_reference) {
+                Ok(child_id) if depth < MAX_OUTLINE_DEPTH => {
+                    self.read_outline_level(source, child_id, id_map, visited, depth + 1)
+                }
+                _ => Vec::new(),
+            };
+
+            let target = self.import_outline_target(source, item, id_map);
+            if target.is_none() && (item.has(b"Dest") || item.has(b"A")) {
+                // Points at a page that wasn't imported: drop it but keep its children
+                nodes.extend(children);
+                continue;
+            }
+
+            // Titles and styles are plain values, but may be stored as indirect objects
+            let style = OUTLINE_STYLE_KEYS
+                .iter()
+                .filter_map(|key| item.get_deref(key, source).ok().map(|value| (key.to_vec(), value.clone())))
+                .collect();
+            nodes.push(OutlineNode {
+                title: item.get_deref(b"Title", source).cloned().unwrap_or(Object::string_literal("")),
+                target,
+                style,
+                open: matches!(item.get(b"Count"), Ok(Object::Integer(count)) if *count > 0),
+                children,
+            });
+        }
+
+        nodes
+    }
+
+    /// Resolves an outline item's destination or action into the merged document
+    fn import_outline_target(
+        &mut self,
+        source: &Document,
+        item: &Dictionary,
+        id_map: &mut BTreeMap<ObjectId, ObjectId>,
+    ) -> Option<OutlineTarget> {
+        let dest = match item.get(b"Dest") {
+            Ok(dest) => dest.clone(),
+            Err(_) => {
+                let (_, action) = source.dereference(item.get(b"A").ok()?).ok()?;
+                let action = action.as_dict().ok()?;
+                if !is_goto(action) {
+                    // URI and other actions don't point at pages, copy them through
+                    let action = self.import_object(source, Object::Dictionary(action.clone()), id_map);
+                    return Some(OutlineTarget::Action(action));
+                }
+                action.get(b"D").ok()?.clone()
+            }
+        };
+
+        let explicit = match dest {
+            Object::Name(_) | Object::String(..) => resolve_named_destination(source, &dest)?,
+            Object::Reference(_) => source.dereference(&dest).ok()?.1.clone(),
+            other => other,
+        };
+        let explicit = self.import_object(source, explicit, id_map);
+
+        // Unselected pages were mapped to null during import
+        match explicit.as_array().ok()?.first() {
+            Some(Object::Reference(_)) => Some(OutlineTarget::Dest(explicit)),
+            _ => None,
+        }
+    }
+
+    /// Writes sibling outline items under `parent_id`, returning (first, last, visible descendant count)
+    fn write_outline_level(&mut self, nodes: Vec<OutlineNode>, parent_id: ObjectId) -> (ObjectId, ObjectId, i64) {
+        let ids: Vec<ObjectId> = nodes.iter().map(|_| self.document.new_object_id()).collect();
+        let mut visible = ids.len() as i64;
+
+        for (index, node) in nodes.into_iter().enumerate() {
+            let mut item = Dictionary::new();
+            item.set("Title", node.title);
+            item.set("Parent", Object::Reference(parent_id));
+            if index > 0 {
+                item.set("Prev", Object::Reference(ids[index - 1]));
+            }
+            if index + 1 < ids.len() {
+                item.set("Next", Object::Reference(ids[index + 1]));
+            }
+            match node.target {
+                Some(OutlineTarget::Dest(dest)) => item.set("Dest", dest),
+                Some(OutlineTarget::Action(action)) => item.set("A", action),
+                None => {}
+            }
+            for (key, value) in node.style {
+                item.set(key, value);
+            }
+
+            if !node.children.is_empty() {
+                let (first, last, count) = self.write_outline_level(node.children, ids[index]);
+                item.set("First", Object::Reference(first));
+                item.set("Last", Object::Reference(last));
+                // A positive count means expanded, negative collapsed
+                if node.open {
+                    item.set("Count", Object::Integer(count));
+                    visible += count;
+                } else {
+                    item.set("Count", Object::Integer(-count));
+                }
+            }
+
+            self.document.objects.insert(ids[index], Object::Dictionary(item));
+        }
+
+        (ids[0], ids[ids.len() - 1], visible)
+    }
+
+    /// Reserves the field's top-level name, appending " (2)", " (3)"... when it clashes
+    fn claim_field_name(&mut self, field_id: ObjectId) {
+        let Ok(field) = self.document.get_dictionary_mut(field_id) else {
+            return;
+        };
+        let Ok(Object::String(name, format)) = field.get(b"T").cloned() else {
+            return;
+        };
+
+        let mut candidate = name.clone();
+        let mut suffix = 2;
+        while self.form_field_names.contains(&candidate) {
+            candidate = [name.as_slice(), format!(" ({})", suffix).as_bytes()].concat();
+            suffix += 1;
+        }
+        if candidate != name {
+            field.set("T", Object::String(candidate.clone(), format));
+        }
+        self.form_field_names.insert(candidate);
+    }
+
+    /// Unions `/DR` resource dictionaries (fonts etc.); the first definition of a name wins
+    fn merge_default_resources(&mut self, resources: Object) {
+        let Ok((_, Object::Dictionary(resources))) = self.document.dereference(&resources) else {
+            return;
+        };
+        let resources = resources.clone();
+
+        let mut merged = match self.form_defaults.remove(b"DR") {
+            Some(Object::Dictionary(dict)) => dict,
+            _ => Dictionary::new(),
+        };
+        for (category, entries) in resources.iter() {
+            let Ok((_, Object::Dictionary(entries))) = self.document.dereference(entries) else {
+                continue;
+            };
+            let mut target = match merged.remove(category) {
+                Some(Object::Dictionary(dict)) => dict,
+                _ => Dictionary::new(),
+            };
+            for (name, value) in entries.iter() {
+                if !target.has(name) {
+                    target.set(name.clone(), value.clone());
+                }
+            }
+            merged.set(category.clone(), Object::Dictionary(target));
+        }
+        self.form_defaults.set("DR", Object::Dictionary(merged));
+    }
+}
+
+/// Deep-copies `object` and everything it references from `source` into `target`.
+/// `id_map` records what was already copied, so repeated imports from one source share objects.
+pub fn import_object(
+    target: &mut Document,
+    source: &Document,
+    object: Object,
+    id_map: &mut BTreeMap<ObjectId, ObjectId>,
+) -> Object {
+    let mut pending = VecDeque::new();
+    let copied = remap_references(target, source, object, id_map, &mut pending);
+    copy_pending(target, source, id_map, &mut pending);
+    copied
+}
+
+/// Copies queued objects until everything they reference has been copied too
+fn copy_pending(
+    target: &mut Document,
+    source: &Document,
+    id_map: &mut BTreeMap<ObjectId, ObjectId>,
+    pending: &mut VecDeque<(ObjectId, ObjectId)>,
+) {
+    // Breadth first, to avoid deep recursion on long reference chains
+    while let Some((source_id, new_id)) = pending.pop_front() {
+        let object = source.get_object(source_id).cloned().unwrap_or(Object::Null);
+        let copied = remap_references(target, source, object, id_map, pending);
+        target.objects.insert(new_id, copied);
+    }
+}
+
+/// Rewrites references inside `object` to ids in the new document, queueing unseen objects for copying.
+/// References to pages that were not selected (or to page tree nodes) become null.
+fn remap_references(
+    target: &mut Document,
+    source: &Document,
+    object: Object,
+    id_map: &mut BTreeMap<ObjectId, ObjectId>,
+    pending: &mut VecDeque<(ObjectId, ObjectId)>,
+) -> Object {
+    match object {
+        Object::Reference(source_id) => {
+            if let Some(&new_id) = id_map.get(&source_id) {
+                return Object::Reference(new_id);
+            }
+            if is_page_tree_node(source, source_id) {
+                return Object::Null;
+            }
+            let new_id = target.new_object_id();
+            id_map.insert(source_id, new_id);
+            pending.push_back((source_id, new_id));
+            Object::Reference(new_id)
+        }
+        Object::Array(items) => Object::Array(
+            items
+                .into_iter()
+                .map(|item| remap_references(target, source, item, id_map, pending))
+                .collect(),
+        ),
+        Object::Dictionary(dict) => Object::Dictionary(remap_dictionary(target, source, dict, id_map, pending)),
+        Object::Stream(mut stream) => {
+            stream.dict = remap_dictionary(target, source, stream.dict, id_map, pending);
+            Object::Stream(stream)
+        }
+        other => other,
+    }
+}
+
+fn remap_dictionary(
+    target: &mut Document,
+    source: &Document,
+    mut dict: Dictionary,
+    id_map: &mut BTreeMap<ObjectId, ObjectId>,
+    pending: &mut VecDeque<(ObjectId, ObjectId)>,
+) -> Dictionary {
+    for (_, value) in dict.iter_mut() {
+        let original = std::mem::replace(value, Object::Null);
+        *value = remap_references(target, source, original, id_map, pending);
+    }
+    dict
+}
+
+/// True for `/Page` and `/Pages` objects; only explicitly selected pages are ever copied
+fn is_page_tree_node(source: &Document, id: ObjectId) -> bool {
+    match source.get_object(id) {
+        Ok(Object::Dictionary(dict)) => dict.type_is(b"Page") || dict.type_is(b"Pages"),
+        _ => false,
+    }
+}
+
+pub fn is_goto(action: &Dictionary) -> bool {
+    matches!(action.get(b"S").and_then(Object::as_name), Ok(b"GoTo"))
+}
+
+/// The document's `/Outlines` dictionary, if any
+pub fn outline_root(doc: &Document) -> Option<&Dictionary> {
+    doc.catalog().ok()?.get_deref(b"Outlines", doc).ok()?.as_dict().ok()
+}
+
+/// Looks a named destination up in `/Dests` (PDF 1.1) or the `/Names` tree, returning the explicit array
+pub fn resolve_named_destination(doc: &Document, name: &Object) -> Option<Object> {
+    let catalog = doc.catalog().ok()?;
+    let key = match name {
+        Object::Name(bytes) | Object::String(bytes, _) => bytes.as_slice(),
+        _ => return None,
+    };
+
+    let from_dests = catalog
+        .get_deref(b"Dests", doc)
+        .ok()
+        .and_then(|dests| dests.as_dict().ok())
+        .and_then(|dests| dests.get(key).ok().cloned());
+    let found = from_dests.or_else(|| {
+        let names = catalog.get_deref(b"Names", doc).ok()?.as_dict().ok()?;
+        let tree = names.get_deref(b"Dests", doc).ok()?.as_dict().ok()?;
+        lookup_name_tree(doc, tree, key, 0)
+    })?;
+
+    // Values are either the array itself or a dictionary holding it under /D
+    let (_, found) = doc.dereference(&found).ok()?;
+    match found {
+        Object::Array(_) => Some(found.clone()),
+        Object::Dictionary(dict) => dict.get_deref(b"D", doc).ok().cloned(),
+        _ => None,
+    }
+}
+
+/// Searches a name tree for `key`
+fn lookup_name_tree(doc: &Document, node: &Dictionary, key: &[u8], depth: usize) -> Option<Object> {
+    if depth > 32 {
+        return None;
+    }
+
+    if let Ok(names) = node.get_deref(b"Names", doc).and_then(Object::as_array) {
+        for pair in names.chunks(2) {
+            if let [Object::String(name, _), value] = pair {
+                if name.as_slice() == key {
+                    return Some(value.clone());
+                }
+            }
+        }
+    }
+
+    if let Ok(kids) = node.get_deref(b"Kids", doc).and_then(Object::as_array) {
+        for kid in kids {
+            let Some(kid) = kid.as_reference().ok().and_then(|id| doc.get_dictionary(id).ok()) else {
+                continue;
+            };
+            // Skip subtrees whose /Limits exclude the key
+            if let Ok([Object::String(low, _), Object::String(high, _)]) =
+                kid.get(b"Limits").and_then(Object::as_array).map(Vec::as_slice)
+            {
+                if key < low.as_slice() || key > high.as_slice() {
+                    continue;
+                }
+            }
+            if let Some(found) = lookup_name_tree(doc, kid, key, depth + 1) {
+                return Some(found);
+            }
+        }
+    }
+
+    None
+}
+
+/// Returns a copy of the page dictionary with inherited attributes resolved from its ancestors
+pub fn flatten_inherited_attributes(doc: &Document, page_id: ObjectId) -> Result<Dictionary, String> {
+    let mut page = doc
+        .get_dictionary(page_id)
+        .map_err(|e| format!("Failed to get page object: {}", e))?
+        .clone();
+
+    for key in INHERITABLE_PAGE_KEYS {
+        if page.has(key) {
+            continue;
+        }
+        if let Some(value) = find_inherited_attribute(doc, page_id, key) {
+            page.set(key.to_vec(), value.clone());
+        }
+    }
+
+    Ok(page)
+}
+
+/// Looks up a page attribute on the page itself or the nearest ancestor that defines it
+pub fn find_inherited_attribute<'a>(doc: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut current = doc.get_dictionary(page_id).ok()?;
+
+    // Bound the walk in case of a cyclic /Parent chain
+    for _ in 0..64 {
+        if let Ok(value) = current.get(key) {
+            return Some(value);
+        }
+        let parent_id = current.get(b"Parent").and_then(Object::as_reference).ok()?;
+        current = doc.get_dictionary(parent_id).ok()?;
+    }
+
+    None
+}
@@ -0,0 +1,236 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, SecondsFormat, Utc};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::HashSet;
+
+use crate::utils::pdf_strings::decode_text_string;
+
+/// Parses a PDF date string, "D:YYYYMMDDHHmmSSOHH'mm'" where everything after the year is optional
+pub fn parse_pdf_date(text: &str) -> Option<DateTime<FixedOffset>> {
+    let text = text.trim();
+    let text = text.strip_prefix("D:").unwrap_or(text);
+    let digits = text.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits < 4 {
+        return None;
+    }
+
+    let field = |start: usize, default: u32| -> u32 {
+        text.get(start..start + 2)
+            .filter(|_| start + 2 <= digits)
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(default)
+    };
+    let year: i32 = text[..4].parse().ok()?;
+    let naive = NaiveDate::from_ymd_opt(year, field(4, 1), field(6, 1))?
+        .and_hms_opt(field(8, 0), field(10, 0), field(12, 0))?;
+
+    // Offset: Z, or +HH'mm' / -HH'mm' with the minutes optional
+    let zone = &text[digits..];
+    let offset_seconds = match zone.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            let numbers: Vec<i32> = zone[1..]
+                .split('\'')
+                .filter_map(|part| part.trim().parse().ok())
+                .collect();
+            let seconds = numbers.first().copied().unwrap_or(0) * 3600 + numbers.get(1).copied().unwrap_or(0) * 60;
+            if sign == '-' { -seconds } else { seconds }
+        }
+        _ => 0,
+    };
+    naive.and_local_timezone(FixedOffset::east_opt(offset_seconds)?).single()
+}
+
+/// Formats a date as a PDF date string
+pub fn format_pdf_date(date: &DateTime<FixedOffset>) -> String {
+    let offset = date.offset().local_minus_utc();
+    let zone = if offset == 0 {
+        "Z".to_string()
+    } else {
+        let sign = if offset < 0 { '-' } else { '+' };
+        format!("{}{:02}'{:02}'", sign, offset.abs() / 3600, offset.abs() % 3600 / 60)
+    };
+    format!("D:{}{}", date.format("%Y%m%d%H%M%S"), zone)
+}
+
+/// The document's `/Info` dictionary, created (as an indirect object) if missing
+pub fn info_dictionary_mut(doc: &mut Document) -> Result<&mut Dictionary, String> {
+    let info_id = match doc.trailer.get(b"Info") {
+        Ok(Object::Reference(id)) if doc.get_dictionary(*id).is_ok() => *id,
+        existing => {
+            let info = match existing {
+                Ok(Object::Dictionary(dict)) => dict.clone(),
+                _ => Dictionary::new(),
+            };
+            let id = doc.add_object(info);
+            doc.trailer.set("Info", id);
+            id
+        }
+    };
+    doc.get_dictionary_mut(info_id)
+        .map_err(|e| format!("Failed to get document info: {}", e))
+}
+
+/// Info entries as text, with strings decoded
+pub fn read_info(doc: &Document) -> Vec<(String, String)> {
+    let Some(info) = doc
+        .trailer
+        .get(b"Info")
+        .ok()
+        .and_then(|value| doc.dereference(value).ok())
+        .and_then(|(_, value)| value.as_dict().ok())
+    else {
+        return Vec::new();
+    };
+
+    info.iter()
+        .map(|(key, value)| {
+            let value = doc.dereference(value).map(|(_, v)| v).unwrap_or(value);
+            let text = match value {
+                Object::String(bytes, _) => decode_text_string(bytes),
+                Object::Name(name) => String::from_utf8_lossy(name).to_string(),
+                Object::Integer(i) => i.to_string(),
+                Object::Real(f) => f.to_string(),
+                Object::Boolean(b) => b.to_string(),
+                other => format!("{:?}", other),
+            };
+            (String::from_utf8_lossy(key).to_string(), text)
+        })
+        .collect()
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The current XMP packet of the catalog's `/Metadata` stream, if any
+pub fn read_xmp(doc: &Document) -> Option<String> {
+    let catalog = doc.catalog().ok()?;
+    let (_, metadata) = doc.dereference(catalog.get(b"Metadata").ok()?).ok()?;
+    let stream = metadata.as_stream().ok()?;
+    let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+    Some(String::from_utf8_lossy(&content).to_string())
+}
+
+/// Builds an XMP packet mirroring the Info entries. PDF/A identification from the
+/// previous packet is kept so archival files don't lose their conformance claim.
+pub fn build_xmp(info: &[(String, String)], previous: Option<&str>) -> String {
+    let get = |key: &str| info.iter().find(|(k, v)| k == key && !v.is_empty()).map(|(_, v)| xml_escape(v));
+    let xmp_date = |key: &str| {
+        info.iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, v)| parse_pdf_date(v))
+            .map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, true))
+    };
+
+    let mut properties = vec!["<dc:format>application/pdf</dc:format>".to_string()];
+    if let Some(title) = get("Title") {
+        properties.push(format!("<dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>", title));
+    }
+    if let Some(author) = get("Author") {
+        properties.push(format!("<dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>", author));
+    }
+    if let Some(subject) = get("Subject") {
+        properties.push(format!("<dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>", subject));
+    }
+    if let Some(keywords) = get("Keywords") {
+        properties.push(format!("<pdf:Keywords>{}</pdf:Keywords>", keywords));
+    }
+    if let Some(producer) = get("Producer") {
+        properties.push(format!("<pdf:Producer>{}</pdf:Producer>", producer));
+    }
+    if let Some(creator) = get("Creator") {
+        properties.push(format!("<xmp:CreatorTool>{}</xmp:CreatorTool>", creator));
+    }
+    if let Some(created) = xmp_date("CreationDate") {
+        properties.push(format!("<xmp:CreateDate>{}</xmp:CreateDate>", created));
+    }
+    if let Some(modified) = xmp_date("ModDate") {
+        properties.push(format!("<xmp:ModifyDate>{}</xmp:ModifyDate>", modified));
+    }
+    properties.push(format!("<xmp:MetadataDate>{}</xmp:MetadataDate>", Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)));
+
+    // Element (<pdfaid:part>1</pdfaid:part>) or attribute (pdfaid:part="1") form
+    let pdfa = |name: &str| {
+        let pattern = regex::Regex::new(&format!(r#"pdfaid:{}(?:>|\s*=\s*["'])\s*([0-9A-Za-z]+)"#, name)).ok()?;
+        Some(pattern.captures(previous?)?.get(1)?.as_str().to_string())
+    };
+    if let Some(part) = pdfa("part") {
+        properties.push(format!("<pdfaid:part>{}</pdfaid:part>", part));
+        if let Some(conformance) = pdfa("conformance") {
+            properties.push(format!("<pdfaid:conformance>{}</pdfaid:conformance>", conformance));
+        }
+    }
+
+    format!(
+        concat!(
+            "<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+            " <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+            "  <rdf:Description rdf:about=\"\"\n",
+            "    xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n",
+            "    xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n",
+            "    xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\"\n",
+            "    xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\">\n",
+            "{}\n",
+            "  </rdf:Description>\n",
+            " </rdf:RDF>\n",
+            "</x:xmpmeta>\n",
+            "<?xpacket end=\"w\"?>"
+        ),
+        properties.iter().map(|p| format!("   {}", p)).collect::<Vec<_>>().join("\n")
+    )
+}
+
+/// Replaces the catalog's XMP metadata stream, or adds one
+pub fn write_xmp(doc: &mut Document, xmp: String) -> Result<(), String> {
+    let stream = Stream::new(dictionary! { "Type" => "Metadata", "Subtype" => "XML" }, xmp.into_bytes());
+    let existing = doc
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"Metadata").ok())
+        .and_then(|value| value.as_reference().ok());
+
+    match existing {
+        Some(id) => {
+            doc.objects.insert(id, Object::Stream(stream));
+        }
+        None => {
+            let id = doc.add_object(stream);
+            doc.catalog_mut()
+                .map_err(|e| format!("Failed to get document catalog: {}", e))?
+                .set("Metadata", id);
+        }
+    }
+    Ok(())
+}
+
+/// Removes the Info dictionary, every XMP metadata stream (document, page and image level)
+/// and application private data (`/PieceInfo`)
+pub fn scrub_metadata(doc: &mut Document) {
+    if let Ok(info_id) = doc.trailer.get(b"Info").and_then(Object::as_reference) {
+        doc.objects.remove(&info_id);
+    }
+    doc.trailer.remove(b"Info");
+
+    let metadata_ids: HashSet<ObjectId> = doc
+        .objects
+        .iter()
+        .filter(|(_, object)| matches!(object, Object::Stream(stream) if stream.dict.type_is(b"Metadata")))
+        .map(|(&id, _)| id)
+        .collect();
+    for id in &metadata_ids {
+        doc.objects.remove(id);
+    }
+
+    for object in doc.objects.values_mut() {
+        let dict = match object {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &mut stream.dict,
+            _ => continue,
+        };
+        dict.remove(b"Metadata");
+        dict.remove(b"PieceInfo");
+    }
+}
@@ -0,0 +1,278 @@
+use lopdf::{Document, Object, ObjectId};
+use std::collections::HashMap;
+
+use crate::utils::pdf_merge::{find_inherited_attribute, is_goto, outline_root, resolve_named_destination};
+use crate::utils::pdf_strings::decode_text_string;
+
+/// Named paper sizes in points, portrait
+pub const PAPER_SIZES: &[(&str, f32, f32)] = &[
+    ("A0", 2383.94, 3370.39),
+    ("A1", 1683.78, 2383.94),
+    ("A2", 1190.55, 1683.78),
+    ("A3", 841.89, 1190.55),
+    ("A4", 595.28, 841.89),
+    ("A5", 419.53, 595.28),
+    ("A6", 297.64, 419.53),
+    ("B4", 708.66, 1000.63),
+    ("B5", 498.9, 708.66),
+    ("Letter", 612.0, 792.0),
+    ("Legal", 612.0, 1008.0),
+    ("Tabloid", 792.0, 1224.0),
+    ("Executive", 522.0, 756.0),
+];
+
+/// Parses a page range list such as "1-3,5,7-" into inclusive (start, end) pairs.
+///
+/// An open end ("7-") runs to the last page and an open start ("-3") from the first.
+pub fn parse_page_ranges(spec: &str, page_count: u32) -> Result<Vec<(u32, u32)>, String> {
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid page number: {}", value.trim()))
+    };
+
+    let mut ranges = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => {
+                let start = if start.trim().is_empty() { 1 } else { parse(start)? };
+                let end = if end.trim().is_empty() { page_count } else { parse(end)? };
+                (start, end)
+            }
+            None => {
+                let page = parse(part)?;
+                (page, page)
+            }
+        };
+
+        if start == 0 || start > end || end > page_count {
+            return Err(format!("Invalid page range {} (document has {} pages)", part, page_count));
+        }
+        ranges.push((start, end));
+    }
+
+    if ranges.is_empty() {
+        return Err("No page ranges given".to_string());
+    }
+    Ok(ranges)
+}
+
+/// Top-level bookmarks as (title, 1-based page number), in outline order.
+/// Bookmarks that don't lead to a page of this document are skipped.
+pub fn top_level_bookmarks(doc: &Document) -> Vec<(String, u32)> {
+    let page_numbers: HashMap<ObjectId, u32> = doc
+        .get_pages()
+        .into_iter()
+        .map(|(number, id)| (id, number))
+        .collect();
+
+    let mut bookmarks = Vec::new();
+    let mut current = outline_root(doc).and_then(|root| root.get(b"First").and_then(Object::as_reference).ok());
+    let mut visited = std::collections::HashSet::new();
+
+    while let Some(item_id) = current {
+        if !visited.insert(item_id) {
+            break;
+        }
+        let Ok(item) = doc.get_dictionary(item_id) else {
+            break;
+        };
+        current = item.get(b"Next").and_then(Object::as_reference).ok();
+
+        let page = outline_item_page(doc, item).and_then(|id| page_numbers.get(&id).copied());
+        if let Some(page) = page {
+            let title = item
+                .get_deref(b"Title", doc)
+                .and_then(Object::as_str)
+                .map(decode_text_string)
+                .unwrap_or_default();
+            bookmarks.push((title, page));
+        }
+    }
+
+    bookmarks
+}
+
+/// The page an outline item points at, following named destinations and GoTo actions
+fn outline_item_page(doc: &Document, item: &lopdf::Dictionary) -> Option<ObjectId> {
+    let dest = match item.get(b"Dest") {
+        Ok(dest) => dest,
+        Err(_) => {
+            let action = item.get_deref(b"A", doc).ok()?.as_dict().ok()?;
+            if !is_goto(action) {
+                return None;
+            }
+            action.get(b"D").ok()?
+        }
+    };
+
+    let explicit = match doc.dereference(dest).ok()?.1 {
+        named @ (Object::Name(_) | Object::String(..)) => resolve_named_destination(doc, named)?,
+        other => other.clone(),
+    };
+    explicit.as_array().ok()?.first()?.as_reference().ok()
+}
+
+/// Width and height in points of a page's (possibly inherited) MediaBox, US Letter if missing
+pub fn media_box_size(doc: &Document, page_id: ObjectId) -> (f32, f32) {
+    match page_box(doc, page_id, b"MediaBox") {
+        Some([x0, y0, x1, y1]) => (x1 - x0, y1 - y0),
+        None => (612.0, 792.0),
+    }
+}
+
+/// A page boundary box ("MediaBox", "CropBox"...) as normalised [x0, y0, x1, y1], following inheritance
+pub fn page_box(doc: &Document, page_id: ObjectId, key: &[u8]) -> Option<[f32; 4]> {
+    let (_, value) = doc.dereference(find_inherited_attribute(doc, page_id, key)?).ok()?;
+    let values: Vec<f32> = value
+        .as_array()
+        .ok()?
+        .iter()
+        .filter_map(|v| doc.dereference(v).ok().and_then(|(_, v)| v.as_float().ok()))
+        .collect();
+
+    match values.as_slice() {
+        [a, b, c, d] => Some([a.min(*c), b.min(*d), a.max(*c), b.max(*d)]),
+        _ => None,
+    }
+}
+
+/// The visible area of a page: its CropBox clipped to the MediaBox
+pub fn visible_box(doc: &Document, page_id: ObjectId) -> [f32; 4] {
+    let media = page_box(doc, page_id, b"MediaBox").unwrap_or([0.0, 0.0, 612.0, 792.0]);
+    match page_box(doc, page_id, b"CropBox") {
+        Some(crop) => clip_box(crop, media),
+        None => media,
+    }
+}
+
+/// Intersection of two boxes; `inner` is ignored if they don't overlap
+pub fn clip_box(inner: [f32; 4], outer: [f32; 4]) -> [f32; 4] {
+    let clipped = [inner[0].max(outer[0]), inner[1].max(outer[1]), inner[2].min(outer[2]), inner[3].min(outer[3])];
+    if clipped[0] < clipped[2] && clipped[1] < clipped[3] { clipped } else { outer }
+}
+
+/// Page rotation normalised to 0, 90, 180 or 270 degrees clockwise
+pub fn page_rotation(doc: &Document, page_id: ObjectId) -> i64 {
+    find_inherited_attribute(doc, page_id, b"Rotate")
+        .and_then(|value| value.as_i64().ok())
+        .map(|degrees| degrees.rem_euclid(360) / 90 * 90)
+        .unwrap_or(0)
+}
+
+/// Name of the paper size matching these dimensions in either orientation, within a millimetre
+pub fn paper_size_name(width: f32, height: f32) -> Option<&'static str> {
+    let (short, long) = (width.min(height), width.max(height));
+    PAPER_SIZES
+        .iter()
+        .find(|(_, w, h)| (short - w).abs() <= 3.0 && (long - h).abs() <= 3.0)
+        .map(|(name, _, _)| *name)
+}
+
+/// Portrait width and height in points of a named paper size ("A4", "letter"...)
+pub fn paper_size(name: &str) -> Option<(f32, f32)> {
+    PAPER_SIZES
+        .iter()
+        .find(|(size, _, _)| size.eq_ignore_ascii_case(name.trim()))
+        .map(|&(_, width, height)| (width, height))
+}
+
+/// Maps a point given in points from the top-left corner of the page as displayed
+/// (after /Rotate) back to default user space
+pub fn displayed_to_user(visible_box: [f32; 4], rotation: i64, x: f32, y: f32) -> (f32, f32) {
+    let [x0, y0, x1, y1] = visible_box;
+    match rotation {
+        90 => (x0 + y, y0 + x),
+        180 => (x1 - x, y0 + y),
+        270 => (x1 - y, y1 - x),
+        _ => (x0 + x, y1 - y),
+    }
+}
+
+/// Text matrix placing text at a displayed point (see `displayed_to_user`) so it reads upright on screen
+pub fn upright_text_matrix(visible_box: [f32; 4], rotation: i64, x: f32, y: f32) -> [f32; 6] {
+    let (e, f) = displayed_to_user(visible_box, rotation, x, y);
+    match rotation {
+        90 => [0.0, 1.0, -1.0, 0.0, e, f],
+        180 => [-1.0, 0.0, 0.0, -1.0, e, f],
+        270 => [0.0, -1.0, 1.0, 0.0, e, f],
+        _ => [1.0, 0.0, 0.0, 1.0, e, f],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_ranges_with_open_ends() {
+        assert_eq!(parse_page_ranges("1-3, 5,7-", 10), Ok(vec![(1, 3), (5, 5), (7, 10)]));
+        assert_eq!(parse_page_ranges("-2", 10), Ok(vec![(1, 2)]));
+        assert_eq!(parse_page_ranges("-", 4), Ok(vec![(1, 4)]));
+    }
+
+    #[test]
+    fn reversed_page_ranges_are_rejected() {
+        assert!(parse_page_ranges("5-3", 10).is_err());
+    }
+
+    #[test]
+    fn out_of_range_pages_are_rejected() {
+        assert!(parse_page_ranges("0", 10).is_err());
+        assert!(parse_page_ranges("8-11", 10).is_err());
+        assert!(parse_page_ranges("11-", 10).is_err());
+        assert!(parse_page_ranges("4294967296", 10).is_err());
+    }
+
+    #[test]
+    fn duplicate_page_ranges_are_kept() {
+        assert_eq!(parse_page_ranges("2,2,1-3", 5), Ok(vec![(2, 2), (2, 2), (1, 3)]));
+    }
+
+    #[test]
+    fn empty_or_malformed_page_ranges_are_rejected() {
+        assert!(parse_page_ranges("", 10).is_err());
+        assert!(parse_page_ranges(" , ", 10).is_err());
+        assert!(parse_page_ranges("a-3", 10).is_err());
+        assert!(parse_page_ranges("1-2-3", 10).is_err());
+    }
+
+    // A 200 x 300 page whose visible box doesn't start at the origin
+    const BOX: [f32; 4] = [10.0, 20.0, 210.0, 320.0];
+
+    #[test]
+    fn displayed_corners_map_to_the_visible_box() {
+        // Displayed size, then the user-space corners seen at the displayed top-left and bottom-right
+        let cases = [
+            (0, (200.0, 300.0), (10.0, 320.0), (210.0, 20.0)),
+            (90, (300.0, 200.0), (10.0, 20.0), (210.0, 320.0)),
+            (180, (200.0, 300.0), (210.0, 20.0), (10.0, 320.0)),
+            (270, (300.0, 200.0), (210.0, 320.0), (10.0, 20.0)),
+        ];
+        for (rotation, (width, height), top_left, bottom_right) in cases {
+            assert_eq!(displayed_to_user(BOX, rotation, 0.0, 0.0), top_left, "rotation {}", rotation);
+            assert_eq!(displayed_to_user(BOX, rotation, width, height), bottom_right, "rotation {}", rotation);
+        }
+    }
+
+    #[test]
+    fn unknown_rotations_are_treated_as_upright() {
+        assert_eq!(displayed_to_user(BOX, 45, 5.0, 7.0), displayed_to_user(BOX, 0, 5.0, 7.0));
+    }
+
+    #[test]
+    fn upright_text_runs_rightwards_and_up_on_screen() {
+        let (x, y) = (30.0, 40.0);
+        for rotation in [0, 90, 180, 270] {
+            let [a, b, c, d, e, f] = upright_text_matrix(BOX, rotation, x, y);
+            let origin = displayed_to_user(BOX, rotation, x, y);
+            let right = displayed_to_user(BOX, rotation, x + 1.0, y);
+            let up = displayed_to_user(BOX, rotation, x, y - 1.0);
+
+            assert_eq!((e, f), origin, "rotation {}", rotation);
+            assert_eq!((a, b), (right.0 - origin.0, right.1 - origin.1), "rotation {}", rotation);
+            assert_eq!((c, d), (up.0 - origin.0, up.1 - origin.1), "rotation {}", rotation);
+        }
+    }
+}
@@ -0,0 +1,1291 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::utils::pdf_content::helvetica_text_width;
+use crate::utils::pdf_images::{decode_image_xobject, embed_image, EmbeddedImage};
+use crate::utils::pdf_merge::find_inherited_attribute;
+use crate::utils::pdf_pages::{displayed_to_user, page_rotation, visible_box};
+
+/// Forms nested deeper than this are dropped whole when they touch a redaction area
+const MAX_FORM_DEPTH: usize = 12;
+
+/// Marked-content properties that can carry a copy of the text they mark
+const TEXT_PROPERTIES: &[&[u8]] = &[b"ActualText", b"Alt", b"E"];
+
+/// Arrays and dictionaries nested deeper than this in a content stream are rejected as malformed
+const MAX_NESTING: usize = 64;
+
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// What was removed from a page
+#[derive(Debug, Default, Clone)]
+pub struct RedactionCounts {
+    pub glyphs: usize,
+    pub images: usize,
+    pub paths: usize,
+    pub annotations: usize,
+    pub image_ids: Vec<ObjectId>,  // The original images that were cut or blacked out
+}
+
+impl RedactionCounts {
+    pub fn add(&mut self, other: RedactionCounts) {
+        self.glyphs += other.glyphs;
+        self.images += other.images;
+        self.paths += other.paths;
+        self.annotations += other.annotations;
+        self.image_ids.extend(other.image_ids);
+    }
+}
+
+/// Removes everything drawn inside `areas` (default user space) from a page: glyphs are cut
+/// out of text (the rest of the line keeps its position), image pixels are painted black,
+/// vector paths crossing an area are dropped, forms are redacted recursively and overlapping
+/// annotations are deleted. Replaced objects are only unreferenced; prune the document before saving.
+pub fn redact_page(doc: &mut Document, page_id: ObjectId, areas: &[[f32; 4]]) -> Result<RedactionCounts, String> {
+    let content = doc
+        .get_page_content(page_id)
+        .map_err(|e| format!("Failed to read page content: {}", e))?;
+    let resources = page_resources(doc, page_id);
+    let listed = listed_xobjects(doc, &resources);
+
+    let mut redactor = Redactor { doc, areas, counts: RedactionCounts::default(), fonts: HashMap::new() };
+    let redacted = redactor.redact_stream(&content, resources, IDENTITY, 0)?;
+    let mut counts = redactor.counts;
+
+    if let Some((content, resources)) = redacted {
+        let still_listed: HashSet<ObjectId> = listed_xobjects(doc, &resources).into_values().collect();
+        let mut stream = Stream::new(Dictionary::new(), content);
+        let _ = stream.compress();
+        let content_id = doc.add_object(stream);
+        let page = doc
+            .get_dictionary_mut(page_id)
+            .map_err(|e| format!("Failed to get page object: {}", e))?;
+        page.set("Contents", Object::Reference(content_id));
+        page.set("Resources", Object::Dictionary(resources));
+
+        let replaced: HashSet<ObjectId> = listed.into_values().filter(|id| !still_listed.contains(id)).collect();
+        if !replaced.is_empty() {
+            unlist_xobjects(doc, &replaced)?;
+        }
+    }
+
+    counts.annotations = redact_annotations(doc, page_id, areas)?;
+    Ok(counts)
+}
+
+/// A page's resources, inherited ones included, as a direct dictionary
+fn page_resources(doc: &Document, page_id: ObjectId) -> Dictionary {
+    find_inherited_attribute(doc, page_id, b"Resources")
+        .and_then(|value| doc.dereference(value).ok())
+        .and_then(|(_, value)| value.as_dict().ok().cloned())
+        .unwrap_or_default()
+}
+
+/// A resource category (Font, XObject...) as a direct dictionary
+fn resource_category(doc: &Document, resources: &Dictionary, category: &[u8]) -> Dictionary {
+    resources
+        .get(category)
+        .ok()
+        .and_then(|value| doc.dereference(value).ok())
+        .and_then(|(_, value)| value.as_dict().ok().cloned())
+        .unwrap_or_default()
+}
+
+/// The XObjects named in a resource dictionary, by name
+fn listed_xobjects(doc: &Document, resources: &Dictionary) -> HashMap<Vec<u8>, ObjectId> {
+    resource_category(doc, resources, b"XObject")
+        .iter()
+        .filter_map(|(name, value)| Some((name.clone(), value.as_reference().ok()?)))
+        .collect()
+}
+
+/// Every XObject a page draws, following nested forms. A stream that can't be parsed
+/// counts as drawing everything its resources list.
+pub fn drawn_xobjects(doc: &Document, page_id: ObjectId) -> HashSet<ObjectId> {
+    let mut drawn = HashSet::new();
+    let content = doc.get_page_content(page_id).unwrap_or_default();
+    let mut pending = vec![(content, page_resources(doc, page_id))];
+
+    while let Some((content, resources)) = pending.pop() {
+        let listed = listed_xobjects(doc, &resources);
+        let Ok(items) = parse_content(&content) else {
+            drawn.extend(listed.into_values());
+            continue;
+        };
+        for item in items {
+            let ContentItem::Operation(operation) = item else { continue };
+            let name = operation.operands.first().and_then(|n| n.as_name().ok());
+            let Some(&id) = name.filter(|_| operation.operator == "Do").and_then(|name| listed.get(name)) else {
+                continue;
+            };
+            if !drawn.insert(id) {
+                continue;
+            }
+            if let Ok(Object::Stream(form)) = doc.get_object(id) {
+                if form.dict.get(b"Subtype").and_then(Object::as_name).is_ok_and(|s| s == b"Form") {
+                    // Forms without their own resources use the ones they are drawn with
+                    let form_resources = match form.dict.get(b"Resources") {
+                        Ok(value) => doc.dereference(value).ok().and_then(|(_, v)| v.as_dict().ok().cloned()).unwrap_or_default(),
+                        Err(_) => resources.clone(),
+                    };
+                    let content = form.decompressed_content().unwrap_or_else(|_| form.content.clone());
+                    pending.push((content, form_resources));
+                }
+            }
+        }
+    }
+    drawn
+}
+
+/// Resource dictionaries are often shared between pages, so after a page's XObjects were
+/// replaced the originals may still be listed for other pages. Every page listing one gets
+/// resources of its own without the ones it doesn't draw, and the page tree nodes (whose
+/// resources are now pinned on every page inheriting them) drop them all.
+fn unlist_xobjects(doc: &mut Document, replaced: &HashSet<ObjectId>) -> Result<(), String> {
+    for page_id in doc.get_pages().into_values() {
+        let mut resources = page_resources(doc, page_id);
+        let listed = listed_xobjects(doc, &resources);
+        if !listed.values().any(|id| replaced.contains(id)) {
+            continue;
+        }
+
+        let drawn = drawn_xobjects(doc, page_id);
+        let mut xobjects = resource_category(doc, &resources, b"XObject");
+        for (name, id) in listed {
+            if replaced.contains(&id) && !drawn.contains(&id) {
+                xobjects.remove(&name);
+            }
+        }
+        resources.set("XObject", Object::Dictionary(xobjects));
+        doc.get_dictionary_mut(page_id)
+            .map_err(|e| format!("Failed to get page object: {}", e))?
+            .set("Resources", Object::Dictionary(resources));
+    }
+
+    let nodes: Vec<ObjectId> = doc
+        .objects
+        .iter()
+        .filter(|(_, object)| object.as_dict().is_ok_and(|dict| dict.type_is(b"Pages") && dict.has(b"Resources")))
+        .map(|(id, _)| *id)
+        .collect();
+    for node_id in nodes {
+        let resources = doc
+            .get_dictionary(node_id)
+            .and_then(|node| node.get_deref(b"Resources", doc))
+            .and_then(Object::as_dict)
+            .cloned();
+        let Ok(mut resources) = resources else {
+            continue;
+        };
+        let mut xobjects = resource_category(doc, &resources, b"XObject");
+        for (name, id) in listed_xobjects(doc, &resources) {
+            if replaced.contains(&id) {
+                xobjects.remove(&name);
+            }
+        }
+        resources.set("XObject", Object::Dictionary(xobjects));
+        doc.get_dictionary_mut(node_id)
+            .map_err(|e| format!("Failed to get page tree: {}", e))?
+            .set("Resources", Object::Dictionary(resources));
+    }
+    Ok(())
+}
+
+/// Deletes annotations overlapping the areas; form fields behind deleted widgets lose their values
+fn redact_annotations(doc: &mut Document, page_id: ObjectId, areas: &[[f32; 4]]) -> Result<usize, String> {
+    let annotations = match doc.get_dictionary(page_id).and_then(|page| page.get(b"Annots")) {
+        Ok(value) => match doc.dereference(value) {
+            Ok((_, Object::Array(items))) => items.clone(),
+            _ => return Ok(0),
+        },
+        Err(_) => return Ok(0),
+    };
+
+    let mut kept = Vec::new();
+    let mut cleared_fields = Vec::new();
+    for annotation in annotations {
+        let Ok((annotation_id, Object::Dictionary(dict))) = doc.dereference(&annotation) else {
+            kept.push(annotation);
+            continue;
+        };
+        let rect = dict.get(b"Rect").ok().and_then(|r| rect_from_object(doc, r));
+        if !rect.is_some_and(|rect| areas.iter().any(|area| touches(area, &rect))) {
+            kept.push(annotation);
+            continue;
+        }
+
+        if dict.get(b"Subtype").and_then(Object::as_name).is_ok_and(|s| s == b"Widget") {
+            cleared_fields.extend(annotation_id);
+            if let Ok(parent) = dict.get(b"Parent").and_then(Object::as_reference) {
+                cleared_fields.push(parent);
+            }
+        }
+    }
+
+    for id in cleared_fields {
+        if let Ok(field) = doc.get_dictionary_mut(id) {
+            field.remove(b"V");
+            field.remove(b"AP");
+        }
+    }
+
+    let removed = doc.get_dictionary(page_id).ok().and_then(|page| page.get(b"Annots").ok()).map_or(0, |value| {
+        doc.dereference(value).ok().and_then(|(_, v)| v.as_array().ok()).map_or(0, |all| all.len() - kept.len())
+    });
+    doc.get_dictionary_mut(page_id)
+        .map_err(|e| format!("Failed to get page object: {}", e))?
+        .set("Annots", Object::Array(kept));
+    Ok(removed)
+}
+
+/// Replaces a page's content with an image of it, as rendered, dropping annotations
+pub fn flatten_page_to_image(doc: &mut Document, page_id: ObjectId, image: &EmbeddedImage) -> Result<(), String> {
+    let visible = visible_box(doc, page_id);
+    let rotation = page_rotation(doc, page_id);
+    let (width, height) = if rotation == 90 || rotation == 270 {
+        (visible[3] - visible[1], visible[2] - visible[0])
+    } else {
+        (visible[2] - visible[0], visible[3] - visible[1])
+    };
+
+    // The image's unit square spans the page as displayed, bottom-left corner first
+    let origin = displayed_to_user(visible, rotation, 0.0, height);
+    let right = displayed_to_user(visible, rotation, width, height);
+    let up = displayed_to_user(visible, rotation, 0.0, 0.0);
+    let matrix = [right.0 - origin.0, right.1 - origin.1, up.0 - origin.0, up.1 - origin.1, origin.0, origin.1];
+
+    let content = Content {
+        operations: vec![
+            Operation::new("q", vec![]),
+            Operation::new("cm", matrix.iter().map(|&v| Object::Real(v)).collect()),
+            Operation::new("Do", vec![Object::Name(b"Im1".to_vec())]),
+            Operation::new("Q", vec![]),
+        ],
+    }
+    .encode()
+    .map_err(|e| format!("Failed to encode page content: {}", e))?;
+    let content_id = doc.add_object(Stream::new(Dictionary::new(), content));
+
+    let mut xobjects = Dictionary::new();
+    xobjects.set("Im1", Object::Reference(image.id));
+    let mut resources = Dictionary::new();
+    resources.set("XObject", Object::Dictionary(xobjects));
+
+    let page = doc
+        .get_dictionary_mut(page_id)
+        .map_err(|e| format!("Failed to get page object: {}", e))?;
+    page.set("Contents", Object::Reference(content_id));
+    page.set("Resources", Object::Dictionary(resources));
+    page.remove(b"Annots");
+    Ok(())
+}
+
+/// Whether a glyph box is covered enough by an area to count as inside it
+pub fn covers_glyph(area: &[f32; 4], glyph: &[f32; 4], fraction: f32) -> bool {
+    let (width, height) = (glyph[2] - glyph[0], glyph[3] - glyph[1]);
+    let overlap_x = area[2].min(glyph[2]) - area[0].max(glyph[0]);
+    let overlap_y = area[3].min(glyph[3]) - area[1].max(glyph[1]);
+    // Zero-size glyphs still hold text, so they only need to touch the area
+    let needed = |extent: f32| if extent > 0.01 { extent * fraction } else { 0.0 };
+    overlap_x >= needed(width) && overlap_y >= needed(height)
+}
+
+/// Whether two boxes share any point, edges included
+fn touches(a: &[f32; 4], b: &[f32; 4]) -> bool {
+    a[0] <= b[2] && b[0] <= a[2] && a[1] <= b[3] && b[1] <= a[3]
+}
+
+/// Whether two boxes overlap by a positive area
+fn overlaps(a: &[f32; 4], b: &[f32; 4]) -> bool {
+    a[0] < b[2] && b[0] < a[2] && a[1] < b[3] && b[1] < a[3]
+}
+
+fn contains(outer: &[f32; 4], inner: &[f32; 4]) -> bool {
+    outer[0] <= inner[0] && outer[1] <= inner[1] && outer[2] >= inner[2] && outer[3] >= inner[3]
+}
+
+fn rect_from_object(doc: &Document, object: &Object) -> Option<[f32; 4]> {
+    let (_, object) = doc.dereference(object).ok()?;
+    let values: Vec<f32> = object.as_array().ok()?.iter().filter_map(|v| v.as_float().ok()).collect();
+    match values.as_slice() {
+        [a, b, c, d] => Some([a.min(*c), b.min(*d), a.max(*c), b.max(*d)]),
+        _ => None,
+    }
+}
+
+/// `a` then `b`, in PDF's row-vector convention
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+fn transform(m: &Matrix, x: f32, y: f32) -> (f32, f32) {
+    (x * m[0] + y * m[2] + m[4], x * m[1] + y * m[3] + m[5])
+}
+
+fn invert(m: &Matrix) -> Option<Matrix> {
+    let determinant = m[0] * m[3] - m[1] * m[2];
+    if determinant.abs() < 1e-9 {
+        return None;
+    }
+    let (a, b, c, d) = (m[3] / determinant, -m[1] / determinant, -m[2] / determinant, m[0] / determinant);
+    Some([a, b, c, d, -(m[4] * a + m[5] * c), -(m[4] * b + m[5] * d)])
+}
+
+/// Bounds of a box after transforming its corners
+fn transformed_bounds(m: &Matrix, [x0, y0, x1, y1]: [f32; 4]) -> [f32; 4] {
+    let corners = [(x0, y0), (x0, y1), (x1, y0), (x1, y1)].map(|(x, y)| transform(m, x, y));
+    let mut bounds = [f32::MAX, f32::MAX, f32::MIN, f32::MIN];
+    for (x, y) in corners {
+        bounds = [bounds[0].min(x), bounds[1].min(y), bounds[2].max(x), bounds[3].max(y)];
+    }
+    bounds
+}
+
+fn matrix_from_operands(operands: &[Object]) -> Option<Matrix> {
+    let values: Vec<f32> = operands.iter().filter_map(|v| v.as_float().ok()).collect();
+    <[f32; 6]>::try_from(values.as_slice()).ok()
+}
+
+/// Glyph widths of a font, in text space units (fractions of the font size)
+struct FontMetrics {
+    two_byte: bool,
+    vertical: bool,
+    widths: HashMap<u32, f32>,
+    default_width: f32,
+}
+
+impl FontMetrics {
+    fn width(&self, code: u32) -> f32 {
+        self.widths.get(&code).copied().unwrap_or(self.default_width)
+    }
+
+    fn load(doc: &Document, font: &Dictionary) -> FontMetrics {
+        let number = |object: &Object| doc.dereference(object).ok().and_then(|(_, v)| v.as_float().ok());
+        let array = |key: &[u8]| -> Vec<Object> {
+            font.get(key)
+                .ok()
+                .and_then(|v| doc.dereference(v).ok())
+                .and_then(|(_, v)| v.as_array().ok().cloned())
+                .unwrap_or_default()
+        };
+
+        match font.get(b"Subtype").and_then(Object::as_name).unwrap_or(b"") {
+            b"Type0" => {
+                let vertical = font.get(b"Encoding").and_then(Object::as_name).is_ok_and(|name| name.ends_with(b"-V"));
+                let descendant = array(b"DescendantFonts")
+                    .first()
+                    .and_then(|d| doc.dereference(d).ok())
+                    .and_then(|(_, d)| d.as_dict().ok().cloned())
+                    .unwrap_or_default();
+                let default_width = descendant.get(b"DW").ok().and_then(number).unwrap_or(1000.0) / 1000.0;
+
+                // W entries: "first [w1 w2 ...]" or "first last w"
+                let mut widths = HashMap::new();
+                let entries = descendant
+                    .get(b"W")
+                    .ok()
+                    .and_then(|v| doc.dereference(v).ok())
+                    .and_then(|(_, v)| v.as_array().ok().cloned())
+                    .unwrap_or_default();
+                let mut i = 0;
+                while i + 1 < entries.len() {
+                    let first = number(&entries[i]).unwrap_or(0.0) as u32;
+                    match doc.dereference(&entries[i + 1]).map(|(_, v)| v) {
+                        Ok(Object::Array(list)) => {
+                            for (offset, width) in list.iter().enumerate() {
+                                widths.insert(first + offset as u32, number(width).unwrap_or(0.0) / 1000.0);
+                            }
+                            i += 2;
+                        }
+                        _ => {
+                            let last = number(&entries[i + 1]).unwrap_or(0.0) as u32;
+                            let width = entries.get(i + 2).and_then(number).unwrap_or(0.0) / 1000.0;
+                            for code in first..=last.min(first + 0xFFFF) {
+                                widths.insert(code, width);
+                            }
+                            i += 3;
+                        }
+                    }
+                }
+                FontMetrics { two_byte: true, vertical, widths, default_width }
+            }
+            subtype => {
+                // Type 3 glyph space is given by its FontMatrix, everything else is 1/1000 em
+                let scale = if subtype == b"Type3" {
+                    array(b"FontMatrix").first().and_then(number).unwrap_or(0.001).abs()
+                } else {
+                    0.001
+                };
+                let first_char = font.get(b"FirstChar").ok().and_then(number).unwrap_or(0.0) as u32;
+                let mut widths: HashMap<u32, f32> = array(b"Widths")
+                    .iter()
+                    .enumerate()
+                    .map(|(i, w)| (first_char + i as u32, number(w).unwrap_or(0.0) * scale))
+                    .collect();
+
+                let base_font = font.get(b"BaseFont").and_then(Object::as_name).unwrap_or(b"");
+                if widths.is_empty() {
+                    // Standard 14 fonts may come without widths
+                    let courier = base_font.windows(7).any(|w| w == b"Courier");
+                    widths = (0..=255u32)
+                        .map(|code| (code, if courier { 0.6 } else { helvetica_text_width(&[code as u8], 1.0) }))
+                        .collect();
+                }
+                let default_width = font
+                    .get(b"FontDescriptor")
+                    .ok()
+                    .and_then(|d| doc.dereference(d).ok())
+                    .and_then(|(_, d)| d.as_dict().ok())
+                    .and_then(|d| d.get(b"MissingWidth").ok())
+                    .and_then(number)
+                    .map_or(0.5, |w| w * scale);
+                FontMetrics { two_byte: false, vertical: false, widths, default_width }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct GraphicsState {
+    ctm: Matrix,
+    font: Option<Rc<FontMetrics>>,
+    font_size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scale: f32,
+    leading: f32,
+    rise: f32,
+}
+
+/// A subpath under construction, with its bounds in user space
+struct Subpath {
+    operations: Vec<Operation>,
+    bounds: Option<[f32; 4]>,
+}
+
+/// A parsed content stream item; inline images keep their raw bytes
+enum ContentItem {
+    Operation(Operation),
+    InlineImage(Vec<u8>),
+}
+
+struct Redactor<'a> {
+    doc: &'a mut Document,
+    areas: &'a [[f32; 4]],
+    counts: RedactionCounts,
+    fonts: HashMap<ObjectId, Rc<FontMetrics>>,
+}
+
+impl Redactor<'_> {
+    /// Redacts a content stream drawn with `ctm` using `resources`. Returns the new content and
+    /// resources, or None when nothing in it was touched.
+    fn redact_stream(
+        &mut self,
+        content: &[u8],
+        mut resources: Dictionary,
+        ctm: Matrix,
+        depth: usize,
+    ) -> Result<Option<(Vec<u8>, Dictionary)>, String> {
+        let items = parse_content(content)?;
+        let mut output: Vec<ContentItem> = Vec::with_capacity(items.len());
+        let mut changed = false;
+        let mut replaced_names: HashSet<Vec<u8>> = HashSet::new();
+
+        let mut state = GraphicsState {
+            ctm,
+            font: None,
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+        };
+        let mut saved: Vec<GraphicsState> = Vec::new();
+        let (mut text_matrix, mut line_matrix) = (IDENTITY, IDENTITY);
+        let mut path: Vec<Subpath> = Vec::new();
+        let mut clip: Vec<Operation> = Vec::new();
+
+        for item in items {
+            let operation = match item {
+                ContentItem::InlineImage(raw) => {
+                    let bounds = transformed_bounds(&state.ctm, [0.0, 0.0, 1.0, 1.0]);
+                    if self.areas.iter().any(|area| overlaps(area, &bounds)) {
+                        self.counts.images += 1;
+                        changed = true;
+                    } else {
+                        output.push(ContentItem::InlineImage(raw));
+                    }
+                    continue;
+                }
+                ContentItem::Operation(operation) => operation,
+            };
+            let operands = &operation.operands;
+            let float = |i: usize| operands.get(i).and_then(|v| v.as_float().ok()).unwrap_or(0.0);
+
+            match operation.operator.as_str() {
+                "q" => saved.push(state.clone()),
+                "Q" => state = saved.pop().unwrap_or(state),
+                "cm" => {
+                    if let Some(m) = matrix_from_operands(operands) {
+                        state.ctm = multiply(&m, &state.ctm);
+                    }
+                }
+
+                // Paths are held back until painted, then filtered subpath by subpath
+                "m" | "re" => {
+                    let points: Vec<(f32, f32)> = if operation.operator == "re" {
+                        let (x, y, w, h) = (float(0), float(1), float(2), float(3));
+                        vec![(x, y), (x + w, y + h)]
+                    } else {
+                        vec![(float(0), float(1))]
+                    };
+                    path.push(Subpath { operations: Vec::new(), bounds: None });
+                    extend_subpath(&mut path, &state.ctm, &points, operation);
+                    continue;
+                }
+                "l" | "c" | "v" | "y" | "h" => {
+                    let points: Vec<(f32, f32)> = operands
+                        .chunks(2)
+                        .filter(|pair| pair.len() == 2)
+                        .map(|pair| (pair[0].as_float().unwrap_or(0.0), pair[1].as_float().unwrap_or(0.0)))
+                        .collect();
+                    extend_subpath(&mut path, &state.ctm, &points, operation);
+                    continue;
+                }
+                "W" | "W*" => {
+                    clip.push(operation);
+                    continue;
+                }
+                "S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" | "n" => {
+                    let subpaths = std::mem::take(&mut path);
+                    let clipping = !clip.is_empty() || operation.operator == "n";
+                    let total = subpaths.len();
+                    // A subpath enclosing an area entirely (a page background, a table cell) shows nothing of it
+                    let kept: Vec<Subpath> = subpaths
+                        .into_iter()
+                        .filter(|subpath| {
+                            clipping
+                                || !subpath.bounds.is_some_and(|bounds| {
+                                    self.areas.iter().any(|area| touches(area, &bounds) && !contains(&bounds, area))
+                                })
+                        })
+                        .collect();
+
+                    if kept.len() < total {
+                        self.counts.paths += total - kept.len();
+                        changed = true;
+                    }
+                    if !kept.is_empty() || total == 0 {
+                        output.extend(kept.into_iter().flat_map(|s| s.operations).map(ContentItem::Operation));
+                        output.extend(clip.drain(..).map(ContentItem::Operation));
+                        output.push(ContentItem::Operation(operation));
+                    }
+                    clip.clear();
+                    continue;
+                }
+
+                "BT" => {
+                    text_matrix = IDENTITY;
+                    line_matrix = IDENTITY;
+                }
+                "Tf" => {
+                    let name = operands.first().and_then(|n| n.as_name().ok()).unwrap_or(b"");
+                    state.font = Some(self.font_metrics(&resources, name));
+                    state.font_size = float(1);
+                }
+                "Tc" => state.char_spacing = float(0),
+                "Tw" => state.word_spacing = float(0),
+                "Tz" => state.horizontal_scale = float(0) / 100.0,
+                "TL" => state.leading = float(0),
+                "Ts" => state.rise = float(0),
+                "Td" | "TD" => {
+                    if operation.operator == "TD" {
+                        state.leading = -float(1);
+                    }
+                    line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, float(0), float(1)], &line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "Tm" => {
+                    if let Some(m) = matrix_from_operands(operands) {
+                        line_matrix = m;
+                        text_matrix = m;
+                    }
+                }
+                "T*" => {
+                    line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "Tj" | "TJ" | "'" | "\"" => {
+                    let mut prefix = Vec::new();
+                    if let ("\"", [word_spacing, char_spacing, _]) = (operation.operator.as_str(), operands.as_slice()) {
+                        state.word_spacing = word_spacing.as_float().unwrap_or(0.0);
+                        state.char_spacing = char_spacing.as_float().unwrap_or(0.0);
+                        prefix.push(Operation::new("Tw", vec![word_spacing.clone()]));
+                        prefix.push(Operation::new("Tc", vec![char_spacing.clone()]));
+                    }
+                    if operation.operator == "'" || operation.operator == "\"" {
+                        line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                        text_matrix = line_matrix;
+                        prefix.push(Operation::new("T*", vec![]));
+                    }
+
+                    let elements: Vec<Object> = match (operation.operator.as_str(), operands.last()) {
+                        ("TJ", Some(Object::Array(items))) => items.clone(),
+                        (_, Some(text @ Object::String(..))) => vec![text.clone()],
+                        _ => Vec::new(),
+                    };
+                    if let Some(shown) = self.show_text(&elements, &state, &mut text_matrix) {
+                        changed = true;
+                        output.extend(prefix.into_iter().map(ContentItem::Operation));
+                        output.push(ContentItem::Operation(Operation::new("TJ", vec![Object::Array(shown)])));
+                        continue;
+                    }
+                }
+
+                "Do" => {
+                    let name = operands.first().and_then(|n| n.as_name().ok()).unwrap_or(b"").to_vec();
+                    match self.redact_xobject(&mut resources, &name, &state.ctm, depth)? {
+                        XObjectRedaction::Unchanged => {}
+                        XObjectRedaction::Removed => {
+                            changed = true;
+                            replaced_names.insert(name);
+                            continue;
+                        }
+                        XObjectRedaction::Replaced(new_name) => {
+                            changed = true;
+                            replaced_names.insert(name);
+                            output.push(ContentItem::Operation(Operation::new("Do", vec![Object::Name(new_name)])));
+                            continue;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            output.push(ContentItem::Operation(operation));
+        }
+        // An unpainted path at the end of the stream draws nothing, but keep it as it was
+        output.extend(path.into_iter().flat_map(|s| s.operations).map(ContentItem::Operation));
+        output.extend(clip.into_iter().map(ContentItem::Operation));
+
+        if !changed {
+            return Ok(None);
+        }
+
+        // Drop replaced XObjects that are no longer drawn, so the originals don't stay reachable
+        let still_used: HashSet<&[u8]> = output
+            .iter()
+            .filter_map(|item| match item {
+                ContentItem::Operation(op) if op.operator == "Do" => op.operands.first().and_then(|n| n.as_name().ok()),
+                _ => None,
+            })
+            .collect();
+        let unused: Vec<Vec<u8>> = replaced_names.into_iter().filter(|name| !still_used.contains(name.as_slice())).collect();
+        if !unused.is_empty() {
+            let mut xobjects = self.category(&resources, b"XObject");
+            for name in &unused {
+                xobjects.remove(name);
+            }
+            resources.set("XObject", Object::Dictionary(xobjects));
+        }
+
+        // Marked content may repeat the text it wraps
+        for item in output.iter_mut() {
+            if let ContentItem::Operation(op) = item {
+                if op.operator == "BDC" || op.operator == "DP" {
+                    if let Some(Object::Dictionary(properties)) = op.operands.get_mut(1) {
+                        for key in TEXT_PROPERTIES {
+                            properties.remove(key);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Some((encode_content(&output)?, resources)))
+    }
+
+    /// Shows text glyph by glyph, advancing `text_matrix`. Returns a replacement TJ array
+    /// when any glyph fell inside an area, with removed glyphs turned into spacing.
+    fn show_text(&mut self, elements: &[Object], state: &GraphicsState, text_matrix: &mut Matrix) -> Option<Vec<Object>> {
+        let font = state.font.clone().unwrap_or_else(|| {
+            Rc::new(FontMetrics { two_byte: false, vertical: false, widths: HashMap::new(), default_width: 0.5 })
+        });
+        let size = state.font_size;
+        let scale = state.horizontal_scale;
+
+        let mut shown = Vec::new();
+        let mut removed = 0;
+        for element in elements {
+            let (bytes, format) = match element {
+                Object::String(bytes, format) => (bytes, format),
+                number => {
+                    let adjustment = number.as_float().unwrap_or(0.0) / 1000.0 * size;
+                    let offset = if font.vertical { (0.0, -adjustment) } else { (-adjustment * scale, 0.0) };
+                    *text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, offset.0, offset.1], text_matrix);
+                    shown.push(number.clone());
+                    continue;
+                }
+            };
+
+            let mut kept = Vec::new();
+            let code_length = if font.two_byte { 2 } else { 1 };
+            for code_bytes in bytes.chunks(code_length) {
+                let code = code_bytes.iter().fold(0u32, |code, &b| code << 8 | b as u32);
+                let width = font.width(code);
+                let word_spacing = if !font.two_byte && code == 32 { state.word_spacing } else { 0.0 };
+
+                let placement = multiply(text_matrix, &state.ctm);
+                let (glyph_box, advance) = if font.vertical {
+                    // Vertical fonts advance downwards by a default 1 em, centred on the origin
+                    let advance = -size + state.char_spacing;
+                    ([-0.5 * size, -size, 0.5 * size, 0.0], (0.0, advance))
+                } else {
+                    let advance = (width * size + state.char_spacing + word_spacing) * scale;
+                    ([0.0, state.rise - 0.2 * size, width * size * scale, state.rise + 0.8 * size], (advance, 0.0))
+                };
+                let bounds = transformed_bounds(&placement, glyph_box);
+                *text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, advance.0, advance.1], text_matrix);
+
+                if !self.areas.iter().any(|area| covers_glyph(area, &bounds, 0.25)) {
+                    kept.extend_from_slice(code_bytes);
+                    continue;
+                }
+
+                // Keep what follows where it was: a TJ number moves by -n/1000 of the font size
+                removed += 1;
+                if !kept.is_empty() {
+                    shown.push(Object::String(std::mem::take(&mut kept), *format));
+                }
+                let unit = if font.vertical { size } else { size * scale };
+                if unit.abs() > f32::EPSILON {
+                    let distance = if font.vertical { advance.1 } else { advance.0 };
+                    shown.push(Object::Real(-distance / unit * 1000.0));
+                }
+            }
+            if !kept.is_empty() {
+                shown.push(Object::String(kept, *format));
+            }
+        }
+
+        if removed == 0 {
+            return None;
+        }
+        self.counts.glyphs += removed;
+        Some(shown)
+    }
+
+    fn font_metrics(&mut self, resources: &Dictionary, name: &[u8]) -> Rc<FontMetrics> {
+        let entry = self.category(resources, b"Font").get(name).ok().cloned();
+        let id = entry.as_ref().and_then(|e| e.as_reference().ok());
+        if let Some(metrics) = id.and_then(|id| self.fonts.get(&id)) {
+            return metrics.clone();
+        }
+
+        let font = entry
+            .and_then(|e| self.doc.dereference(&e).ok().and_then(|(_, f)| f.as_dict().ok().cloned()))
+            .unwrap_or_default();
+        let metrics = Rc::new(FontMetrics::load(self.doc, &font));
+        if let Some(id) = id {
+            self.fonts.insert(id, metrics.clone());
+        }
+        metrics
+    }
+
+    fn category(&self, resources: &Dictionary, category: &[u8]) -> Dictionary {
+        resource_category(self.doc, resources, category)
+    }
+
+    /// Adds an XObject under a fresh name, returning the name
+    fn add_xobject(&self, resources: &mut Dictionary, object_id: ObjectId) -> Vec<u8> {
+        let mut xobjects = self.category(resources, b"XObject");
+        let name = (1..)
+            .map(|n| format!("Rd{}", n).into_bytes())
+            .find(|name| !xobjects.has(name))
+            .unwrap_or_else(|| b"Rd".to_vec());
+        xobjects.set(name.clone(), Object::Reference(object_id));
+        resources.set("XObject", Object::Dictionary(xobjects));
+        name
+    }
+
+    fn redact_xobject(
+        &mut self,
+        resources: &mut Dictionary,
+        name: &[u8],
+        ctm: &Matrix,
+        depth: usize,
+    ) -> Result<XObjectRedaction, String> {
+        let Some(entry) = self.category(resources, b"XObject").get(name).ok().cloned() else {
+            return Ok(XObjectRedaction::Unchanged);
+        };
+        let Ok((_, Object::Stream(stream))) = self.doc.dereference(&entry) else {
+            return Ok(XObjectRedaction::Unchanged);
+        };
+
+        match stream.dict.get(b"Subtype").and_then(Object::as_name).unwrap_or(b"") {
+            b"Image" => {
+                let bounds = transformed_bounds(ctm, [0.0, 0.0, 1.0, 1.0]);
+                if !self.areas.iter().any(|area| overlaps(area, &bounds)) {
+                    return Ok(XObjectRedaction::Unchanged);
+                }
+                self.counts.images += 1;
+                self.counts.image_ids.extend(entry.as_reference());
+
+                // Stencil masks and images that can't be decoded (or lie wholly inside an area) go entirely
+                let is_mask = matches!(stream.dict.get(b"ImageMask"), Ok(Object::Boolean(true)));
+                if is_mask || self.areas.iter().any(|area| contains(area, &bounds)) {
+                    return Ok(XObjectRedaction::Removed);
+                }
+                let stream = stream.clone();
+                match self.blacked_out_image(&stream, ctm) {
+                    Some(image_id) => Ok(XObjectRedaction::Replaced(self.add_xobject(resources, image_id))),
+                    None => Ok(XObjectRedaction::Removed),
+                }
+            }
+            b"Form" => {
+                let form_matrix = stream
+                    .dict
+                    .get(b"Matrix")
+                    .ok()
+                    .and_then(|m| m.as_array().ok())
+                    .and_then(|m| matrix_from_operands(m))
+                    .unwrap_or(IDENTITY);
+                let form_ctm = multiply(&form_matrix, ctm);
+                let bbox = stream.dict.get(b"BBox").ok().and_then(|b| rect_from_object(self.doc, b));
+                let bounds = bbox.map(|b| transformed_bounds(&form_ctm, b));
+                if bounds.is_some_and(|bounds| !self.areas.iter().any(|area| touches(area, &bounds))) {
+                    return Ok(XObjectRedaction::Unchanged);
+                }
+                if depth >= MAX_FORM_DEPTH {
+                    self.counts.paths += 1;
+                    return Ok(XObjectRedaction::Removed);
+                }
+
+                // Forms without their own resources use the ones they are drawn with
+                let form_resources = match stream.dict.get(b"Resources") {
+                    Ok(value) => self
+                        .doc
+                        .dereference(value)
+                        .ok()
+                        .and_then(|(_, v)| v.as_dict().ok().cloned())
+                        .unwrap_or_default(),
+                    Err(_) => resources.clone(),
+                };
+                let content = stream.decompressed_content().unwrap_or_else(|_| stream.content.clone());
+                let mut dict = stream.dict.clone();
+
+                let Some((content, form_resources)) = self.redact_stream(&content, form_resources, form_ctm, depth + 1)? else {
+                    return Ok(XObjectRedaction::Unchanged);
+                };
+                dict.remove(b"Filter");
+                dict.remove(b"DecodeParms");
+                dict.set("Resources", Object::Dictionary(form_resources));
+                let mut form = Stream::new(dict, content);
+                let _ = form.compress();
+                let form_id = self.doc.add_object(form);
+                Ok(XObjectRedaction::Replaced(self.add_xobject(resources, form_id)))
+            }
+            _ => Ok(XObjectRedaction::Unchanged),
+        }
+    }
+
+    /// A copy of an image with everything under the areas painted black
+    fn blacked_out_image(&mut self, stream: &Stream, ctm: &Matrix) -> Option<ObjectId> {
+        let decoded = decode_image_xobject(self.doc, stream).ok()?;
+        let mut pixels = image::load_from_memory(&decoded.data).ok()?.to_rgba8();
+        let (width, height) = (pixels.width() as f32, pixels.height() as f32);
+        let to_unit_square = invert(ctm)?;
+
+        for area in self.areas {
+            // Image row 0 is the top of the unit square
+            let [u0, v0, u1, v1] = transformed_bounds(&to_unit_square, *area);
+            let left = (u0 * width).floor().clamp(0.0, width) as u32;
+            let right = (u1 * width).ceil().clamp(0.0, width) as u32;
+            let top = ((1.0 - v1) * height).floor().clamp(0.0, height) as u32;
+            let bottom = ((1.0 - v0) * height).ceil().clamp(0.0, height) as u32;
+            for y in top..bottom {
+                for x in left..right {
+                    pixels.put_pixel(x, y, image::Rgba([0, 0, 0, 255]));
+                }
+            }
+        }
+
+        embed_image(self.doc, image::DynamicImage::ImageRgba8(pixels)).ok().map(|image| image.id)
+    }
+}
+
+enum XObjectRedaction {
+    Unchanged,
+    Removed,
+    Replaced(Vec<u8>),
+}
+
+/// Adds a path construction operator to the current subpath, growing its bounds
+fn extend_subpath(path: &mut Vec<Subpath>, ctm: &Matrix, points: &[(f32, f32)], operation: Operation) {
+    if path.is_empty() {
+        path.push(Subpath { operations: Vec::new(), bounds: None });
+    }
+    let subpath = path.last_mut().expect("path has a subpath");
+    for &(x, y) in points {
+        let (x, y) = transform(ctm, x, y);
+        subpath.bounds = Some(match subpath.bounds {
+            Some(b) => [b[0].min(x), b[1].min(y), b[2].max(x), b[3].max(y)],
+            None => [x, y, x, y],
+        });
+    }
+    subpath.operations.push(operation);
+}
+
+fn encode_content(items: &[ContentItem]) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    let mut operations = Vec::new();
+    let flush = |operations: &mut Vec<Operation>, buffer: &mut Vec<u8>| -> Result<(), String> {
+        if !operations.is_empty() {
+            let encoded = Content { operations: std::mem::take(operations) }
+                .encode()
+                .map_err(|e| format!("Failed to encode page content: {}", e))?;
+            buffer.extend(encoded);
+            buffer.push(b'\n');
+        }
+        Ok(())
+    };
+
+    for item in items {
+        match item {
+            ContentItem::Operation(operation) => operations.push(operation.clone()),
+            ContentItem::InlineImage(raw) => {
+                flush(&mut operations, &mut buffer)?;
+                buffer.extend_from_slice(raw);
+                buffer.push(b'\n');
+            }
+        }
+    }
+    flush(&mut operations, &mut buffer)?;
+    Ok(buffer)
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, 0 | 9 | 10 | 12 | 13 | 32)
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    b"()<>[]{}/%".contains(&byte)
+}
+
+enum Token {
+    Object(Object),
+    Keyword(Vec<u8>),
+    ArrayStart,
+    ArrayEnd,
+    DictStart,
+    DictEnd,
+}
+
+/// An array or dictionary being read; a dictionary holds the key awaiting its value
+enum Container {
+    Array(Vec<Object>),
+    Dictionary(Dictionary, Option<Vec<u8>>),
+}
+
+/// Parses a content stream completely. lopdf's own parser stops quietly at anything it
+/// doesn't understand (inline images among them), which would lose content after it.
+fn parse_content(data: &[u8]) -> Result<Vec<ContentItem>, String> {
+    let mut lexer = Lexer { data, position: 0 };
+    let mut items = Vec::new();
+    let mut operands = Vec::new();
+
+    loop {
+        lexer.skip_whitespace();
+        let start = lexer.position;
+        match lexer.next_token()? {
+            None => break,
+            Some(Token::Object(object)) => operands.push(object),
+            Some(Token::Keyword(keyword)) if keyword == b"BI" => {
+                lexer.skip_inline_image()?;
+                items.push(ContentItem::InlineImage(data[start..lexer.position].to_vec()));
+                operands.clear();
+            }
+            Some(Token::Keyword(keyword)) => items.push(ContentItem::Operation(Operation {
+                operator: String::from_utf8_lossy(&keyword).to_string(),
+                operands: std::mem::take(&mut operands),
+            })),
+            Some(_) => return Err("Malformed page content: unbalanced brackets".to_string()),
+        }
+    }
+    Ok(items)
+}
+
+struct Lexer<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Lexer<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(byte) = self.peek() {
+            if is_whitespace(byte) {
+                self.position += 1;
+            } else if byte == b'%' {
+                while self.peek().is_some_and(|b| b != b'\n' && b != b'\r') {
+                    self.position += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// The next token, with arrays and dictionaries read whole into one object. Nesting is
+    /// tracked on a stack of its own, so malformed content can't exhaust the call stack.
+    fn next_token(&mut self) -> Result<Option<Token>, String> {
+        let mut open: Vec<Container> = Vec::new();
+        loop {
+            let object = match self.next_simple_token()? {
+                None if open.is_empty() => return Ok(None),
+                None => return Err("Malformed page content: unterminated array or dictionary".to_string()),
+                Some(Token::ArrayStart | Token::DictStart) if open.len() >= MAX_NESTING => {
+                    return Err("Malformed page content: nested too deeply".to_string());
+                }
+                Some(Token::ArrayStart) => {
+                    open.push(Container::Array(Vec::new()));
+                    continue;
+                }
+                Some(Token::DictStart) => {
+                    open.push(Container::Dictionary(Dictionary::new(), None));
+                    continue;
+                }
+                Some(Token::ArrayEnd) => match open.pop() {
+                    None => return Ok(Some(Token::ArrayEnd)),
+                    Some(Container::Array(items)) => Object::Array(items),
+                    Some(Container::Dictionary(..)) => return Err("Malformed page content: bad dictionary".to_string()),
+                },
+                Some(Token::DictEnd) => match open.pop() {
+                    None => return Ok(Some(Token::DictEnd)),
+                    Some(Container::Dictionary(dict, None)) => Object::Dictionary(dict),
+                    Some(Container::Dictionary(_, Some(_))) => {
+                        return Err("Malformed page content: missing value".to_string());
+                    }
+                    Some(Container::Array(_)) => return Err("Malformed page content: bad array".to_string()),
+                },
+                Some(Token::Keyword(keyword)) if open.is_empty() => return Ok(Some(Token::Keyword(keyword))),
+                Some(Token::Keyword(keyword)) => {
+                    return Err(format!("Malformed page content: unexpected '{}'", String::from_utf8_lossy(&keyword)));
+                }
+                Some(Token::Object(object)) => object,
+            };
+
+            match open.last_mut() {
+                None => return Ok(Some(Token::Object(object))),
+                Some(Container::Array(items)) => items.push(object),
+                Some(Container::Dictionary(dict, key)) => match (key.take(), object) {
+                    (Some(key), value) => dict.set(key, value),
+                    (None, Object::Name(name)) => *key = Some(name),
+                    (None, _) => return Err("Malformed page content: bad dictionary".to_string()),
+                },
+            }
+        }
+    }
+
+    /// The next token as written: brackets come back as separate tokens
+    fn next_simple_token(&mut self) -> Result<Option<Token>, String> {
+        loop {
+            self.skip_whitespace();
+            let Some(byte) = self.peek() else {
+                return Ok(None);
+            };
+
+            let token = match byte {
+                b'(' => Token::Object(Object::String(self.literal_string()?, StringFormat::Literal)),
+                b'<' if self.data.get(self.position + 1) == Some(&b'<') => {
+                    self.position += 2;
+                    Token::DictStart
+                }
+                b'<' => Token::Object(Object::String(self.hex_string()?, StringFormat::Hexadecimal)),
+                b'>' if self.data.get(self.position + 1) == Some(&b'>') => {
+                    self.position += 2;
+                    Token::DictEnd
+                }
+                b'[' => {
+                    self.position += 1;
+                    Token::ArrayStart
+                }
+                b']' => {
+                    self.position += 1;
+                    Token::ArrayEnd
+                }
+                b'/' => {
+                    self.position += 1;
+                    Token::Object(Object::Name(self.name()))
+                }
+                b'{' | b'}' | b')' | b'>' => {
+                    // Stray delimiters carry no meaning in content streams
+                    self.position += 1;
+                    continue;
+                }
+                b'0'..=b'9' | b'+' | b'-' | b'.' => Token::Object(self.number()),
+                _ => {
+                    let start = self.position;
+                    while self.peek().is_some_and(|b| !is_whitespace(b) && !is_delimiter(b)) {
+                        self.position += 1;
+                    }
+                    let keyword = self.data[start..self.position].to_vec();
+                    match keyword.as_slice() {
+                        b"true" => Token::Object(Object::Boolean(true)),
+                        b"false" => Token::Object(Object::Boolean(false)),
+                        b"null" => Token::Object(Object::Null),
+                        _ => Token::Keyword(keyword),
+                    }
+                }
+            };
+            return Ok(Some(token));
+        }
+    }
+
+    fn number(&mut self) -> Object {
+        let start = self.position;
+        while self.peek().is_some_and(|b| b.is_ascii_digit() || b"+-.".contains(&b)) {
+            self.position += 1;
+        }
+        let text = String::from_utf8_lossy(&self.data[start..self.position]).to_string();
+        if text.contains('.') {
+            Object::Real(text.parse().unwrap_or(0.0))
+        } else {
+            text.parse().map(Object::Integer).unwrap_or(Object::Integer(0))
+        }
+    }
+
+    fn name(&mut self) -> Vec<u8> {
+        let mut name = Vec::new();
+        while let Some(byte) = self.peek().filter(|&b| !is_whitespace(b) && !is_delimiter(b)) {
+            self.position += 1;
+            let escaped = self
+                .data
+                .get(self.position..self.position + 2)
+                .filter(|_| byte == b'#')
+                .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+            match escaped {
+                Some(decoded) => {
+                    name.push(decoded);
+                    self.position += 2;
+                }
+                None => name.push(byte),
+            }
+        }
+        name
+    }
+
+    fn literal_string(&mut self) -> Result<Vec<u8>, String> {
+        self.position += 1;
+        let mut text = Vec::new();
+        let mut depth = 1;
+        loop {
+            let byte = self.peek().ok_or("Malformed page content: unterminated string")?;
+            self.position += 1;
+            match byte {
+                b'(' => {
+                    depth += 1;
+                    text.push(byte);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(text);
+                    }
+                    text.push(byte);
+                }
+                b'\\' => {
+                    let escaped = self.peek().ok_or("Malformed page content: unterminated string")?;
+                    self.position += 1;
+                    match escaped {
+                        b'n' => text.push(b'\n'),
+                        b'r' => text.push(b'\r'),
+                        b't' => text.push(b'\t'),
+                        b'b' => text.push(8),
+                        b'f' => text.push(12),
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(digit @ b'0'..=b'7') => {
+                                        value = value * 8 + (digit - b'0') as u32;
+                                        self.position += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            text.push(value as u8);
+                        }
+                        // A backslash at the end of a line continues the string
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') {
+                                self.position += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        other => text.push(other),
+                    }
+                }
+                _ => text.push(byte),
+            }
+        }
+    }
+
+    fn hex_string(&mut self) -> Result<Vec<u8>, String> {
+        self.position += 1;
+        let mut digits = Vec::new();
+        loop {
+            let byte = self.peek().ok_or("Malformed page content: unterminated hex string")?;
+            self.position += 1;
+            match byte {
+                b'>' => break,
+                b if b.is_ascii_hexdigit() => digits.push(b),
+                _ => {}
+            }
+        }
+        if digits.len() % 2 == 1 {
+            digits.push(b'0');
+        }
+        Ok(digits
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap_or("00"), 16).unwrap_or(0))
+            .collect())
+    }
+
+    /// Moves past an inline image's parameters, `ID`, its data and `EI`
+    fn skip_inline_image(&mut self) -> Result<(), String> {
+        loop {
+            match self.next_token()? {
+                Some(Token::Keyword(keyword)) if keyword == b"ID" => break,
+                Some(Token::Object(_)) | Some(Token::Keyword(_)) => {}
+                _ => return Err("Malformed page content: unterminated inline image".to_string()),
+            }
+        }
+        // One whitespace byte separates ID from the data, which runs to a standalone EI
+        self.position += 1;
+        let data = &self.data[self.position.min(self.data.len())..];
+        let end = (0..data.len().saturating_sub(2))
+            .find(|&i| {
+                is_whitespace(data[i])
+                    && &data[i + 1..i + 3] == b"EI"
+                    && !matches!(data.get(i + 3), Some(&b) if !is_whitespace(b))
+            })
+            .ok_or("Malformed page content: unterminated inline image")?;
+        self.position += end + 3;
+        Ok(())
+    }
+}
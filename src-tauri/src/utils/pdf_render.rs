@@ -0,0 +1,121 @@
+use image::DynamicImage;
+use std::path::Path;
+
+use crate::utils::command_executor::{validate_output, CommandExecutor, GhostscriptExecutor};
+use crate::utils::path_utils::get_temp_path;
+
+/// Raster formats Ghostscript can render pages to
+#[derive(Debug, Clone, Copy)]
+pub enum RenderFormat {
+    Png,
+    Jpeg { quality: u8 },
+}
+
+impl RenderFormat {
+    pub fn from_name(name: &str, quality: Option<u8>) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "png" => Ok(RenderFormat::Png),
+            "jpg" | "jpeg" => Ok(RenderFormat::Jpeg { quality: quality.unwrap_or(90).clamp(1, 100) }),
+            _ => Err(format!("Unsupported page image format: {}", name)),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RenderFormat::Png => "png",
+            RenderFormat::Jpeg { .. } => "jpg",
+        }
+    }
+
+    fn device_args(&self) -> Vec<String> {
+        match self {
+            RenderFormat::Png => vec!["-sDEVICE=png16m".to_string()],
+            RenderFormat::Jpeg { quality } => vec!["-sDEVICE=jpeg".to_string(), format!("-dJPEGQ={}", quality)],
+        }
+    }
+}
+
+/// Renders the given 1-based pages to `output_dir` as `{name}_page{N}.{ext}`, returning the paths in order
+pub fn render_pages(
+    input_path: &str,
+    page_numbers: &[u32],
+    dpi: u32,
+    format: RenderFormat,
+    output_dir: &Path,
+    name: &str,
+) -> Result<Vec<String>, String> {
+    if page_numbers.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !(1..=2400).contains(&dpi) {
+        return Err(format!("DPI must be between 1 and 2400, got {}", dpi));
+    }
+    // Each page maps to one file name, so a repeated page would overwrite its earlier image
+    let mut seen = std::collections::HashSet::new();
+    if let Some(page) = page_numbers.iter().find(|&&page| !seen.insert(page)) {
+        return Err(format!("Page {} is listed more than once", page));
+    }
+
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create output directory: {}", e))?;
+
+    // Ghostscript numbers output files sequentially, so render under a scratch prefix and rename
+    let scratch_prefix = format!(".{}_render_{}", name, std::process::id());
+    let pattern = output_dir.join(format!("{}_%d.{}", scratch_prefix, format.extension()));
+    let page_list = page_numbers.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(",");
+
+    let mut args = format.device_args();
+    args.extend([
+        format!("-r{}", dpi),
+        "-dTextAlphaBits=4".to_string(),
+        "-dGraphicsAlphaBits=4".to_string(),
+        "-dUseCropBox".to_string(),
+        format!("-sPageList={}", page_list),
+        "-dNOPAUSE".to_string(),
+        "-dQUIET".to_string(),
+        "-dBATCH".to_string(),
+        "-dSAFER".to_string(),
+        format!("-sOutputFile={}", pattern.to_string_lossy()),
+        input_path.to_string(),
+    ]);
+
+    let output = GhostscriptExecutor.execute_strings(args)?;
+    validate_output(&output)?;
+
+    let width = page_numbers.iter().max().copied().unwrap_or(1).to_string().len();
+    let mut paths = Vec::with_capacity(page_numbers.len());
+    for (index, page) in page_numbers.iter().enumerate() {
+        let rendered = output_dir.join(format!("{}_{}.{}", scratch_prefix, index + 1, format.extension()));
+        let target = output_dir.join(format!("{}_page{:0width$}.{}", name, page, format.extension(), width = width));
+        std::fs::rename(&rendered, &target)
+            .map_err(|e| format!("Failed to write page {} image: {}", page, e))?;
+        paths.push(target.to_string_lossy().to_string());
+    }
+
+    Ok(paths)
+}
+
+/// Renders a single page into memory
+pub fn render_page_image(input_path: &str, page_number: u32, dpi: u32) -> Result<DynamicImage, String> {
+    let scratch_dir = Path::new(&get_temp_path("pdf_page", "d")).to_path_buf();
+    let result = render_pages(input_path, &[page_number], dpi, RenderFormat::Png, &scratch_dir, "page")
+        .and_then(|paths| image::open(&paths[0]).map_err(|e| format!("Failed to read rendered page: {}", e)));
+
+    let _ = std::fs::remove_dir_all(&scratch_dir);
+    result
+}
+
+/// Pixel bounds (left, top, right, bottom, inclusive) of everything that isn't near-white on a rendered page
+pub fn content_bounds(image: &DynamicImage) -> Option<(u32, u32, u32, u32)> {
+    let gray = image.to_luma8();
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, pixel) in gray.enumerate_pixels() {
+        if pixel[0] < 240 {
+            bounds = Some(match bounds {
+                Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+    }
+    bounds
+}
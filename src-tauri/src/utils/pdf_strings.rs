@@ -0,0 +1,98 @@
+/// PDFDocEncoding characters for bytes 0x18-0x1F
+const PDF_DOC_ENCODING_18: [char; 8] = ['\u{02D8}', '\u{02C7}', '\u{02C6}', '\u{02D9}', '\u{02DD}', '\u{02DB}', '\u{02DA}', '\u{02DC}'];
+
+/// PDFDocEncoding characters for bytes 0x80-0xA0 (0x9F is undefined)
+const PDF_DOC_ENCODING_80: [char; 33] = [
+    '\u{2022}', '\u{2020}', '\u{2021}', '\u{2026}', '\u{2014}', '\u{2013}', '\u{0192}', '\u{2044}',
+    '\u{2039}', '\u{203A}', '\u{2212}', '\u{2030}', '\u{201E}', '\u{201C}', '\u{201D}', '\u{2018}',
+    '\u{2019}', '\u{201A}', '\u{2122}', '\u{FB01}', '\u{FB02}', '\u{0141}', '\u{0152}', '\u{0160}',
+    '\u{0178}', '\u{017D}', '\u{0131}', '\u{0142}', '\u{0153}', '\u{0161}', '\u{017E}', '\u{FFFD}',
+    '\u{20AC}',
+];
+
+fn pdf_doc_encoding_char(byte: u8) -> char {
+    match byte {
+        0x18..=0x1F => PDF_DOC_ENCODING_18[(byte - 0x18) as usize],
+        0x80..=0xA0 => PDF_DOC_ENCODING_80[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+fn pdf_doc_encoding_byte(c: char) -> Option<u8> {
+    match c as u32 {
+        0x09 | 0x0A | 0x0D | 0x20..=0x7E | 0xA1..=0xAC | 0xAE..=0xFF => Some(c as u8),
+        _ => PDF_DOC_ENCODING_18
+            .iter()
+            .position(|&m| m == c)
+            .map(|i| 0x18 + i as u8)
+            .or_else(|| PDF_DOC_ENCODING_80.iter().position(|&m| m == c && m != '\u{FFFD}').map(|i| 0x80 + i as u8)),
+    }
+}
+
+/// Encodes text as a PDF text string: PDFDocEncoding when it can represent the text, UTF-16BE with BOM otherwise
+pub fn encode_text_string(text: &str) -> Vec<u8> {
+    if let Some(bytes) = text.chars().map(pdf_doc_encoding_byte).collect::<Option<Vec<u8>>>() {
+        return bytes;
+    }
+
+    let mut bytes = vec![0xFE, 0xFF];
+    for unit in text.encode_utf16() {
+        bytes.extend_from_slice(&unit.to_be_bytes());
+    }
+    bytes
+}
+
+/// Decodes a PDF text string (UTF-16BE with BOM, UTF-8 with BOM, or PDFDocEncoding)
+pub fn decode_text_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8_lossy(utf8).to_string();
+    }
+    bytes.iter().map(|&b| pdf_doc_encoding_char(b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_and_latin1_use_pdf_doc_encoding() {
+        assert_eq!(encode_text_string("Report"), b"Report");
+        assert_eq!(encode_text_string("Café"), b"Caf\xE9");
+        assert_eq!(decode_text_string(b"Caf\xE9"), "Café");
+    }
+
+    #[test]
+    fn pdf_doc_encoding_specials_round_trip() {
+        let text = "\u{2022} \u{2014} \u{20AC} \u{02DC} \u{FB01}";
+        let encoded = encode_text_string(text);
+        assert_eq!(encoded, b"\x80 \x84 \xA0 \x1F \x93");
+        assert_eq!(decode_text_string(&encoded), text);
+    }
+
+    #[test]
+    fn other_text_uses_utf16_with_bom() {
+        let text = "Привет 😀";
+        let encoded = encode_text_string(text);
+        assert_eq!(&encoded[..4], &[0xFE, 0xFF, 0x04, 0x1F]);
+        assert_eq!(decode_text_string(&encoded), text);
+    }
+
+    #[test]
+    fn utf8_with_bom_is_decoded() {
+        assert_eq!(decode_text_string("\u{FEFF}Ünïcode".as_bytes()), "Ünïcode");
+    }
+
+    #[test]
+    fn undefined_pdf_doc_encoding_byte_is_not_produced() {
+        // 0x9F is undefined, so U+FFFD can't be written as PDFDocEncoding
+        assert_eq!(decode_text_string(b"\x9F"), "\u{FFFD}");
+        assert_eq!(&encode_text_string("\u{FFFD}")[..2], &[0xFE, 0xFF]);
+    }
+}
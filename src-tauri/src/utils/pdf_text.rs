@@ -0,0 +1,571 @@
+use pdf_extract::{MediaBox, OutputDev, OutputError, Transform};
+use serde::Serialize;
+
+/// Axis-aligned rectangle in PDF user space (points, origin bottom-left)
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BoundingBox {
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+}
+
+impl BoundingBox {
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            x0: self.x0.min(other.x0),
+            y0: self.y0.min(other.y0),
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TextWord {
+    pub text: String,
+    pub bbox: BoundingBox,
+    /// One box per character of `text`, for locating matches inside the word
+    #[serde(skip)]
+    pub char_boxes: Vec<BoundingBox>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TextLine {
+    pub text: String,
+    pub bbox: BoundingBox,
+    pub font_size: f32,
+    pub words: Vec<TextWord>,
+}
+
+/// Consecutive lines forming a paragraph or column fragment
+#[derive(Debug, Clone, Serialize)]
+pub struct TextBlock {
+    pub bbox: BoundingBox,
+    pub lines: Vec<TextLine>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PageText {
+    pub page_number: u32,
+    pub width: f32,
+    pub height: f32,
+    pub blocks: Vec<TextBlock>,
+}
+
+impl PageText {
+    /// Lines separated by newlines, blocks by blank lines
+    pub fn plain_text(&self) -> String {
+        self.blocks
+            .iter()
+            .map(|block| block.lines.iter().map(|line| line.text.as_str()).collect::<Vec<_>>().join("\n"))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// Extracts positioned text for the given pages (all when `None`).
+///
+/// Without `layout`, lines follow the order of the content stream. With `layout`, the page
+/// is segmented into columns and paragraphs by whitespace (recursive XY-cut) and read
+/// column by column, top to bottom.
+pub fn extract_page_text(bytes: &[u8], page_numbers: Option<&[u32]>, layout: bool) -> Result<Vec<PageText>, String> {
+    let doc = pdf_extract::Document::load_mem(bytes).map_err(|e| format!("Failed to load PDF: {}", e))?;
+
+    let mut collector = GlyphCollector::default();
+    match page_numbers {
+        Some(pages) => {
+            for &page in pages {
+                pdf_extract::output_doc_page(&doc, &mut collector, page)
+                    .map_err(|e| format!("Failed to extract text from page {}: {}", page, e))?;
+            }
+        }
+        None => pdf_extract::output_doc(&doc, &mut collector).map_err(|e| format!("Failed to extract text: {}", e))?,
+    }
+
+    Ok(collector.pages.into_iter().map(|page| page.into_page_text(layout)).collect())
+}
+
+/// A search hit on one page
+pub struct TextMatch {
+    pub page_number: u32,
+    pub text: String,
+    pub context: String,
+    /// One rectangle per line the match spans
+    pub rects: Vec<BoundingBox>,
+}
+
+/// Finds all matches of `pattern` in the pages' text, in reading order.
+/// Lines are joined with spaces, so phrases broken across lines are still found.
+pub fn search_pages(pages: &[PageText], pattern: &regex::Regex, context_chars: usize) -> Vec<TextMatch> {
+    let mut matches = Vec::new();
+
+    for page in pages {
+        // Page text with the box and line of every character (None for inserted separators)
+        let mut text = String::new();
+        let mut boxes: Vec<Option<(BoundingBox, usize)>> = Vec::new();
+        let lines = page.blocks.iter().flat_map(|block| block.lines.iter());
+        for (line_index, line) in lines.enumerate() {
+            for word in &line.words {
+                if !text.is_empty() {
+                    text.push(' ');
+                    boxes.push(None);
+                }
+                text.push_str(&word.text);
+                boxes.extend(word.char_boxes.iter().map(|&b| Some((b, line_index))));
+            }
+        }
+
+        let char_starts: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        let char_index = |byte: usize| char_starts.partition_point(|&start| start < byte);
+
+        for found in pattern.find_iter(&text) {
+            if found.as_str().trim().is_empty() {
+                continue;
+            }
+            let (start, end) = (char_index(found.start()), char_index(found.end()));
+
+            let mut rects: Vec<(usize, BoundingBox)> = Vec::new();
+            for &(bbox, line) in boxes[start..end.min(boxes.len())].iter().flatten() {
+                match rects.last_mut() {
+                    Some((last_line, rect)) if *last_line == line => *rect = rect.union(&bbox),
+                    _ => rects.push((line, bbox)),
+                }
+            }
+
+            let context_start = char_starts[start.saturating_sub(context_chars)];
+            let context_end = char_starts.get(end + context_chars).copied().unwrap_or(text.len());
+            matches.push(TextMatch {
+                page_number: page.page_number,
+                text: found.as_str().to_string(),
+                context: text[context_start..context_end].to_string(),
+                rects: rects.into_iter().map(|(_, rect)| rect).collect(),
+            });
+        }
+    }
+
+    matches
+}
+
+/// Renders pages as Markdown: a heading per page, large short blocks as headings, other blocks reflowed into paragraphs
+pub fn render_markdown(pages: &[PageText]) -> String {
+    let mut out = String::new();
+    for page in pages {
+        out.push_str(&format!("## Page {}\n\n", page.page_number));
+        let body_size = median(page.blocks.iter().flat_map(|b| b.lines.iter().map(|l| l.font_size)).collect());
+
+        for block in &page.blocks {
+            let text = reflow(&block.lines);
+            if text.is_empty() {
+                continue;
+            }
+            let block_size = block.lines.iter().map(|l| l.font_size).fold(0.0, f32::max);
+            if block.lines.len() <= 2 && block_size >= body_size * 1.25 {
+                out.push_str(&format!("### {}\n\n", text));
+            } else {
+                out.push_str(&format!("{}\n\n", text));
+            }
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// Joins wrapped lines into one paragraph, undoing end-of-line hyphenation
+fn reflow(lines: &[TextLine]) -> String {
+    let mut text = String::new();
+    for line in lines {
+        let line_text = line.text.trim();
+        if line_text.is_empty() {
+            continue;
+        }
+        let continues_word = text.ends_with('-') && line_text.starts_with(|c: char| c.is_lowercase());
+        if continues_word {
+            text.pop();
+        } else if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(line_text);
+    }
+    text
+}
+
+fn median(mut values: Vec<f32>) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    values[values.len() / 2]
+}
+
+/// One rendered character in user space
+struct Glyph {
+    text: String,
+    /// Baseline origin
+    x: f32,
+    y: f32,
+    /// End of the advance along the baseline
+    end_x: f32,
+    size: f32,
+    bbox: BoundingBox,
+}
+
+impl Glyph {
+    /// Splits the glyph box evenly when one glyph decodes to several characters (ligatures)
+    fn char_boxes(&self) -> Vec<BoundingBox> {
+        let count = self.text.chars().count().max(1);
+        let step = (self.bbox.x1 - self.bbox.x0) / count as f32;
+        (0..count)
+            .map(|i| BoundingBox {
+                x0: self.bbox.x0 + step * i as f32,
+                x1: self.bbox.x0 + step * (i + 1) as f32,
+                ..self.bbox
+            })
+            .collect()
+    }
+}
+
+struct CollectedPage {
+    page_number: u32,
+    width: f32,
+    height: f32,
+    glyphs: Vec<Glyph>,
+}
+
+/// Receives characters from pdf-extract's content stream interpreter
+#[derive(Default)]
+struct GlyphCollector {
+    pages: Vec<CollectedPage>,
+}
+
+impl OutputDev for GlyphCollector {
+    fn begin_page(&mut self, page_num: u32, media_box: &MediaBox, _: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
+        self.pages.push(CollectedPage {
+            page_number: page_num,
+            width: (media_box.urx - media_box.llx).abs() as f32,
+            height: (media_box.ury - media_box.lly).abs() as f32,
+            glyphs: Vec::new(),
+        });
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn output_character(&mut self, trm: &Transform, width: f64, _spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+        let Some(page) = self.pages.last_mut() else {
+            return Ok(());
+        };
+
+        // The text rendering matrix excludes the font size: its first row is the advance
+        // direction, its second the glyph's up direction
+        let advance = ((trm.m11 * width * font_size) as f32, (trm.m12 * width * font_size) as f32);
+        let up = ((trm.m21 * font_size) as f32, (trm.m22 * font_size) as f32);
+        let (x, y) = (trm.m31 as f32, trm.m32 as f32);
+        let size = up.0.hypot(up.1);
+
+        // Glyph box from a typical descender (-0.2em) to ascender (0.8em)
+        let corners = [-0.2f32, 0.8].iter().flat_map(|&v| {
+            [0.0f32, 1.0].map(|u| (x + u * advance.0 + v * up.0, y + u * advance.1 + v * up.1))
+        });
+        let mut bbox = BoundingBox { x0: f32::MAX, y0: f32::MAX, x1: f32::MIN, y1: f32::MIN };
+        for (cx, cy) in corners {
+            bbox = bbox.union(&BoundingBox { x0: cx, y0: cy, x1: cx, y1: cy });
+        }
+
+        page.glyphs.push(Glyph {
+            text: char.to_string(),
+            x,
+            y,
+            end_x: x + advance.0.hypot(advance.1),
+            size,
+            bbox,
+        });
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+}
+
+/// Words on one baseline with no wide gap between them
+struct Segment {
+    words: Vec<TextWord>,
+    bbox: BoundingBox,
+    baseline: f32,
+    size: f32,
+}
+
+impl CollectedPage {
+    fn into_page_text(self, layout: bool) -> PageText {
+        let body_size = median(self.glyphs.iter().map(|g| g.size).filter(|&s| s > 0.0).collect()).max(1.0);
+        let segments = build_segments(&self.glyphs);
+
+        let blocks = if layout {
+            let mut leaves = Vec::new();
+            xy_cut(segments, body_size, &mut leaves);
+            leaves.into_iter().filter_map(|leaf| make_block(sort_into_lines(leaf))).collect()
+        } else {
+            stream_order_blocks(segments)
+        };
+
+        PageText {
+            page_number: self.page_number,
+            width: self.width,
+            height: self.height,
+            blocks,
+        }
+    }
+}
+
+/// Groups glyphs (in stream order) into words and words into segments
+fn build_segments(glyphs: &[Glyph]) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut word: Option<TextWord> = None;
+    let mut previous: Option<&Glyph> = None;
+
+    for glyph in glyphs {
+        let size = glyph.size.max(1.0);
+        let (new_segment, new_word) = match previous {
+            None => (true, true),
+            Some(prev) => {
+                let off_baseline = (glyph.y - prev.y).abs() > size * 0.5;
+                let moved_back = glyph.x < prev.end_x - size * 0.5;
+                let gap = glyph.x - prev.end_x;
+                let segment_break = off_baseline || moved_back || gap > size * 1.5;
+                (segment_break, segment_break || gap > size * 0.15)
+            }
+        };
+
+        if new_word || glyph.text.trim().is_empty() {
+            push_word(&mut segments, word.take());
+        }
+        if new_segment {
+            segments.push(Segment { words: Vec::new(), bbox: glyph.bbox, baseline: glyph.y, size: glyph.size });
+        }
+
+        if !glyph.text.trim().is_empty() {
+            let current = word.get_or_insert_with(|| TextWord {
+                text: String::new(),
+                bbox: glyph.bbox,
+                char_boxes: Vec::new(),
+            });
+            current.text.push_str(&glyph.text);
+            current.bbox = current.bbox.union(&glyph.bbox);
+            current.char_boxes.extend(glyph.char_boxes());
+            if let Some(segment) = segments.last_mut() {
+                segment.size = segment.size.max(glyph.size);
+            }
+        }
+        previous = Some(glyph);
+    }
+    push_word(&mut segments, word);
+
+    segments.retain(|segment| !segment.words.is_empty());
+    for segment in &mut segments {
+        segment.bbox = segment.words.iter().skip(1).fold(segment.words[0].bbox, |b, w| b.union(&w.bbox));
+    }
+    segments
+}
+
+/// Adds a finished word to the last segment (the one it was read in)
+fn push_word(segments: &mut [Segment], word: Option<TextWord>) {
+    if let (Some(word), Some(segment)) = (word, segments.last_mut()) {
+        segment.words.push(word);
+    }
+}
+
+/// Recursive XY-cut: split at vertical whitespace (columns) first, then at wide horizontal gaps (paragraphs)
+fn xy_cut(segments: Vec<Segment>, body_size: f32, leaves: &mut Vec<Vec<Segment>>) {
+    if segments.len() <= 1 {
+        if !segments.is_empty() {
+            leaves.push(segments);
+        }
+        return;
+    }
+
+    let columns = split_by_gaps(segments, body_size, true);
+    if columns.len() > 1 {
+        for column in columns {
+            xy_cut(column, body_size, leaves);
+        }
+        return;
+    }
+
+    let bands = split_by_gaps(columns.into_iter().flatten().collect(), body_size * 0.5, false);
+    if bands.len() > 1 {
+        for band in bands {
+            xy_cut(band, body_size, leaves);
+        }
+        return;
+    }
+
+    leaves.extend(bands);
+}
+
+/// Splits segments wherever a band of whitespace wider than `min_gap` crosses them all.
+/// Vertical bands give columns left to right, horizontal ones rows top to bottom.
+fn split_by_gaps(mut segments: Vec<Segment>, min_gap: f32, vertical: bool) -> Vec<Vec<Segment>> {
+    // Project every segment onto the axis, oriented so that reading order is ascending
+    let span = |s: &Segment| if vertical { (s.bbox.x0, s.bbox.x1) } else { (-s.bbox.y1, -s.bbox.y0) };
+    segments.sort_by(|a, b| span(a).0.total_cmp(&span(b).0));
+
+    let mut groups: Vec<Vec<Segment>> = Vec::new();
+    let mut group_end = f32::MIN;
+    for segment in segments {
+        let (start, end) = span(&segment);
+        if groups.is_empty() || start >= group_end + min_gap {
+            groups.push(Vec::new());
+            group_end = end;
+        } else {
+            group_end = group_end.max(end);
+        }
+        if let Some(group) = groups.last_mut() {
+            group.push(segment);
+        }
+    }
+    groups
+}
+
+/// Orders a leaf's segments top to bottom and joins those sharing a baseline into lines
+fn sort_into_lines(mut segments: Vec<Segment>) -> Vec<TextLine> {
+    segments.sort_by(|a, b| b.baseline.total_cmp(&a.baseline).then(a.bbox.x0.total_cmp(&b.bbox.x0)));
+
+    let mut rows: Vec<Vec<Segment>> = Vec::new();
+    for segment in segments {
+        match rows.last_mut() {
+            Some(row) if (row[0].baseline - segment.baseline).abs() < row[0].size.max(segment.size) * 0.4 => row.push(segment),
+            _ => rows.push(vec![segment]),
+        }
+    }
+
+    rows.into_iter()
+        .map(|mut row| {
+            row.sort_by(|a, b| a.bbox.x0.total_cmp(&b.bbox.x0));
+            make_line(row)
+        })
+        .collect()
+}
+
+/// Stream-order lines, starting a new block whenever the text jumps or leaves a paragraph gap
+fn stream_order_blocks(segments: Vec<Segment>) -> Vec<TextBlock> {
+    let mut lines: Vec<Vec<Segment>> = Vec::new();
+    for segment in segments {
+        match lines.last_mut() {
+            Some(line)
+                if (line[0].baseline - segment.baseline).abs() < line[0].size.max(segment.size) * 0.4
+                    && segment.bbox.x0 >= line[line.len() - 1].bbox.x1 =>
+            {
+                line.push(segment)
+            }
+            _ => lines.push(vec![segment]),
+        }
+    }
+
+    let mut blocks: Vec<Vec<TextLine>> = Vec::new();
+    for line in lines.into_iter().map(make_line) {
+        let continues = blocks.last().and_then(|block| block.last()).is_some_and(|previous| {
+            let gap = previous.bbox.y0 - line.bbox.y1;
+            gap > -previous.font_size * 0.5 && gap < previous.font_size.max(line.font_size) * 0.5
+        });
+        match blocks.last_mut() {
+            Some(block) if continues => block.push(line),
+            _ => blocks.push(vec![line]),
+        }
+    }
+
+    blocks.into_iter().filter_map(make_block).collect()
+}
+
+fn make_line(segments: Vec<Segment>) -> TextLine {
+    let bbox = segments.iter().skip(1).fold(segments[0].bbox, |b, s| b.union(&s.bbox));
+    let font_size = segments.iter().map(|s| s.size).fold(0.0, f32::max);
+    let words: Vec<TextWord> = segments.into_iter().flat_map(|s| s.words).collect();
+    let text = words.iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" ");
+    TextLine { text, bbox, font_size, words }
+}
+
+fn make_block(lines: Vec<TextLine>) -> Option<TextBlock> {
+    let first = lines.first()?;
+    let bbox = lines.iter().skip(1).fold(first.bbox, |b, l| b.union(&l.bbox));
+    Some(TextBlock { bbox, lines })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAR_WIDTH: f32 = 6.0;
+
+    fn word(text: &str, x: f32, y: f32) -> TextWord {
+        let char_boxes: Vec<BoundingBox> = (0..text.chars().count())
+            .map(|i| BoundingBox { x0: x + i as f32 * CHAR_WIDTH, y0: y, x1: x + (i + 1) as f32 * CHAR_WIDTH, y1: y + 10.0 })
+            .collect();
+        let bbox = char_boxes.iter().skip(1).fold(char_boxes[0], |bbox, b| bbox.union(b));
+        TextWord { text: text.to_string(), bbox, char_boxes }
+    }
+
+    /// A line of words starting at `x`, one space apart
+    fn line(text: &str, x: f32, y: f32) -> TextLine {
+        let mut words = Vec::new();
+        let mut start = x;
+        for part in text.split(' ') {
+            words.push(word(part, start, y));
+            start += (part.chars().count() + 1) as f32 * CHAR_WIDTH;
+        }
+        let bbox = words.iter().skip(1).fold(words[0].bbox, |bbox, w| bbox.union(&w.bbox));
+        TextLine { text: text.to_string(), bbox, font_size: 10.0, words }
+    }
+
+    fn page(page_number: u32, lines: Vec<TextLine>) -> PageText {
+        let bbox = lines.iter().skip(1).fold(lines[0].bbox, |bbox, l| bbox.union(&l.bbox));
+        PageText { page_number, width: 612.0, height: 792.0, blocks: vec![TextBlock { bbox, lines }] }
+    }
+
+    fn rect(bbox: &BoundingBox) -> (f32, f32, f32, f32) {
+        (bbox.x0, bbox.y0, bbox.x1, bbox.y1)
+    }
+
+    #[test]
+    fn match_across_two_lines_gets_a_rectangle_per_line() {
+        let pages = [page(1, vec![line("Quarterly revenue", 100.0, 700.0), line("grew strongly", 100.0, 686.0)])];
+        let matches = search_pages(&pages, &regex::Regex::new("revenue grew").unwrap(), 5);
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].text, "revenue grew");
+        assert_eq!(matches[0].context, "erly revenue grew stro");
+        let rects: Vec<_> = matches[0].rects.iter().map(rect).collect();
+        assert_eq!(rects, vec![(160.0, 700.0, 202.0, 710.0), (100.0, 686.0, 124.0, 696.0)]);
+    }
+
+    #[test]
+    fn match_inside_a_word_covers_only_its_characters() {
+        let pages = [page(3, vec![line("Überweisung pending", 50.0, 500.0)])];
+        let matches = search_pages(&pages, &regex::Regex::new("weis").unwrap(), 100);
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].page_number, 3);
+        assert_eq!(matches[0].context, "Überweisung pending");
+        assert_eq!(matches[0].rects.iter().map(rect).collect::<Vec<_>>(), vec![(74.0, 500.0, 98.0, 510.0)]);
+    }
+
+    #[test]
+    fn matches_are_reported_per_page_in_order() {
+        let pages = [
+            page(1, vec![line("alpha beta", 0.0, 0.0)]),
+            page(2, vec![line("beta gamma beta", 0.0, 0.0)]),
+        ];
+        let matches = search_pages(&pages, &regex::Regex::new("beta").unwrap(), 0);
+        let found: Vec<_> = matches.iter().map(|m| (m.page_number, rect(&m.rects[0]).0)).collect();
+        assert_eq!(found, vec![(1, 36.0), (2, 0.0), (2, 66.0)]);
+    }
+}
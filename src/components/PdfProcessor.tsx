@@ -40,6 +40,14 @@ interface PdfMetadata {
   all_metadata: Record<string, string>;
 }
 
+interface ExtractedImage {
+  path: string;
+  page_number: number;
+  width: number;
+  height: number;
+  format: string;
+}
+
 interface Highlight {
   id: string;
   x: number;
@@ -294,12 +302,12 @@ function PdfProcessor({ file, multiplePdfs, onReset }: PdfProcessorProps) {
         return;
       }
 
-      const imagePaths = await invoke<string[]>('extract_images', {
+      const images = await invoke<ExtractedImage[]>('extract_images', {
         inputPath: file.path,
         outputDir,
       });
 
-      showToast(`Extracted ${imagePaths.length} images`);
+      showToast(`Extracted ${images.length} images`);
     }, (error) => {
       showToast(`Error: ${error}`);
     });